@@ -0,0 +1,102 @@
+//! Insert latency of the keyspace table, which resizes incrementally, against
+//! `std::collections::HashMap`, which moves every entry at once when it grows.
+//!
+//! Both tables are filled with the same keys while each insert is timed. Resizes are rare, so
+//! they barely move the percentiles of the inserts themselves; what they do to a shard shows in
+//! the response times, replayed here as if requests arrived at a steady rate and queued behind
+//! each other, since every request arriving during a pause waits for it. Run with
+//! `cargo bench --bench dict_resize [-- <keys> [<requests per second>]]`.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/shard/dict.rs"]
+mod dict;
+
+use bytes::Bytes;
+use dict::Dict;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const DEFAULT_KEYS: usize = 4_000_000;
+const DEFAULT_RATE: u64 = 250_000;
+
+fn main() {
+    let mut args = std::env::args().skip(1).filter_map(|arg| arg.parse::<u64>().ok());
+    let keys_count = args.next().map_or(DEFAULT_KEYS, |keys| keys as usize);
+    let rate = args.next().unwrap_or(DEFAULT_RATE);
+    if keys_count == 0 || rate == 0 {
+        eprintln!("the key count and the request rate must both be above 0");
+        std::process::exit(2);
+    }
+    let keys: Vec<Bytes> = (0..keys_count).map(|i| Bytes::from(format!("key:{i}"))).collect();
+
+    let mut map = HashMap::new();
+    let map_times = time_inserts(&keys, |key, value| {
+        map.insert(key, value);
+    });
+    drop(map);
+
+    let mut dict = Dict::default();
+    let dict_times = time_inserts(&keys, |key, value| {
+        dict.insert(key, value);
+    });
+    drop(dict);
+
+    println!("{keys_count} inserts, time per insert");
+    header();
+    report("HashMap", map_times.clone());
+    report("Dict", dict_times.clone());
+
+    let interval = Duration::from_secs(1) / rate as u32;
+    println!("\n{keys_count} inserts arriving at {rate}/s, response time");
+    header();
+    report("HashMap", queued(&map_times, interval));
+    report("Dict", queued(&dict_times, interval));
+}
+
+/// Runs `insert` for every key, returning how long each call took.
+fn time_inserts(keys: &[Bytes], mut insert: impl FnMut(Bytes, u64)) -> Vec<Duration> {
+    keys.iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let started = Instant::now();
+            insert(key.clone(), i as u64);
+            started.elapsed()
+        })
+        .collect()
+}
+
+/// Response times of requests arriving every `interval` and served one at a time in
+/// `service` time each: a request starts once it has arrived and the previous one is done.
+fn queued(service: &[Duration], interval: Duration) -> Vec<Duration> {
+    let mut done = Duration::ZERO;
+    service
+        .iter()
+        .enumerate()
+        .map(|(i, &took)| {
+            let arrived = interval * i as u32;
+            done = done.max(arrived) + took;
+            done - arrived
+        })
+        .collect()
+}
+
+fn header() {
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>10} {:>12}",
+        "table", "p50", "p99", "p99.9", "p99.99", "max"
+    );
+}
+
+fn report(name: &str, mut samples: Vec<Duration>) {
+    samples.sort_unstable();
+    let at = |quantile: f64| samples[((samples.len() - 1) as f64 * quantile) as usize];
+    println!(
+        "{:<8} {:>10.1?} {:>10.1?} {:>10.1?} {:>10.1?} {:>12.1?}",
+        name,
+        at(0.5),
+        at(0.99),
+        at(0.999),
+        at(0.9999),
+        samples[samples.len() - 1],
+    );
+}
@@ -0,0 +1,102 @@
+//! Global allocator that keeps count of the heap in use, for `MEMORY STATS` and
+//! `MEMORY DOCTOR`.
+//!
+//! It forwards to the system allocator and, like Redis' `zmalloc`, tracks the bytes currently
+//! allocated in per-thread counters that are only summed when read, so allocating never
+//! touches a cache line shared with another shard, and samples their peak. Comparing that with
+//! the resident set size the kernel reports gives the fragmentation ratio.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+
+pub struct CountingAllocator;
+
+/// Counter slots; threads past this many share slots round-robin.
+const SLOTS: usize = 64;
+
+/// Bytes allocated less bytes freed by the threads using a slot, padded to a cache line so
+/// that shard threads never contend on it. A slot can go negative when its threads free memory
+/// allocated elsewhere, such as values handed to the lazyfree thread.
+#[repr(align(64))]
+struct Slot(AtomicIsize);
+
+static COUNTERS: [Slot; SLOTS] = [const { Slot(AtomicIsize::new(0)) }; SLOTS];
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SLOT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            count(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            count(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        count(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            count(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Adds `bytes` to the calling thread's slot.
+fn count(bytes: isize) {
+    let slot = SLOT
+        .try_with(|slot| match slot.get() {
+            Some(index) => index,
+            None => {
+                let index = NEXT_SLOT.fetch_add(1, Ordering::Relaxed) % SLOTS;
+                slot.set(Some(index));
+                index
+            }
+        })
+        .unwrap_or(0);
+    COUNTERS[slot].0.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Heap bytes currently allocated by the process, summed over the slots.
+pub fn allocated() -> usize {
+    let total: isize = COUNTERS.iter().map(|slot| slot.0.load(Ordering::Relaxed)).sum();
+    total.max(0) as usize
+}
+
+/// Folds the current heap size into the peak. Every shard runs it from its periodic tick
+/// rather than on each allocation, so a spike shorter than a tick may go unnoticed.
+pub fn record_peak() {
+    PEAK.fetch_max(allocated(), Ordering::Relaxed);
+}
+
+/// Most heap bytes seen allocated since startup; see [`record_peak`].
+pub fn peak_allocated() -> usize {
+    record_peak();
+    PEAK.load(Ordering::Relaxed)
+}
+
+/// Resident set size of the process, if the platform reports it (Linux only).
+pub fn resident() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
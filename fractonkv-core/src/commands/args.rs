@@ -0,0 +1,169 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::errors::CommandExecutionError;
+
+/// Zero-copy cursor over the arguments of a command (everything after the command name).
+///
+/// Both `BlobString` and `SimpleString` frames are accepted, so keys and values may contain
+/// arbitrary bytes regardless of how the client chose to encode them.
+pub struct Args<'a> {
+    name: &'static str,
+    frames: &'a [BytesFrame],
+    pos: usize,
+}
+
+/// A case-insensitive option token such as `EX` or `WITHSCORES`.
+#[derive(Clone, Copy)]
+pub struct Token<'a>(&'a [u8]);
+
+impl Token<'_> {
+    pub fn is(&self, token: &str) -> bool {
+        self.0.eq_ignore_ascii_case(token.as_bytes())
+    }
+
+    /// Builds the error reported when this token is not a subcommand of `container`.
+    pub fn unknown_subcommand(&self, container: &'static str) -> CommandExecutionError {
+        CommandExecutionError::UnknownSubcommand(
+            String::from_utf8_lossy(self.0).into_owned(),
+            container,
+        )
+    }
+
+    /// Builds the error reported for a flag the command does not know.
+    pub fn unsupported_option(&self) -> CommandExecutionError {
+        CommandExecutionError::UnsupportedOption(String::from_utf8_lossy(self.0).into_owned())
+    }
+
+    /// Builds the syntax error reported for an unexpected token.
+    pub fn syntax_error(&self) -> CommandExecutionError {
+        CommandExecutionError::SyntaxError(String::from_utf8_lossy(self.0).into_owned())
+    }
+}
+
+impl<'a> Args<'a> {
+    pub fn new(name: &'static str, frames: &'a [BytesFrame]) -> Self {
+        Self { name, frames, pos: 0 }
+    }
+
+    /// Number of arguments not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.frames.len() - self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn wrong_arity(&self) -> CommandExecutionError {
+        CommandExecutionError::WrongArity(self.name)
+    }
+
+    /// Error for a key-count argument that asks for no keys at all.
+    pub fn no_input_keys(&self) -> CommandExecutionError {
+        CommandExecutionError::NoInputKeys(self.name)
+    }
+
+    /// Error for an expire time that overflows once converted to an absolute deadline.
+    pub fn invalid_expire(&self) -> CommandExecutionError {
+        CommandExecutionError::InvalidExpireIn(self.name)
+    }
+
+    /// Fails with a wrong-arity error unless every argument has been consumed.
+    pub fn finish(&self) -> Result<(), CommandExecutionError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.wrong_arity())
+        }
+    }
+
+    pub fn next_bytes(&mut self) -> Result<&'a Bytes, CommandExecutionError> {
+        let frame = self.frames.get(self.pos).ok_or_else(|| self.wrong_arity())?;
+        self.pos += 1;
+        match frame {
+            BytesFrame::BlobString { data, .. } | BytesFrame::SimpleString { data, .. } => Ok(data),
+            _ => Err(CommandExecutionError::InvalidParams(self.name)),
+        }
+    }
+
+    pub fn next_str(&mut self) -> Result<&'a str, CommandExecutionError> {
+        let bytes = self.next_bytes()?;
+        std::str::from_utf8(bytes).map_err(|_| CommandExecutionError::InvalidParams(self.name))
+    }
+
+    pub fn next_i64(&mut self) -> Result<i64, CommandExecutionError> {
+        parse_i64(self.next_bytes()?).ok_or(CommandExecutionError::NotInteger)
+    }
+
+    pub fn next_f64(&mut self) -> Result<f64, CommandExecutionError> {
+        parse_f64(self.next_bytes()?).ok_or(CommandExecutionError::NotFloat)
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'a>, CommandExecutionError> {
+        Ok(Token(self.next_bytes()?))
+    }
+
+    /// Consumes the next argument only if it equals `token`, ignoring case.
+    pub fn eat_token(&mut self, token: &str) -> bool {
+        let matched = match self.frames.get(self.pos) {
+            Some(BytesFrame::BlobString { data, .. } | BytesFrame::SimpleString { data, .. }) => {
+                Token(data).is(token)
+            }
+            _ => false,
+        };
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    /// Consumes every remaining argument as raw bytes.
+    pub fn rest_bytes(&mut self) -> Result<Vec<&'a Bytes>, CommandExecutionError> {
+        let mut out = Vec::with_capacity(self.remaining());
+        while !self.is_empty() {
+            out.push(self.next_bytes()?);
+        }
+        Ok(out)
+    }
+}
+
+/// Parses a signed 64-bit integer the way Redis does: no whitespace, no `+` sign and no
+/// leading zeros.
+pub fn parse_i64(bytes: &[u8]) -> Option<i64> {
+    let (negative, digits) = match bytes {
+        [b'-', rest @ ..] => (true, rest),
+        _ => (false, bytes),
+    };
+    match digits {
+        [] => return None,
+        [b'0'] if !negative => return Some(0),
+        [b'0', ..] => return None,
+        _ => {}
+    }
+
+    let mut value: i64 = 0;
+    for &b in digits {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        let digit = (b - b'0') as i64;
+        value = value.checked_mul(10)?;
+        value = if negative {
+            value.checked_sub(digit)?
+        } else {
+            value.checked_add(digit)?
+        };
+    }
+    Some(value)
+}
+
+/// Parses a double, accepting `inf`, `+inf` and `-inf` (any case) and rejecting NaN.
+pub fn parse_f64(bytes: &[u8]) -> Option<f64> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    if s.is_empty() || s.starts_with(char::is_whitespace) || s.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let value: f64 = s.parse().ok()?;
+    (!value.is_nan()).then_some(value)
+}
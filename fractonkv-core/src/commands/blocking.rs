@@ -0,0 +1,307 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+use std::time::{Duration, Instant};
+
+use crate::{
+    commands::{
+        CommandKind,
+        args::{Args, parse_f64},
+        list::End,
+        lmove::{move_between, parse_lmpop, pop_many},
+        reply::{array, bulk, double},
+        zset::{remove_if_empty, zset_mut},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        blocking::{BlockedOn, UnblockMode, WaitOutcome, next_wait_seq},
+        clock,
+        shard::{Client, Shard},
+    },
+};
+
+/// What a blocking command pops once one of its keys has data.
+enum BlockingPop {
+    /// `BLPOP`/`BRPOP`: one element from the first non-empty list.
+    List(End),
+    /// `BLMPOP`: up to `count` elements from the first non-empty list.
+    Lists(End, usize),
+    /// `BLMOVE`: one element from the only key into `destination`.
+    Move { destination: Bytes, from: End, to: End },
+    /// `BZPOPMIN`/`BZPOPMAX`: the lowest or highest scored member.
+    SortedSet { max: bool },
+}
+
+/// A parsed blocking command: the keys it waits on, how long, and what it pops.
+pub struct BlockingRequest {
+    keys: Vec<Bytes>,
+    timeout: Option<Duration>,
+    pop: BlockingPop,
+}
+
+impl BlockingRequest {
+    pub fn parse(cmd: CommandKind, mut args: Args) -> Result<Self, CommandExecutionError> {
+        match cmd {
+            CommandKind::Blpop | CommandKind::Brpop => {
+                let (keys, timeout) = keys_then_timeout(&mut args)?;
+                let end = if cmd == CommandKind::Blpop {
+                    End::Left
+                } else {
+                    End::Right
+                };
+                Ok(Self { keys, timeout, pop: BlockingPop::List(end) })
+            }
+            CommandKind::Bzpopmin | CommandKind::Bzpopmax => {
+                let (keys, timeout) = keys_then_timeout(&mut args)?;
+                let pop = BlockingPop::SortedSet { max: cmd == CommandKind::Bzpopmax };
+                Ok(Self { keys, timeout, pop })
+            }
+            CommandKind::Blmove => {
+                let source = args.next_bytes()?.clone();
+                let destination = args.next_bytes()?.clone();
+                let from = End::from_token(args.next_token()?)?;
+                let to = End::from_token(args.next_token()?)?;
+                let timeout = parse_timeout(args.next_bytes()?)?;
+                args.finish()?;
+                let pop = BlockingPop::Move { destination, from, to };
+                Ok(Self { keys: vec![source], timeout, pop })
+            }
+            CommandKind::Blmpop => {
+                let timeout = parse_timeout(args.next_bytes()?)?;
+                let (keys, end, count) = parse_lmpop(&mut args)?;
+                let keys = keys.into_iter().cloned().collect();
+                Ok(Self { keys, timeout, pop: BlockingPop::Lists(end, count) })
+            }
+            _ => unreachable!("{} is not a blocking command", cmd.name()),
+        }
+    }
+
+    /// The type of value the command waits for.
+    fn kind(&self) -> BlockedOn {
+        match self.pop {
+            BlockingPop::SortedSet { .. } => BlockedOn::SortedSet,
+            _ => BlockedOn::List,
+        }
+    }
+
+    /// Makes one non-blocking attempt over the keys in order. `None` means every key was
+    /// empty.
+    async fn try_pop(&self, shard: &Shard) -> Result<Option<BytesFrame>, CommandExecutionError> {
+        if let BlockingPop::Move { destination, from, to } = &self.pop {
+            let popped =
+                move_between(shard, self.keys[0].clone(), destination.clone(), *from, *to).await?;
+            return Ok(popped.map(bulk));
+        }
+
+        for key in &self.keys {
+            let owned = key.clone();
+            let reply = match self.pop {
+                BlockingPop::List(end) => shard
+                    .run_on(shard.owner(key), move |db| pop_many(db, &owned, end, 1))
+                    .await?
+                    .and_then(|mut popped| popped.pop())
+                    .map(|value| array(vec![bulk(key.clone()), bulk(value)])),
+                BlockingPop::Lists(end, count) => shard
+                    .run_on(shard.owner(key), move |db| pop_many(db, &owned, end, count))
+                    .await?
+                    .map(|popped| {
+                        array(vec![
+                            bulk(key.clone()),
+                            array(popped.into_iter().map(bulk).collect()),
+                        ])
+                    }),
+                BlockingPop::SortedSet { max } => shard
+                    .run_on(shard.owner(key), move |db| {
+                        let Some(zset) = zset_mut(db, &owned, clock::now())? else {
+                            return Ok(None);
+                        };
+                        let popped = if max { zset.pop_max() } else { zset.pop_min() };
+                        remove_if_empty(db, &owned);
+                        Ok(popped)
+                    })
+                    .await?
+                    .map(|(member, score)| {
+                        array(vec![bulk(key.clone()), bulk(member), double(score)])
+                    }),
+                BlockingPop::Move { .. } => unreachable!("handled above"),
+            };
+            if reply.is_some() {
+                return Ok(reply);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Runs a blocking command for `client`: pops right away if any key has data, otherwise parks
+/// on the keys' wait queues and retries every time it is woken, until it pops, times out (null
+/// reply), is ended by `CLIENT UNBLOCK` or the client disconnects. In the last case the reply
+/// goes nowhere; it is only released from its keys, so that no element is popped for it.
+pub async fn run_blocking(
+    shard: &Shard,
+    client: &Client<'_>,
+    cmd: CommandKind,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let request = BlockingRequest::parse(cmd, args)?;
+    let deadline = request.timeout.map(|timeout| Instant::now() + timeout);
+    let seq = next_wait_seq();
+    let mut parked = false;
+
+    loop {
+        let attempt = request.try_pop(shard).await;
+        if parked {
+            shard.release_keys(seq, &request.keys).await;
+        }
+        if let Some(reply) = attempt? {
+            return Ok(reply);
+        }
+
+        let outcome =
+            shard.wait_for_keys(client, seq, &request.keys, request.kind(), deadline).await;
+        parked = true;
+        match outcome {
+            WaitOutcome::Woken => continue,
+            WaitOutcome::TimedOut
+            | WaitOutcome::Unblocked(UnblockMode::Timeout)
+            | WaitOutcome::Disconnected => {
+                shard.release_keys(seq, &request.keys).await;
+                return Ok(BytesFrame::Null);
+            }
+            WaitOutcome::Unblocked(UnblockMode::Error) => {
+                shard.release_keys(seq, &request.keys).await;
+                return Err(CommandExecutionError::Unblocked);
+            }
+        }
+    }
+}
+
+/// Parses `key [key ...] timeout`.
+fn keys_then_timeout(
+    args: &mut Args,
+) -> Result<(Vec<Bytes>, Option<Duration>), CommandExecutionError> {
+    let mut rest = args.rest_bytes()?;
+    let timeout = parse_timeout(rest.pop().ok_or_else(|| args.wrong_arity())?)?;
+    Ok((rest.into_iter().cloned().collect(), timeout))
+}
+
+/// Parses a timeout in (fractional) seconds; zero means "block forever".
+fn parse_timeout(bytes: &[u8]) -> Result<Option<Duration>, CommandExecutionError> {
+    let seconds = parse_f64(bytes).ok_or(CommandExecutionError::TimeoutNotFloat)?;
+    if seconds < 0.0 {
+        return Err(CommandExecutionError::TimeoutNegative);
+    }
+    if seconds == 0.0 {
+        return Ok(None);
+    }
+    Duration::try_from_secs_f64(seconds)
+        .map(Some)
+        .map_err(|_| CommandExecutionError::TimeoutNotFloat)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use crate::{
+        commands::reply::{array, bulk, integer},
+        errors::CommandExecutionError,
+        shard::testing::{Client, Cluster},
+    };
+
+    /// Long enough for a command sent just before to have parked its client.
+    const SETTLE: Duration = Duration::from_millis(100);
+
+    async fn blpop(cluster: &Cluster, keys: &[&str]) -> Client {
+        let mut client = cluster.connect().await;
+        client.send(&[&["BLPOP"], keys, &["0"]].concat()).await;
+        tokio::time::sleep(SETTLE).await;
+        client
+    }
+
+    fn popped(key: &str, value: &str) -> BytesFrame {
+        array(vec![bulk(key.to_string().into()), bulk(value.to_string().into())])
+    }
+
+    #[tokio::test]
+    async fn clients_are_served_in_the_order_they_blocked() {
+        let cluster = Cluster::start(2);
+        let mut first = blpop(&cluster, &["fifo"]).await;
+        let mut second = blpop(&cluster, &["fifo"]).await;
+        let mut third = blpop(&cluster, &["fifo"]).await;
+        let mut pusher = cluster.connect().await;
+
+        pusher.call(&["RPUSH", "fifo", "a"]).await;
+        assert_eq!(first.recv().await, popped("fifo", "a"));
+        assert!(!second.replies_within(SETTLE).await);
+
+        pusher.call(&["RPUSH", "fifo", "b", "c"]).await;
+        assert_eq!(second.recv().await, popped("fifo", "b"));
+        assert_eq!(third.recv().await, popped("fifo", "c"));
+        assert_eq!(pusher.call(&["EXISTS", "fifo"]).await, integer(0));
+    }
+
+    #[tokio::test]
+    async fn a_client_blocked_on_keys_of_several_shards_pops_from_the_one_filled() {
+        let cluster = Cluster::start(3);
+        let keys = cluster.keys_on_distinct_shards("multi:", 3);
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let mut blocked = blpop(&cluster, &keys).await;
+        let mut pusher = cluster.connect().await;
+
+        pusher.call(&["RPUSH", keys[2], "v"]).await;
+        assert_eq!(blocked.recv().await, popped(keys[2], "v"));
+
+        // The client left the queues of its other keys, so pushes there stay put.
+        pusher.call(&["RPUSH", keys[0], "w"]).await;
+        tokio::time::sleep(SETTLE).await;
+        assert_eq!(pusher.call(&["LLEN", keys[0]]).await, integer(1));
+    }
+
+    #[tokio::test]
+    async fn client_unblock_ends_the_wait_with_a_timeout_or_an_error() {
+        let cluster = Cluster::start(2);
+        let mut blocked = cluster.connect().await;
+        let BytesFrame::Number { data: id, .. } = blocked.call(&["CLIENT", "ID"]).await else {
+            panic!("CLIENT ID did not reply with a number");
+        };
+        let id = id.to_string();
+        let mut admin = cluster.connect().await;
+
+        blocked.send(&["BLPOP", "unblock", "0"]).await;
+        tokio::time::sleep(SETTLE).await;
+        assert_eq!(admin.call(&["CLIENT", "UNBLOCK", &id, "TIMEOUT"]).await, integer(1));
+        assert_eq!(blocked.recv().await, BytesFrame::Null);
+
+        blocked.send(&["BZPOPMIN", "unblock", "0"]).await;
+        tokio::time::sleep(SETTLE).await;
+        assert_eq!(admin.call(&["CLIENT", "UNBLOCK", &id, "ERROR"]).await, integer(1));
+        assert_eq!(blocked.recv().await, CommandExecutionError::Unblocked.into());
+
+        assert_eq!(admin.call(&["CLIENT", "UNBLOCK", &id]).await, integer(0));
+        // Nobody is left waiting on the key.
+        admin.call(&["RPUSH", "unblock", "v"]).await;
+        tokio::time::sleep(SETTLE).await;
+        assert_eq!(admin.call(&["LLEN", "unblock"]).await, integer(1));
+    }
+
+    #[tokio::test]
+    async fn a_client_that_disconnects_while_blocked_pops_nothing() {
+        let cluster = Cluster::start(2);
+        drop(blpop(&cluster, &["gone"]).await);
+        let mut waiting = blpop(&cluster, &["other"]).await;
+        tokio::time::sleep(SETTLE).await;
+
+        let mut pusher = cluster.connect().await;
+        pusher.call(&["RPUSH", "gone", "v"]).await;
+        tokio::time::sleep(SETTLE).await;
+        assert_eq!(
+            pusher.call(&["LRANGE", "gone", "0", "-1"]).await,
+            array(vec![bulk("v".into())])
+        );
+
+        pusher.call(&["RPUSH", "other", "w"]).await;
+        assert_eq!(waiting.recv().await, popped("other", "w"));
+    }
+}
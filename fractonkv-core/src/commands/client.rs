@@ -0,0 +1,49 @@
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{args::Args, reply::integer},
+    errors::CommandExecutionError,
+    shard::{blocking::UnblockMode, shard::Shard},
+};
+
+/// `CLIENT ID` and `CLIENT UNBLOCK client-id [TIMEOUT | ERROR]`.
+pub async fn handle_client(
+    shard: &Shard,
+    client_id: u64,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let subcommand = args.next_token()?;
+
+    if subcommand.is("ID") {
+        args.finish()?;
+        return Ok(integer(client_id as i64));
+    }
+
+    if subcommand.is("UNBLOCK") {
+        let target = args.next_i64()?;
+        let mode = if args.is_empty() {
+            UnblockMode::Timeout
+        } else {
+            let token = args.next_token()?;
+            if token.is("TIMEOUT") {
+                UnblockMode::Timeout
+            } else if token.is("ERROR") {
+                UnblockMode::Error
+            } else {
+                return Err(token.syntax_error());
+            }
+        };
+        args.finish()?;
+
+        // The client may be connected to any shard; only that one knows it.
+        let mut unblocked = false;
+        for id in 0..shard.peers.len() {
+            unblocked |= shard
+                .run_on_shard(id, move |peer| peer.unblock_client(target as u64, mode))
+                .await;
+        }
+        return Ok(integer(unblocked as i64));
+    }
+
+    Err(subcommand.unknown_subcommand("CLIENT"))
+}
@@ -0,0 +1,44 @@
+use redis_protocol::resp3::types::{BytesFrame, FrameMap};
+
+use crate::{
+    commands::{
+        args::Args,
+        glob::glob_match,
+        reply::{bulk, ok},
+    },
+    config::CONFIG,
+    errors::CommandExecutionError,
+};
+
+/// `CONFIG GET pattern [pattern ...]` and `CONFIG SET name value [name value ...]`.
+pub fn handle_config(mut args: Args) -> Result<BytesFrame, CommandExecutionError> {
+    let subcommand = args.next_token()?;
+
+    if subcommand.is("GET") {
+        let patterns = args.rest_bytes()?;
+        if patterns.is_empty() {
+            return Err(args.wrong_arity());
+        }
+        let settings = CONFIG
+            .get(|name| patterns.iter().any(|pattern| glob_match(pattern, name.as_bytes(), true)));
+        let mut map = FrameMap::new();
+        for (name, value) in settings {
+            map.insert(bulk(name.into()), bulk(value.into()));
+        }
+        return Ok(BytesFrame::Map { data: map, attributes: None });
+    }
+
+    if subcommand.is("SET") {
+        if args.is_empty() || !args.remaining().is_multiple_of(2) {
+            return Err(args.wrong_arity());
+        }
+        let mut pairs = Vec::with_capacity(args.remaining() / 2);
+        while !args.is_empty() {
+            pairs.push((args.next_str()?, args.next_str()?));
+        }
+        CONFIG.set(&pairs)?;
+        return Ok(ok());
+    }
+
+    Err(subcommand.unknown_subcommand("CONFIG"))
+}
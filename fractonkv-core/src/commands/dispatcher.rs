@@ -1,84 +1,418 @@
 use redis_protocol::resp3::types::BytesFrame;
 
 use crate::{
-    commands::{CommandKind, get::handle_get, set::handle_set},
+    commands::{
+        CommandKind,
+        args::Args,
+        blocking::run_blocking,
+        client::handle_client,
+        config::handle_config,
+        expire::{
+            handle_expire, handle_expireat, handle_expireidle, handle_expiresliding,
+            handle_expiretime, handle_persist, handle_pexpire, handle_pexpireat,
+            handle_pexpireidle, handle_pexpiresliding, handle_pexpiretime, handle_pttl, handle_ttl,
+        },
+        get::{handle_get, handle_getdel, handle_getex},
+        hash::{
+            handle_hdel, handle_hexists, handle_hget, handle_hgetall, handle_hincrby,
+            handle_hincrbyfloat, handle_hkeys, handle_hlen, handle_hmget, handle_hmset,
+            handle_hrandfield, handle_hset, handle_hsetnx, handle_hstrlen, handle_hvals,
+        },
+        hash_ttl::{
+            handle_hexpire, handle_hgetex, handle_hpersist, handle_hpexpire, handle_hpttl,
+            handle_hsetex, handle_httl,
+        },
+        incr::{handle_decr, handle_decrby, handle_incr, handle_incrby, handle_incrbyfloat},
+        keyspace::{
+            copy_cross_shard, del_cross_shard, exists_cross_shard, handle_copy, handle_dbsize,
+            handle_del, handle_exists, handle_flush, handle_randomkey, handle_rename,
+            handle_renamenx, handle_touch, handle_type, handle_unlink, rename_cross_shard,
+            renamenx_cross_shard, touch_cross_shard, unlink_cross_shard,
+        },
+        lcs::{handle_lcs, lcs_cross_shard},
+        list::{
+            handle_lindex, handle_linsert, handle_llen, handle_lpop, handle_lpos, handle_lpush,
+            handle_lpushx, handle_lrange, handle_lrem, handle_lset, handle_ltrim, handle_rpop,
+            handle_rpush, handle_rpushx,
+        },
+        lmove::{
+            handle_lmove, handle_lmpop, handle_rpoplpush, lmove_cross_shard, lmpop_cross_shard,
+            rpoplpush_cross_shard,
+        },
+        memory::handle_memory,
+        mget::{handle_mget, mget_cross_shard},
+        mset::{handle_mset, handle_msetnx, mset_cross_shard, msetnx_cross_shard},
+        object::{handle_debug, handle_object},
+        scan::{handle_hscan, handle_keys, handle_scan, handle_sscan, handle_zscan},
+        set::{handle_getset, handle_psetex, handle_set, handle_setex, handle_setnx},
+        sets::{
+            handle_sadd, handle_scard, handle_sdiff, handle_sdiffstore, handle_sinter,
+            handle_sintercard, handle_sinterstore, handle_sismember, handle_smembers,
+            handle_smismember, handle_smove, handle_spop, handle_srandmember, handle_srem,
+            handle_sunion, handle_sunionstore, sdiff_cross_shard, sdiffstore_cross_shard,
+            sinter_cross_shard, sintercard_cross_shard, sinterstore_cross_shard, smove_cross_shard,
+            sunion_cross_shard, sunionstore_cross_shard,
+        },
+        string::{handle_append, handle_getrange, handle_setrange, handle_strlen},
+        zset::{
+            handle_zadd, handle_zcard, handle_zcount, handle_zincrby, handle_zlexcount,
+            handle_zmscore, handle_zpopmax, handle_zpopmin, handle_zrandmember, handle_zrange,
+            handle_zrangebylex, handle_zrangebyscore, handle_zrangestore, handle_zrank,
+            handle_zrem, handle_zremrangebylex, handle_zremrangebyrank, handle_zremrangebyscore,
+            handle_zrevrange, handle_zrevrangebylex, handle_zrevrangebyscore, handle_zrevrank,
+            handle_zscore, zrangestore_cross_shard,
+        },
+        zset_algebra::{
+            handle_zdiff, handle_zdiffstore, handle_zinter, handle_zintercard, handle_zinterstore,
+            handle_zunion, handle_zunionstore, zdiff_cross_shard, zdiffstore_cross_shard,
+            zinter_cross_shard, zintercard_cross_shard, zinterstore_cross_shard,
+            zunion_cross_shard, zunionstore_cross_shard,
+        },
+    },
     errors::CommandExecutionError,
-    shard::types::DataStore,
+    shard::{
+        shard::{Client, Shard},
+        types::DataStore,
+    },
 };
 
+/// Which dispatcher a command goes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Route {
+    /// [`dispatch_connection`]: the command needs the client's connection.
+    Connection,
+    /// [`dispatch_all_shards`]: the command acts on the whole keyspace.
+    AllShards,
+    /// [`dispatch_command`], or [`dispatch_cross_shard`] when its keys span several shards.
+    Keys,
+}
+
+pub fn route(cmd: CommandKind) -> Route {
+    match cmd {
+        CommandKind::Blpop
+        | CommandKind::Brpop
+        | CommandKind::Blmove
+        | CommandKind::Blmpop
+        | CommandKind::Bzpopmin
+        | CommandKind::Bzpopmax
+        | CommandKind::Client => Route::Connection,
+        CommandKind::Dbsize
+        | CommandKind::Flushdb
+        | CommandKind::Flushall
+        | CommandKind::Randomkey
+        | CommandKind::Scan
+        | CommandKind::Keys
+        | CommandKind::Memory => Route::AllShards,
+        _ => Route::Keys,
+    }
+}
+
+/// Runs a command whose keys (if any) are all owned by the shard holding `db`. Commands routed
+/// elsewhere (see [`route`]) fail with an error.
 pub fn dispatch_command(
     cmd: CommandKind,
     args: &[BytesFrame],
     db: &mut DataStore,
 ) -> Result<BytesFrame, CommandExecutionError> {
+    let args = Args::new(cmd.name(), args);
+
     match cmd {
-        CommandKind::Zrange => todo!(),
-        CommandKind::Hincrbyfloat => todo!(),
-        CommandKind::Zunionstore => todo!(),
-        CommandKind::Incr => todo!(),
-        CommandKind::Smembers => todo!(),
-        CommandKind::Zcount => todo!(),
-        CommandKind::Zinterstore => todo!(),
-        CommandKind::Zrevrank => todo!(),
-        CommandKind::Linsert => todo!(),
-        CommandKind::Sdiffstore => todo!(),
-        CommandKind::Spop => todo!(),
-        CommandKind::Hincrby => todo!(),
-        CommandKind::Sdiff => todo!(),
-        CommandKind::Sinterstore => todo!(),
-        CommandKind::Ltrim => todo!(),
-        CommandKind::Zremrangebyscore => todo!(),
-        CommandKind::Lpushx => todo!(),
-        CommandKind::Zincrby => todo!(),
-        CommandKind::Hkeys => todo!(),
+        CommandKind::Zrange => handle_zrange(args, db),
+        CommandKind::Hincrbyfloat => handle_hincrbyfloat(args, db),
+        CommandKind::Zunionstore => handle_zunionstore(args, db),
+        CommandKind::Incr => handle_incr(args, db),
+        CommandKind::Smembers => handle_smembers(args, db),
+        CommandKind::Zcount => handle_zcount(args, db),
+        CommandKind::Zinterstore => handle_zinterstore(args, db),
+        CommandKind::Zrevrank => handle_zrevrank(args, db),
+        CommandKind::Linsert => handle_linsert(args, db),
+        CommandKind::Sdiffstore => handle_sdiffstore(args, db),
+        CommandKind::Spop => handle_spop(args, db),
+        CommandKind::Hincrby => handle_hincrby(args, db),
+        CommandKind::Sdiff => handle_sdiff(args, db),
+        CommandKind::Sinterstore => handle_sinterstore(args, db),
+        CommandKind::Ltrim => handle_ltrim(args, db),
+        CommandKind::Zremrangebyscore => handle_zremrangebyscore(args, db),
+        CommandKind::Lpushx => handle_lpushx(args, db),
+        CommandKind::Zincrby => handle_zincrby(args, db),
+        CommandKind::Hkeys => handle_hkeys(args, db),
         CommandKind::Set => handle_set(args, db),
-        CommandKind::Llen => todo!(),
-        CommandKind::Hvals => todo!(),
-        CommandKind::Smove => todo!(),
-        CommandKind::Mget => todo!(),
-        CommandKind::Zrangebyscore => todo!(),
-        CommandKind::Hlen => todo!(),
-        CommandKind::Zcard => todo!(),
-        CommandKind::Rpush => todo!(),
-        CommandKind::Hget => todo!(),
-        CommandKind::Append => todo!(),
-        CommandKind::Scard => todo!(),
-        CommandKind::Sinter => todo!(),
-        CommandKind::Hdel => todo!(),
-        CommandKind::Sadd => todo!(),
+        CommandKind::Llen => handle_llen(args, db),
+        CommandKind::Hvals => handle_hvals(args, db),
+        CommandKind::Smove => handle_smove(args, db),
+        CommandKind::Mget => handle_mget(args, db),
+        CommandKind::Zrangebyscore => handle_zrangebyscore(args, db),
+        CommandKind::Hlen => handle_hlen(args, db),
+        CommandKind::Zcard => handle_zcard(args, db),
+        CommandKind::Rpush => handle_rpush(args, db),
+        CommandKind::Hget => handle_hget(args, db),
+        CommandKind::Append => handle_append(args, db),
+        CommandKind::Scard => handle_scard(args, db),
+        CommandKind::Sinter => handle_sinter(args, db),
+        CommandKind::Hdel => handle_hdel(args, db),
+        CommandKind::Sadd => handle_sadd(args, db),
         CommandKind::Get => handle_get(args, db),
-        CommandKind::Hmset => todo!(),
-        CommandKind::Decr => todo!(),
-        CommandKind::Srandmember => todo!(),
-        CommandKind::Rpoplpush => todo!(),
-        CommandKind::Zrank => todo!(),
-        CommandKind::Lpop => todo!(),
-        CommandKind::Hmget => todo!(),
-        CommandKind::Zscore => todo!(),
-        CommandKind::Srem => todo!(),
-        CommandKind::Zrevrange => todo!(),
-        CommandKind::Rpop => todo!(),
-        CommandKind::Mset => todo!(),
-        CommandKind::Sismember => todo!(),
-        CommandKind::Zadd => todo!(),
-        CommandKind::Lindex => todo!(),
-        CommandKind::Setnx => todo!(),
-        CommandKind::Sunion => todo!(),
-        CommandKind::Hgetall => todo!(),
-        CommandKind::Getset => todo!(),
-        CommandKind::Zrem => todo!(),
-        CommandKind::Incrby => todo!(),
-        CommandKind::Sunionstore => todo!(),
-        CommandKind::Zrevrangebyscore => todo!(),
-        CommandKind::Strlen => todo!(),
-        CommandKind::Decrby => todo!(),
-        CommandKind::Hexists => todo!(),
-        CommandKind::Lrem => todo!(),
-        CommandKind::Lrange => todo!(),
-        CommandKind::Lpush => todo!(),
-        CommandKind::Hsetnx => todo!(),
-        CommandKind::Zremrangebyrank => todo!(),
-        CommandKind::Rpushx => todo!(),
-        CommandKind::Hset => todo!(),
+        CommandKind::Hmset => handle_hmset(args, db),
+        CommandKind::Decr => handle_decr(args, db),
+        CommandKind::Srandmember => handle_srandmember(args, db),
+        CommandKind::Rpoplpush => handle_rpoplpush(args, db),
+        CommandKind::Zrank => handle_zrank(args, db),
+        CommandKind::Lpop => handle_lpop(args, db),
+        CommandKind::Hmget => handle_hmget(args, db),
+        CommandKind::Zscore => handle_zscore(args, db),
+        CommandKind::Srem => handle_srem(args, db),
+        CommandKind::Zrevrange => handle_zrevrange(args, db),
+        CommandKind::Rpop => handle_rpop(args, db),
+        CommandKind::Mset => handle_mset(args, db),
+        CommandKind::Sismember => handle_sismember(args, db),
+        CommandKind::Zadd => handle_zadd(args, db),
+        CommandKind::Lindex => handle_lindex(args, db),
+        CommandKind::Setnx => handle_setnx(args, db),
+        CommandKind::Sunion => handle_sunion(args, db),
+        CommandKind::Hgetall => handle_hgetall(args, db),
+        CommandKind::Getset => handle_getset(args, db),
+        CommandKind::Zrem => handle_zrem(args, db),
+        CommandKind::Incrby => handle_incrby(args, db),
+        CommandKind::Sunionstore => handle_sunionstore(args, db),
+        CommandKind::Zrevrangebyscore => handle_zrevrangebyscore(args, db),
+        CommandKind::Strlen => handle_strlen(args, db),
+        CommandKind::Decrby => handle_decrby(args, db),
+        CommandKind::Hexists => handle_hexists(args, db),
+        CommandKind::Lrem => handle_lrem(args, db),
+        CommandKind::Lrange => handle_lrange(args, db),
+        CommandKind::Lpush => handle_lpush(args, db),
+        CommandKind::Hsetnx => handle_hsetnx(args, db),
+        CommandKind::Zremrangebyrank => handle_zremrangebyrank(args, db),
+        CommandKind::Rpushx => handle_rpushx(args, db),
+        CommandKind::Hset => handle_hset(args, db),
+        CommandKind::Incrbyfloat => handle_incrbyfloat(args, db),
+        CommandKind::Getrange => handle_getrange(args, db),
+        CommandKind::Setrange => handle_setrange(args, db),
+        CommandKind::Getdel => handle_getdel(args, db),
+        CommandKind::Getex => handle_getex(args, db),
+        CommandKind::Setex => handle_setex(args, db),
+        CommandKind::Psetex => handle_psetex(args, db),
+        CommandKind::Lcs => handle_lcs(args, db),
+        CommandKind::Msetnx => handle_msetnx(args, db),
+        CommandKind::Hstrlen => handle_hstrlen(args, db),
+        CommandKind::Hrandfield => handle_hrandfield(args, db),
+        CommandKind::Expire => handle_expire(args, db),
+        CommandKind::Pexpire => handle_pexpire(args, db),
+        CommandKind::Expireat => handle_expireat(args, db),
+        CommandKind::Pexpireat => handle_pexpireat(args, db),
+        CommandKind::Ttl => handle_ttl(args, db),
+        CommandKind::Pttl => handle_pttl(args, db),
+        CommandKind::Expiretime => handle_expiretime(args, db),
+        CommandKind::Pexpiretime => handle_pexpiretime(args, db),
+        CommandKind::Persist => handle_persist(args, db),
+        CommandKind::Expireidle => handle_expireidle(args, db),
+        CommandKind::Pexpireidle => handle_pexpireidle(args, db),
+        CommandKind::Expiresliding => handle_expiresliding(args, db),
+        CommandKind::Pexpiresliding => handle_pexpiresliding(args, db),
+        CommandKind::Object => handle_object(args, db),
+        CommandKind::Debug => handle_debug(args, db),
+        CommandKind::Hexpire => handle_hexpire(args, db),
+        CommandKind::Hpexpire => handle_hpexpire(args, db),
+        CommandKind::Httl => handle_httl(args, db),
+        CommandKind::Hpttl => handle_hpttl(args, db),
+        CommandKind::Hpersist => handle_hpersist(args, db),
+        CommandKind::Hgetex => handle_hgetex(args, db),
+        CommandKind::Hsetex => handle_hsetex(args, db),
+        CommandKind::Lset => handle_lset(args, db),
+        CommandKind::Lpos => handle_lpos(args, db),
+        CommandKind::Lmove => handle_lmove(args, db),
+        CommandKind::Lmpop => handle_lmpop(args, db),
+        CommandKind::Smismember => handle_smismember(args, db),
+        CommandKind::Sintercard => handle_sintercard(args, db),
+        CommandKind::Zrangestore => handle_zrangestore(args, db),
+        CommandKind::Zrangebylex => handle_zrangebylex(args, db),
+        CommandKind::Zrevrangebylex => handle_zrevrangebylex(args, db),
+        CommandKind::Zlexcount => handle_zlexcount(args, db),
+        CommandKind::Zremrangebylex => handle_zremrangebylex(args, db),
+        CommandKind::Zpopmin => handle_zpopmin(args, db),
+        CommandKind::Zpopmax => handle_zpopmax(args, db),
+        CommandKind::Zmscore => handle_zmscore(args, db),
+        CommandKind::Zrandmember => handle_zrandmember(args, db),
+        CommandKind::Zunion => handle_zunion(args, db),
+        CommandKind::Zinter => handle_zinter(args, db),
+        CommandKind::Zdiff => handle_zdiff(args, db),
+        CommandKind::Zdiffstore => handle_zdiffstore(args, db),
+        CommandKind::Zintercard => handle_zintercard(args, db),
+        CommandKind::Del => handle_del(args, db),
+        CommandKind::Unlink => handle_unlink(args, db),
+        CommandKind::Exists => handle_exists(args, db),
+        CommandKind::Touch => handle_touch(args, db),
+        CommandKind::Type => handle_type(args, db),
+        CommandKind::Rename => handle_rename(args, db),
+        CommandKind::Renamenx => handle_renamenx(args, db),
+        CommandKind::Copy => handle_copy(args, db),
+        CommandKind::Hscan => handle_hscan(args, db),
+        CommandKind::Sscan => handle_sscan(args, db),
+        CommandKind::Zscan => handle_zscan(args, db),
+        CommandKind::Config => handle_config(args),
+        CommandKind::Dbsize
+        | CommandKind::Flushdb
+        | CommandKind::Flushall
+        | CommandKind::Randomkey
+        | CommandKind::Scan
+        | CommandKind::Keys
+        | CommandKind::Memory
+        | CommandKind::Blpop
+        | CommandKind::Brpop
+        | CommandKind::Blmove
+        | CommandKind::Blmpop
+        | CommandKind::Bzpopmin
+        | CommandKind::Bzpopmax
+        | CommandKind::Client => Err(CommandExecutionError::WrongContext(cmd.name())),
+    }
+}
+
+/// Runs `cmd` with `args` against `db` and returns its reply, failing the test if the command
+/// errors.
+#[cfg(test)]
+pub fn run(db: &mut DataStore, cmd: CommandKind, args: &[&str]) -> BytesFrame {
+    try_run(db, cmd, args).unwrap_or_else(|err| panic!("{} failed: {err}", cmd.name()))
+}
+
+/// Runs `cmd` with `args` against `db` and returns the error message it replies with, failing
+/// the test if the command succeeds.
+#[cfg(test)]
+pub fn run_err(db: &mut DataStore, cmd: CommandKind, args: &[&str]) -> String {
+    match try_run(db, cmd, args) {
+        Ok(reply) => panic!("{} succeeded with {reply:?}", cmd.name()),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+fn try_run(
+    db: &mut DataStore,
+    cmd: CommandKind,
+    args: &[&str],
+) -> Result<BytesFrame, CommandExecutionError> {
+    let args: Vec<BytesFrame> = args
+        .iter()
+        .map(|arg| crate::commands::reply::bulk(bytes::Bytes::copy_from_slice(arg.as_bytes())))
+        .collect();
+    dispatch_command(cmd, &args, db)
+}
+
+/// Runs a command whose keys are owned by more than one shard. Only commands with a cross-shard
+/// executor are accepted; the rest fail with `CROSSSLOT`.
+pub async fn dispatch_cross_shard(
+    shard: &Shard,
+    cmd: CommandKind,
+    args: &[BytesFrame],
+) -> Result<BytesFrame, CommandExecutionError> {
+    let args = Args::new(cmd.name(), args);
+
+    match cmd {
+        CommandKind::Mget => mget_cross_shard(shard, args).await,
+        CommandKind::Mset => mset_cross_shard(shard, args).await,
+        CommandKind::Msetnx => msetnx_cross_shard(shard, args).await,
+        CommandKind::Lcs => lcs_cross_shard(shard, args).await,
+        CommandKind::Rpoplpush => rpoplpush_cross_shard(shard, args).await,
+        CommandKind::Lmove => lmove_cross_shard(shard, args).await,
+        CommandKind::Lmpop => lmpop_cross_shard(shard, args).await,
+        CommandKind::Smove => smove_cross_shard(shard, args).await,
+        CommandKind::Sinter => sinter_cross_shard(shard, args).await,
+        CommandKind::Sunion => sunion_cross_shard(shard, args).await,
+        CommandKind::Sdiff => sdiff_cross_shard(shard, args).await,
+        CommandKind::Sinterstore => sinterstore_cross_shard(shard, args).await,
+        CommandKind::Sunionstore => sunionstore_cross_shard(shard, args).await,
+        CommandKind::Sdiffstore => sdiffstore_cross_shard(shard, args).await,
+        CommandKind::Sintercard => sintercard_cross_shard(shard, args).await,
+        CommandKind::Zrangestore => zrangestore_cross_shard(shard, args).await,
+        CommandKind::Zunion => zunion_cross_shard(shard, args).await,
+        CommandKind::Zinter => zinter_cross_shard(shard, args).await,
+        CommandKind::Zdiff => zdiff_cross_shard(shard, args).await,
+        CommandKind::Zunionstore => zunionstore_cross_shard(shard, args).await,
+        CommandKind::Zinterstore => zinterstore_cross_shard(shard, args).await,
+        CommandKind::Zdiffstore => zdiffstore_cross_shard(shard, args).await,
+        CommandKind::Zintercard => zintercard_cross_shard(shard, args).await,
+        CommandKind::Del => del_cross_shard(shard, args).await,
+        CommandKind::Unlink => unlink_cross_shard(shard, args).await,
+        CommandKind::Exists => exists_cross_shard(shard, args).await,
+        CommandKind::Touch => touch_cross_shard(shard, args).await,
+        CommandKind::Rename => rename_cross_shard(shard, args).await,
+        CommandKind::Renamenx => renamenx_cross_shard(shard, args).await,
+        CommandKind::Copy => copy_cross_shard(shard, args).await,
+        _ => Err(CommandExecutionError::CrossShard),
+    }
+}
+
+/// Runs the commands that need the client's connection rather than just a store: blocking
+/// pops, which park the connection, and `CLIENT`. Other commands fail with an error.
+pub async fn dispatch_connection(
+    shard: &Shard,
+    client: &Client<'_>,
+    cmd: CommandKind,
+    args: &[BytesFrame],
+) -> Result<BytesFrame, CommandExecutionError> {
+    let args = Args::new(cmd.name(), args);
+
+    match cmd {
+        CommandKind::Blpop
+        | CommandKind::Brpop
+        | CommandKind::Blmove
+        | CommandKind::Blmpop
+        | CommandKind::Bzpopmin
+        | CommandKind::Bzpopmax => run_blocking(shard, client, cmd, args).await,
+        CommandKind::Client => handle_client(shard, client.id, args).await,
+        _ => Err(CommandExecutionError::WrongContext(cmd.name())),
+    }
+}
+
+/// Runs the commands that act on the whole keyspace rather than on keys they name, fanning out
+/// to every shard. `MEMORY` is among them as only its `USAGE` subcommand is about a single key.
+/// Other commands fail with an error.
+pub async fn dispatch_all_shards(
+    shard: &Shard,
+    cmd: CommandKind,
+    args: &[BytesFrame],
+) -> Result<BytesFrame, CommandExecutionError> {
+    let args = Args::new(cmd.name(), args);
+
+    match cmd {
+        CommandKind::Dbsize => handle_dbsize(shard, args).await,
+        CommandKind::Flushdb | CommandKind::Flushall => handle_flush(shard, args).await,
+        CommandKind::Randomkey => handle_randomkey(shard, args).await,
+        CommandKind::Scan => handle_scan(shard, args).await,
+        CommandKind::Keys => handle_keys(shard, args).await,
+        CommandKind::Memory => handle_memory(shard, args).await,
+        _ => Err(CommandExecutionError::WrongContext(cmd.name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Route, route, run_err};
+    use crate::{commands::CommandKind, shard::types::DataStore};
+
+    #[test]
+    fn commands_routed_elsewhere_fail_on_a_store_instead_of_panicking() {
+        let mut db = DataStore::default();
+        let elsewhere = [
+            "DBSIZE",
+            "FLUSHDB",
+            "FLUSHALL",
+            "RANDOMKEY",
+            "SCAN",
+            "KEYS",
+            "MEMORY",
+            "BLPOP",
+            "BRPOP",
+            "BLMOVE",
+            "BLMPOP",
+            "BZPOPMIN",
+            "BZPOPMAX",
+            "CLIENT",
+        ];
+        for name in elsewhere {
+            let cmd: CommandKind = name.parse().unwrap();
+            assert_ne!(route(cmd), Route::Keys, "{name}");
+            let err = run_err(&mut db, cmd, &["k", "0"]);
+            assert_eq!(err, format!("ERR '{}' command is not allowed in this context", cmd.name()));
+        }
     }
 }
@@ -0,0 +1,347 @@
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::{Args, Token},
+        reply::integer,
+        set::positive_ms,
+    },
+    config::CONFIG,
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        types::{DataStore, lookup, lookup_mut},
+    },
+};
+
+/// Replies of `TTL`, `PTTL`, `EXPIRETIME` and `PEXPIRETIME` for keys without a deadline.
+const NO_KEY: i64 = -2;
+const NO_TTL: i64 = -1;
+
+/// `NX | XX | GT | LT` guard of the expire commands. A missing TTL counts as an infinite one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExpireCondition {
+    #[default]
+    Always,
+    /// Only set a deadline when there is none.
+    Nx,
+    /// Only replace an existing deadline.
+    Xx,
+    /// Only move the deadline later.
+    Gt,
+    /// Only move the deadline earlier.
+    Lt,
+}
+
+impl ExpireCondition {
+    pub fn from_token(token: Token) -> Option<Self> {
+        if token.is("NX") {
+            Some(Self::Nx)
+        } else if token.is("XX") {
+            Some(Self::Xx)
+        } else if token.is("GT") {
+            Some(Self::Gt)
+        } else if token.is("LT") {
+            Some(Self::Lt)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `new` may replace the `current` deadline.
+    pub fn allows<T: Ord>(self, current: Option<T>, new: T) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Nx => current.is_none(),
+            Self::Xx => current.is_some(),
+            Self::Gt => current.is_some_and(|current| new > current),
+            Self::Lt => current.is_none_or(|current| new < current),
+        }
+    }
+}
+
+/// `EXPIRE key seconds [NX | XX | GT | LT]`.
+pub fn handle_expire(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire(&mut args, db, 1000, false)
+}
+
+/// `PEXPIRE key milliseconds [NX | XX | GT | LT]`.
+pub fn handle_pexpire(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire(&mut args, db, 1, false)
+}
+
+/// `EXPIREAT key unix-time-seconds [NX | XX | GT | LT]`.
+pub fn handle_expireat(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire(&mut args, db, 1000, true)
+}
+
+/// `PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]`.
+pub fn handle_pexpireat(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire(&mut args, db, 1, true)
+}
+
+pub fn handle_ttl(mut args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    ttl(&mut args, db, |deadline, now| (deadline.saturating_sub(now) + 500) / 1000)
+}
+
+pub fn handle_pttl(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    ttl(&mut args, db, |deadline, now| deadline.saturating_sub(now))
+}
+
+pub fn handle_expiretime(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    ttl(&mut args, db, |deadline, _| deadline / 1000)
+}
+
+pub fn handle_pexpiretime(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    ttl(&mut args, db, |deadline, _| deadline)
+}
+
+pub fn handle_persist(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let persisted = lookup_mut(db, key, clock::now()).is_some_and(|obj| obj.persist());
+    Ok(integer(persisted as i64))
+}
+
+/// `EXPIREIDLE key seconds`: the key expires once it goes that long without an access, on
+/// top of any TTL it has.
+pub fn handle_expireidle(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire_idle(&mut args, db, 1000)
+}
+
+/// `PEXPIREIDLE key milliseconds`.
+pub fn handle_pexpireidle(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire_idle(&mut args, db, 1)
+}
+
+/// `EXPIRESLIDING key seconds`: replaces the TTL with one that restarts on every access.
+pub fn handle_expiresliding(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire_sliding(&mut args, db, 1000)
+}
+
+/// `PEXPIRESLIDING key milliseconds`.
+pub fn handle_pexpiresliding(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    expire_sliding(&mut args, db, 1)
+}
+
+fn expire_idle(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: i64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let timeout = positive_ms(args.next_i64()?, unit_ms)?;
+    args.finish()?;
+
+    let Some(obj) = lookup_mut(db, key, clock::now()) else {
+        return Ok(integer(0));
+    };
+    obj.idle_timeout = Some(timeout);
+    Ok(integer(1))
+}
+
+fn expire_sliding(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: i64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let ttl = positive_ms(args.next_i64()?, unit_ms)?;
+    args.finish()?;
+
+    let now = clock::now();
+    let Some(obj) = lookup_mut(db, key, now) else {
+        return Ok(integer(0));
+    };
+    obj.set_sliding_ttl(ttl, now);
+    Ok(integer(1))
+}
+
+/// Shared body of the `EXPIRE` family. `amount` is in units of `unit_ms` and, when `absolute`
+/// is set, counts from the Unix epoch rather than from now. A deadline that has already
+/// passed deletes the key, once the conditions allow it.
+fn expire(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: i64,
+    absolute: bool,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let amount = args.next_i64()?;
+    let conditions = parse_conditions(args)?;
+
+    let now = clock::now();
+    let ms = amount.checked_mul(unit_ms).ok_or_else(|| args.invalid_expire())?;
+    let deadline = if absolute {
+        ms
+    } else {
+        ms.checked_add(now as i64).ok_or_else(|| args.invalid_expire())?
+    };
+    // Deadlines before the epoch are just as much in the past as any other.
+    let deadline = UnixMillis::try_from(deadline).unwrap_or(0);
+
+    let Some(obj) = lookup_mut(db, key, now) else {
+        return Ok(integer(0));
+    };
+    let current = obj.deadline();
+    if !conditions.iter().all(|condition| condition.allows(current, deadline)) {
+        return Ok(integer(0));
+    }
+    if deadline <= now {
+        db.delete(key, CONFIG.lazyfree_lazy_expire());
+    } else {
+        obj.set_expires_at(Some(deadline));
+    }
+    Ok(integer(1))
+}
+
+/// Shared body of `TTL` and friends: `reply` turns the key's deadline and the current time
+/// into the answer.
+fn ttl(
+    args: &mut Args,
+    db: &mut DataStore,
+    reply: impl FnOnce(UnixMillis, UnixMillis) -> u64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let now = clock::now();
+    Ok(integer(match lookup(db, key, now) {
+        None => NO_KEY,
+        Some(obj) => match obj.deadline() {
+            Some(deadline) => reply(deadline, now) as i64,
+            None => NO_TTL,
+        },
+    }))
+}
+
+/// Parses the trailing `NX | XX | GT | LT` flags. Unlike the hash-field commands, `XX` may be
+/// combined with `GT` or `LT`, and every flag given must hold for the deadline to change.
+fn parse_conditions(args: &mut Args) -> Result<Vec<ExpireCondition>, CommandExecutionError> {
+    let mut conditions = Vec::new();
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        let condition =
+            ExpireCondition::from_token(token).ok_or_else(|| token.unsupported_option())?;
+        if !conditions.contains(&condition) {
+            conditions.push(condition);
+        }
+    }
+    if conditions.contains(&ExpireCondition::Nx) && conditions.len() > 1 {
+        return Err(CommandExecutionError::ExpireNxAndOthers);
+    }
+    if conditions.contains(&ExpireCondition::Gt) && conditions.contains(&ExpireCondition::Lt) {
+        return Err(CommandExecutionError::ExpireGtLt);
+    }
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::run,
+            reply::{bulk, integer},
+        },
+        shard::{
+            clock::{TEST_START as START, manual_clock},
+            types::DataStore,
+        },
+    };
+
+    #[test]
+    fn set_ex_expires_once_the_clock_reaches_the_deadline() {
+        let clock = manual_clock();
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Set, &["k", "v", "EX", "10"]);
+
+        clock.advance(9_999);
+        assert_eq!(run(&mut db, CommandKind::Pttl, &["k"]), integer(1));
+        assert_eq!(run(&mut db, CommandKind::Get, &["k"]), bulk("v".into()));
+
+        clock.advance(1);
+        assert_eq!(run(&mut db, CommandKind::Get, &["k"]), BytesFrame::Null);
+        assert_eq!(db.len(), 0);
+    }
+
+    #[test]
+    fn set_pxat_keeps_the_absolute_deadline() {
+        let clock = manual_clock();
+        let mut db = DataStore::default();
+        let deadline = (START + 500).to_string();
+        run(&mut db, CommandKind::Set, &["k", "v", "PXAT", &deadline]);
+
+        clock.advance(100);
+        let expected = integer(START as i64 + 500);
+        assert_eq!(run(&mut db, CommandKind::Pexpiretime, &["k"]), expected);
+        assert_eq!(run(&mut db, CommandKind::Pttl, &["k"]), integer(400));
+
+        clock.advance(400);
+        assert_eq!(run(&mut db, CommandKind::Exists, &["k"]), integer(0));
+    }
+
+    #[test]
+    fn expire_conditions_compare_against_the_current_ttl() {
+        manual_clock();
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Set, &["k", "v"]);
+        let expire = |db: &mut DataStore, args: &[&str]| {
+            run(db, CommandKind::Expire, &[&["k"], args].concat())
+        };
+
+        // No TTL counts as an infinite one.
+        assert_eq!(expire(&mut db, &["100", "XX"]), integer(0));
+        assert_eq!(expire(&mut db, &["100", "GT"]), integer(0));
+        assert_eq!(expire(&mut db, &["100", "NX"]), integer(1));
+        assert_eq!(expire(&mut db, &["200", "NX"]), integer(0));
+        assert_eq!(expire(&mut db, &["50", "GT"]), integer(0));
+        assert_eq!(expire(&mut db, &["200", "GT"]), integer(1));
+        assert_eq!(expire(&mut db, &["300", "LT"]), integer(0));
+        assert_eq!(expire(&mut db, &["150", "XX", "LT"]), integer(1));
+        assert_eq!(run(&mut db, CommandKind::Ttl, &["k"]), integer(150));
+
+        run(&mut db, CommandKind::Persist, &["k"]);
+        assert_eq!(expire(&mut db, &["100", "LT"]), integer(1));
+        assert_eq!(run(&mut db, CommandKind::Ttl, &["k"]), integer(100));
+    }
+}
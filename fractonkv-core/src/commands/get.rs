@@ -1,27 +1,92 @@
 use redis_protocol::resp3::types::BytesFrame;
-use std::time::Instant;
 
-use crate::{errors::CommandExecutionError, shard::types::DataStore};
+use crate::{
+    commands::{
+        args::Args,
+        reply::bulk,
+        set::{SetExpiry, parse_expiry, parse_idle_timeout},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        types::{DataStore, lookup_mut},
+    },
+};
 
-pub fn handle_get(
-    args: &[BytesFrame],
+pub fn handle_get(mut args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let now = clock::now();
+    match lookup_mut(db, key, now) {
+        Some(v) => Ok(bulk(v.data.string_bytes().ok_or(CommandExecutionError::WrongType)?)),
+        None => Ok(BytesFrame::Null),
+    }
+}
+
+pub fn handle_getdel(
+    mut args: Args,
     db: &mut DataStore,
 ) -> Result<BytesFrame, CommandExecutionError> {
-    let key = match args.first() {
-        Some(BytesFrame::SimpleString { data, .. }) => data,
-        _ => return Err(CommandExecutionError::WrongArity("GET")),
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let value = match lookup_mut(db, key, clock::now()) {
+        Some(v) => v.data.string_bytes().ok_or(CommandExecutionError::WrongType)?,
+        None => return Ok(BytesFrame::Null),
     };
+    db.remove(key);
+    Ok(bulk(value))
+}
+
+pub fn handle_getex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
 
-    match db.get_mut(key) {
-        Some(v) => {
-            if let Some(ttl) = v.ttl
-                && Instant::now() >= v.created_at + ttl
-            {
-                return Ok(BytesFrame::Null);
+    // `None` leaves the TTL alone.
+    let now = clock::now();
+    let mut expiry: Option<SetExpiry> = None;
+    let mut idle_timeout = None;
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if let Some(timeout) = parse_idle_timeout(token, &mut args)? {
+            if idle_timeout.is_some() {
+                return Err(token.syntax_error());
             }
-            v.last_accessed = Instant::now();
-            Ok(v.data.to_bytes_frame())
+            idle_timeout = Some(timeout);
+            continue;
         }
-        None => Ok(BytesFrame::Null),
+        if expiry.is_some() {
+            return Err(token.syntax_error());
+        }
+        expiry = Some(if token.is("PERSIST") {
+            SetExpiry::Persist
+        } else {
+            parse_expiry(token, &mut args, now)?.ok_or_else(|| token.syntax_error())?
+        });
+    }
+
+    let Some(v) = lookup_mut(db, key, now) else {
+        return Ok(BytesFrame::Null);
+    };
+    let value = v.data.string_bytes().ok_or(CommandExecutionError::WrongType)?;
+
+    match expiry {
+        Some(SetExpiry::At(deadline)) if deadline <= now => {
+            db.remove(key);
+            return Ok(bulk(value));
+        }
+        Some(SetExpiry::At(deadline)) => v.set_expires_at(Some(deadline)),
+        Some(SetExpiry::Sliding(ttl)) => v.set_sliding_ttl(ttl, now),
+        Some(SetExpiry::Persist) => {
+            v.persist();
+        }
+        Some(SetExpiry::KeepTtl) | None => {}
+    }
+    if idle_timeout.is_some() {
+        v.idle_timeout = idle_timeout;
     }
+    Ok(bulk(value))
 }
@@ -0,0 +1,182 @@
+//! Glob-style pattern matching for `MATCH` and `KEYS`, a port of Redis' `stringmatchlen`.
+//!
+//! `*` matches any run of bytes, `?` any single byte, `[...]` a set of bytes with `^` for
+//! negation and `a-z` for ranges, and `\` escapes the next byte.
+
+/// Recursion depth after which a pattern is considered abusive and fails to match.
+const MAX_NESTING: usize = 1000;
+
+/// Whether `string` matches the glob `pattern` in full.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    // `*` on its own matches everything, including the empty string.
+    if pattern == b"*" {
+        return true;
+    }
+    let mut skip_longer_matches = false;
+    matches(pattern, string, nocase, &mut skip_longer_matches, 0)
+}
+
+fn matches(
+    mut pattern: &[u8],
+    mut string: &[u8],
+    nocase: bool,
+    skip_longer_matches: &mut bool,
+    nesting: usize,
+) -> bool {
+    if nesting > MAX_NESTING {
+        return false;
+    }
+    let fold = |c: u8| if nocase { c.to_ascii_lowercase() } else { c };
+
+    while let (Some(&p), Some(&s)) = (pattern.first(), string.first()) {
+        match p {
+            b'*' => {
+                while pattern.get(1) == Some(&b'*') {
+                    pattern = &pattern[1..];
+                }
+                if pattern.len() == 1 {
+                    return true;
+                }
+                while !string.is_empty() {
+                    if matches(&pattern[1..], string, nocase, skip_longer_matches, nesting + 1) {
+                        return true;
+                    }
+                    if *skip_longer_matches {
+                        return false;
+                    }
+                    string = &string[1..];
+                }
+                // The rest of the pattern matches nowhere in the rest of the string, so
+                // letting an earlier `*` swallow more bytes cannot help either.
+                *skip_longer_matches = true;
+                return false;
+            }
+            b'?' => pattern = &pattern[1..],
+            b'[' => {
+                pattern = &pattern[1..];
+                let negate = pattern.first() == Some(&b'^');
+                if negate {
+                    pattern = &pattern[1..];
+                }
+                let mut matched = false;
+                loop {
+                    match *pattern {
+                        [b'\\', escaped, ref rest @ ..] => {
+                            matched |= escaped == s;
+                            pattern = rest;
+                        }
+                        [b']', ref rest @ ..] => {
+                            pattern = rest;
+                            break;
+                        }
+                        // An unterminated set ends with the pattern.
+                        [] => break,
+                        [start, b'-', end, ref rest @ ..] => {
+                            let (low, high) = (fold(start.min(end)), fold(start.max(end)));
+                            matched |= (low..=high).contains(&fold(s));
+                            pattern = rest;
+                        }
+                        [c, ref rest @ ..] => {
+                            matched |= fold(c) == fold(s);
+                            pattern = rest;
+                        }
+                    }
+                }
+                if matched == negate {
+                    return false;
+                }
+            }
+            _ => {
+                if p == b'\\' && pattern.len() >= 2 {
+                    pattern = &pattern[1..];
+                }
+                if fold(pattern[0]) != fold(s) {
+                    return false;
+                }
+                pattern = &pattern[1..];
+            }
+        }
+        string = &string[1..];
+
+        if string.is_empty() {
+            while pattern.first() == Some(&b'*') {
+                pattern = &pattern[1..];
+            }
+            break;
+        }
+    }
+    pattern.is_empty() && string.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn matches_like_stringmatchlen() {
+        let cases: &[(&str, &str, bool, bool)] = &[
+            // `*` and `?`.
+            ("*", "", false, true),
+            ("*", "anything", false, true),
+            ("", "", false, true),
+            ("", "a", false, false),
+            ("a*", "", false, false),
+            ("h*llo", "hllo", false, true),
+            ("h*llo", "heeeello", false, true),
+            ("h*llo", "hello!", false, false),
+            ("*llo*", "hello world", false, true),
+            ("**a**", "a", false, true),
+            ("h?llo", "hello", false, true),
+            ("h?llo", "hllo", false, false),
+            ("???", "ab", false, false),
+            ("a*a*a*a*a*a*a*a*b", &"a".repeat(64), false, false),
+            // Sets, negated sets and ranges.
+            ("h[ae]llo", "hallo", false, true),
+            ("h[ae]llo", "hillo", false, false),
+            ("h[^e]llo", "hallo", false, true),
+            ("h[^e]llo", "hello", false, false),
+            ("h[a-b]llo", "hbllo", false, true),
+            ("h[a-b]llo", "hcllo", false, false),
+            ("h[b-a]llo", "hallo", false, true),
+            ("[^a-z]", "Q", false, true),
+            ("[^a-z]", "q", false, false),
+            ("[^a-z]", "", false, false),
+            ("[a-c-e]", "-", false, true),
+            // Escapes, inside and outside sets.
+            ("h\\*llo", "h*llo", false, true),
+            ("h\\*llo", "hello", false, false),
+            ("\\?", "?", false, true),
+            ("\\?", "a", false, false),
+            ("[\\]]", "]", false, true),
+            ("[a\\-z]", "-", false, true),
+            ("[a\\-z]", "b", false, false),
+            ("a\\", "a\\", false, true),
+            // An unterminated set ends with the pattern.
+            ("[a", "a", false, true),
+            ("[a", "b", false, false),
+            ("[", "a", false, false),
+            ("[^", "a", false, true),
+            ("x[ab", "xb", false, true),
+            // nocase folds literals, sets and ranges alike.
+            ("HeLLo", "hello", true, true),
+            ("HeLLo", "hello", false, false),
+            ("h[AE]llo", "hello", true, true),
+            ("[A-Z]", "q", true, true),
+            ("[^a-z]", "Q", true, false),
+            ("h\\Ello", "hello", true, true),
+        ];
+        for &(pattern, string, nocase, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), string.as_bytes(), nocase),
+                expected,
+                "{pattern:?} against {string:?} (nocase: {nocase})"
+            );
+        }
+    }
+
+    #[test]
+    fn deeply_nested_patterns_fail_instead_of_overflowing() {
+        let pattern = "*a".repeat(2000);
+        assert!(!glob_match(pattern.as_bytes(), "a".repeat(2000).as_bytes(), false));
+    }
+}
@@ -0,0 +1,336 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::{BytesFrame, FrameMap};
+
+use crate::{
+    commands::{
+        args::{Args, parse_f64, parse_i64},
+        random::sample,
+        reply::{array, bulk, bulk_or_null, format_f64, integer, ok},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        types::{DataKind, DataStore, HashValue, StoreObject, lookup_mut},
+    },
+};
+
+pub fn handle_hset(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let pairs = parse_field_values(&mut args)?;
+
+    let hash = hash_or_create(db, key, clock::now())?;
+    let added = pairs
+        .into_iter()
+        .filter(|(field, value)| hash.insert((*field).clone(), (*value).clone()))
+        .count();
+    Ok(integer(added as i64))
+}
+
+pub fn handle_hmset(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let pairs = parse_field_values(&mut args)?;
+
+    let hash = hash_or_create(db, key, clock::now())?;
+    for (field, value) in pairs {
+        hash.insert(field.clone(), value.clone());
+    }
+    Ok(ok())
+}
+
+pub fn handle_hsetnx(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    let value = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_or_create(db, key, clock::now())?;
+    if hash.contains_key(field) {
+        return Ok(integer(0));
+    }
+    hash.insert(field.clone(), value.clone());
+    Ok(integer(1))
+}
+
+pub fn handle_hget(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(bulk_or_null(hash.and_then(|hash| hash.get(field))))
+}
+
+pub fn handle_hmget(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let fields = args.rest_bytes()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(array(
+        fields
+            .into_iter()
+            .map(|field| bulk_or_null(hash.as_ref().and_then(|hash| hash.get(field))))
+            .collect(),
+    ))
+}
+
+pub fn handle_hdel(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let fields = args.rest_bytes()?;
+
+    let Some(hash) = hash_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = fields.into_iter().filter(|field| hash.remove(field)).count();
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_hexists(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(integer(hash.is_some_and(|hash| hash.contains_key(field)) as i64))
+}
+
+pub fn handle_hlen(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(integer(hash.map_or(0, |hash| hash.len()) as i64))
+}
+
+pub fn handle_hstrlen(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    let len = hash.and_then(|hash| hash.get(field)).map_or(0, |value| value.len());
+    Ok(integer(len as i64))
+}
+
+pub fn handle_hkeys(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(array(hash.map_or_else(Vec::new, |hash| hash.keys().map(bulk).collect())))
+}
+
+pub fn handle_hvals(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    Ok(array(hash.map_or_else(Vec::new, |hash| hash.values().map(bulk).collect())))
+}
+
+/// `HGETALL` replies with a RESP3 map of field to value.
+pub fn handle_hgetall(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let mut map = FrameMap::new();
+    if let Some(hash) = hash_mut(db, key, clock::now())? {
+        for (field, value) in hash.iter() {
+            map.insert(bulk(field), bulk(value));
+        }
+    }
+    Ok(BytesFrame::Map { data: map, attributes: None })
+}
+
+pub fn handle_hincrby(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    let delta = args.next_i64()?;
+    args.finish()?;
+
+    let hash = hash_or_create(db, key, clock::now())?;
+    let current = match hash.get(field) {
+        Some(value) => parse_i64(&value).ok_or(CommandExecutionError::HashNotInteger)?,
+        None => 0,
+    };
+    let updated = current.checked_add(delta).ok_or(CommandExecutionError::Overflow)?;
+    hash.update(field.clone(), Bytes::from(updated.to_string()));
+    Ok(integer(updated))
+}
+
+pub fn handle_hincrbyfloat(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let field = args.next_bytes()?;
+    let delta = args.next_f64()?;
+    args.finish()?;
+
+    if !delta.is_finite() {
+        return Err(CommandExecutionError::NanOrInfinity);
+    }
+
+    // Nothing is created until the result is known to be storable.
+    let now = clock::now();
+    let current = match hash_mut(db, key, now)?.and_then(|hash| hash.get(field)) {
+        Some(value) => parse_f64(&value).ok_or(CommandExecutionError::HashNotFloat)?,
+        None => 0.0,
+    };
+    let updated = current + delta;
+    if !updated.is_finite() {
+        return Err(CommandExecutionError::NanOrInfinity);
+    }
+
+    let formatted = Bytes::from(format_f64(updated));
+    hash_or_create(db, key, now)?.update(field.clone(), formatted.clone());
+    Ok(bulk(formatted))
+}
+
+/// `HRANDFIELD key [count [WITHVALUES]]`. A positive count returns distinct fields, a negative
+/// one may repeat fields; see [`sample`].
+pub fn handle_hrandfield(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        Some(args.next_i64()?)
+    };
+    let with_values = if count.is_some() && !args.is_empty() {
+        let token = args.next_token()?;
+        if !token.is("WITHVALUES") {
+            return Err(token.syntax_error());
+        }
+        true
+    } else {
+        false
+    };
+    args.finish()?;
+
+    let hash = hash_mut(db, key, clock::now())?;
+    let Some(count) = count else {
+        let field = hash.and_then(|hash| hash.random()).map(|(field, _)| field);
+        return Ok(bulk_or_null(field));
+    };
+    let Some(hash) = hash.filter(|hash| !hash.is_empty()) else {
+        return Ok(array(Vec::new()));
+    };
+    let picked = sample(count, hash.len(), hash.iter(), || hash.random())?;
+
+    Ok(array(
+        picked
+            .into_iter()
+            .map(|(field, value)| {
+                if with_values {
+                    array(vec![bulk(field), bulk(value)])
+                } else {
+                    bulk(field)
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Reads the remaining arguments as `field value` pairs; there must be at least one.
+fn parse_field_values<'a>(
+    args: &mut Args<'a>,
+) -> Result<Vec<(&'a Bytes, &'a Bytes)>, CommandExecutionError> {
+    if args.is_empty() || !args.remaining().is_multiple_of(2) {
+        return Err(args.wrong_arity());
+    }
+    let mut pairs = Vec::with_capacity(args.remaining() / 2);
+    while !args.is_empty() {
+        pairs.push((args.next_bytes()?, args.next_bytes()?));
+    }
+    Ok(pairs)
+}
+
+/// Hash stored at `key`, `None` if the key is missing, or `WRONGTYPE` for other types.
+///
+/// Fields whose TTL has passed are dropped first, along with the key if no field survives.
+pub fn hash_mut<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<Option<&'a mut HashValue>, CommandExecutionError> {
+    let drained = match lookup_mut(db, key, now) {
+        Some(StoreObject { data: DataKind::Hash(hash), .. }) => {
+            hash.expire_fields(now) > 0 && hash.is_empty()
+        }
+        Some(_) => return Err(CommandExecutionError::WrongType),
+        None => return Ok(None),
+    };
+    if drained {
+        db.remove(key);
+        return Ok(None);
+    }
+    Ok(stored_hash(db, key))
+}
+
+/// Hash stored at `key`, creating an empty one if the key is missing.
+pub fn hash_or_create<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<&'a mut HashValue, CommandExecutionError> {
+    if hash_mut(db, key, now)?.is_none() {
+        db.insert(key.clone(), StoreObject::new(DataKind::Hash(HashValue::default()), now));
+    }
+    Ok(stored_hash(db, key).expect("hash was just looked up or created"))
+}
+
+/// Drops `key` once its hash has no fields left, as Redis never keeps empty collections.
+pub fn remove_if_empty(db: &mut DataStore, key: &Bytes) {
+    if stored_hash(db, key).is_some_and(|hash| hash.is_empty()) {
+        db.remove(key);
+    }
+}
+
+fn stored_hash<'a>(db: &'a mut DataStore, key: &Bytes) -> Option<&'a mut HashValue> {
+    match db.get_mut(key) {
+        Some(StoreObject { data: DataKind::Hash(hash), .. }) => Some(hash),
+        _ => None,
+    }
+}
@@ -0,0 +1,330 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::{Args, Token},
+        expire::ExpireCondition,
+        hash::{hash_mut, hash_or_create, remove_if_empty},
+        reply::{array, bulk_or_null, integer},
+        set::{absolute_deadline, relative_deadline},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        types::DataStore,
+    },
+};
+
+/// Per-field replies shared by `HEXPIRE`, `HTTL` and `HPERSIST`.
+const NO_FIELD: i64 = -2;
+const NO_TTL: i64 = -1;
+const CONDITION_NOT_MET: i64 = 0;
+const UPDATED: i64 = 1;
+const DELETED: i64 = 2;
+
+pub fn handle_hexpire(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    hexpire(&mut args, db, 1000)
+}
+
+pub fn handle_hpexpire(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    hexpire(&mut args, db, 1)
+}
+
+pub fn handle_httl(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    httl(&mut args, db, 1000)
+}
+
+pub fn handle_hpttl(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    httl(&mut args, db, 1)
+}
+
+pub fn handle_hpersist(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    expect_fields(&mut args)?;
+    let fields = parse_fields(&mut args, 1)?;
+
+    let mut hash = hash_mut(db, key, clock::now())?;
+    Ok(array(
+        fields
+            .into_iter()
+            .map(|field| {
+                integer(match hash.as_deref_mut() {
+                    Some(hash) if hash.contains_key(field) => {
+                        if hash.persist(field) {
+                            UPDATED
+                        } else {
+                            NO_TTL
+                        }
+                    }
+                    _ => NO_FIELD,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// `HGETEX key [EX | PX | EXAT | PXAT | PERSIST] FIELDS numfields field...`: reads the fields
+/// and then updates the TTL of those that exist.
+pub fn handle_hgetex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let now = clock::now();
+
+    let mut expiry = None;
+    loop {
+        let token = args.next_token()?;
+        if token.is("FIELDS") {
+            break;
+        }
+        if expiry.is_some() {
+            return Err(token.syntax_error());
+        }
+        expiry = Some(if token.is("PERSIST") {
+            FieldExpiry::Persist
+        } else {
+            parse_deadline(token, &mut args, now)?
+                .map(FieldExpiry::At)
+                .ok_or_else(|| token.syntax_error())?
+        });
+    }
+    let fields = parse_fields(&mut args, 1)?;
+
+    let Some(hash) = hash_mut(db, key, now)? else {
+        return Ok(array(fields.iter().map(|_| BytesFrame::Null).collect()));
+    };
+    let values = fields.iter().map(|field| bulk_or_null(hash.get(field))).collect();
+
+    for field in fields {
+        match expiry {
+            Some(FieldExpiry::At(deadline)) if deadline <= now => {
+                hash.remove(field);
+            }
+            Some(FieldExpiry::At(deadline)) => hash.set_expires_at(field, deadline),
+            Some(FieldExpiry::Persist) => {
+                hash.persist(field);
+            }
+            Some(FieldExpiry::KeepTtl) | None => {}
+        }
+    }
+    remove_if_empty(db, key);
+    Ok(array(values))
+}
+
+/// `HSETEX key [FNX | FXX] [EX | PX | EXAT | PXAT | KEEPTTL] FIELDS numfields field value...`.
+/// Replies 1 when every field was written and 0 when the `FNX`/`FXX` condition failed.
+pub fn handle_hsetex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let now = clock::now();
+
+    let mut only_new = None;
+    let mut expiry = None;
+    loop {
+        let token = args.next_token()?;
+        if token.is("FIELDS") {
+            break;
+        }
+        if token.is("FNX") || token.is("FXX") {
+            if only_new.is_some() {
+                return Err(token.syntax_error());
+            }
+            only_new = Some(token.is("FNX"));
+            continue;
+        }
+        if expiry.is_some() {
+            return Err(token.syntax_error());
+        }
+        expiry = Some(if token.is("KEEPTTL") {
+            FieldExpiry::KeepTtl
+        } else {
+            parse_deadline(token, &mut args, now)?
+                .map(FieldExpiry::At)
+                .ok_or_else(|| token.syntax_error())?
+        });
+    }
+    let pairs = parse_fields(&mut args, 2)?;
+    let pairs: Vec<(&Bytes, &Bytes)> = pairs.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+
+    let existing = hash_mut(db, key, now)?;
+    let allowed = match only_new {
+        Some(true) => {
+            existing.is_none_or(|hash| pairs.iter().all(|(field, _)| !hash.contains_key(field)))
+        }
+        Some(false) => {
+            existing.is_some_and(|hash| pairs.iter().all(|(field, _)| hash.contains_key(field)))
+        }
+        None => true,
+    };
+    if !allowed {
+        return Ok(integer(0));
+    }
+
+    let hash = hash_or_create(db, key, now)?;
+    for (field, value) in pairs {
+        match expiry {
+            Some(FieldExpiry::KeepTtl) => hash.update(field.clone(), value.clone()),
+            Some(FieldExpiry::At(deadline)) if deadline <= now => {
+                hash.remove(field);
+            }
+            Some(FieldExpiry::At(deadline)) => {
+                hash.insert(field.clone(), value.clone());
+                hash.set_expires_at(field, deadline);
+            }
+            Some(FieldExpiry::Persist) | None => {
+                hash.insert(field.clone(), value.clone());
+            }
+        }
+    }
+    remove_if_empty(db, key);
+    Ok(integer(1))
+}
+
+/// TTL change requested by `HGETEX`/`HSETEX`.
+#[derive(Clone, Copy)]
+enum FieldExpiry {
+    Persist,
+    KeepTtl,
+    At(UnixMillis),
+}
+
+fn hexpire(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: i64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let amount = args.next_i64()?;
+    let now = clock::now();
+
+    let mut token = args.next_token()?;
+    let condition = match ExpireCondition::from_token(token) {
+        Some(condition) => {
+            token = args.next_token()?;
+            condition
+        }
+        None => ExpireCondition::Always,
+    };
+    if !token.is("FIELDS") {
+        return Err(CommandExecutionError::FieldsMissing);
+    }
+    let fields = parse_fields(args, 1)?;
+
+    if amount < 0 {
+        return Err(CommandExecutionError::NegativeExpire);
+    }
+    let ms = amount.checked_mul(unit_ms).ok_or(CommandExecutionError::InvalidExpire)?;
+    let deadline = now.checked_add(ms as u64).ok_or(CommandExecutionError::InvalidExpire)?;
+
+    let Some(hash) = hash_mut(db, key, now)? else {
+        return Ok(array(fields.iter().map(|_| integer(NO_FIELD)).collect()));
+    };
+    let replies = fields
+        .into_iter()
+        .map(|field| {
+            integer(if !hash.contains_key(field) {
+                NO_FIELD
+            } else if !condition.allows(hash.expires_at(field), deadline) {
+                CONDITION_NOT_MET
+            } else if deadline <= now {
+                hash.remove(field);
+                DELETED
+            } else {
+                hash.set_expires_at(field, deadline);
+                UPDATED
+            })
+        })
+        .collect();
+    remove_if_empty(db, key);
+    Ok(array(replies))
+}
+
+fn httl(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: u64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    expect_fields(args)?;
+    let fields = parse_fields(args, 1)?;
+
+    let now = clock::now();
+    let hash = hash_mut(db, key, now)?;
+    Ok(array(
+        fields
+            .into_iter()
+            .map(|field| {
+                integer(match hash.as_deref() {
+                    Some(hash) if hash.contains_key(field) => match hash.expires_at(field) {
+                        // Rounded up, so a field never reports 0 while it is still visible.
+                        Some(deadline) => deadline.saturating_sub(now).div_ceil(unit_ms) as i64,
+                        None => NO_TTL,
+                    },
+                    _ => NO_FIELD,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Parses an `EX | PX | EXAT | PXAT` option into a deadline. Returns `None` if `token` is none
+/// of them; absolute deadlines may already be in the past.
+fn parse_deadline(
+    token: Token,
+    args: &mut Args,
+    now: UnixMillis,
+) -> Result<Option<UnixMillis>, CommandExecutionError> {
+    Ok(Some(if token.is("EX") {
+        relative_deadline(args.next_i64()?, 1000, now)?
+    } else if token.is("PX") {
+        relative_deadline(args.next_i64()?, 1, now)?
+    } else if token.is("EXAT") {
+        absolute_deadline(args.next_i64()?, 1000)?
+    } else if token.is("PXAT") {
+        absolute_deadline(args.next_i64()?, 1)?
+    } else {
+        return Ok(None);
+    }))
+}
+
+fn expect_fields(args: &mut Args) -> Result<(), CommandExecutionError> {
+    if args.eat_token("FIELDS") {
+        Ok(())
+    } else {
+        Err(CommandExecutionError::FieldsMissing)
+    }
+}
+
+/// Reads `numfields` followed by exactly that many groups of `per_field` arguments.
+fn parse_fields<'a>(
+    args: &mut Args<'a>,
+    per_field: usize,
+) -> Result<Vec<&'a Bytes>, CommandExecutionError> {
+    let count = args.next_i64()?;
+    if count <= 0 {
+        return Err(CommandExecutionError::NumFieldsNotPositive);
+    }
+    if (count as u64).checked_mul(per_field as u64) != Some(args.remaining() as u64) {
+        return Err(CommandExecutionError::NumFieldsMismatch);
+    }
+    args.rest_bytes()
+}
@@ -0,0 +1,117 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::{Args, parse_f64, parse_i64},
+        reply::{bulk, format_f64, integer},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        types::{DataKind, DataStore, StoreObject, lookup_mut},
+    },
+};
+
+pub fn handle_incr(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+    incr_by(db, key, 1)
+}
+
+pub fn handle_decr(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+    incr_by(db, key, -1)
+}
+
+pub fn handle_incrby(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let delta = args.next_i64()?;
+    args.finish()?;
+    incr_by(db, key, delta)
+}
+
+pub fn handle_decrby(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let delta = args.next_i64()?;
+    args.finish()?;
+    // DECRBY i64::MIN cannot be expressed as an increment.
+    incr_by(db, key, delta.checked_neg().ok_or(CommandExecutionError::Overflow)?)
+}
+
+pub fn handle_incrbyfloat(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let delta = args.next_f64()?;
+    args.finish()?;
+
+    let now = clock::now();
+    let current = match lookup_mut(db, key, now) {
+        Some(obj) => match &obj.data {
+            DataKind::Int(value) => *value as f64,
+            data => {
+                let bytes = data.string_bytes().ok_or(CommandExecutionError::WrongType)?;
+                parse_f64(&bytes).ok_or(CommandExecutionError::NotFloat)?
+            }
+        },
+        None => 0.0,
+    };
+
+    let updated = current + delta;
+    if !updated.is_finite() {
+        return Err(CommandExecutionError::NanOrInfinity);
+    }
+
+    // Stored as a string, like Redis, so later reads see exactly what was replied.
+    let formatted = Bytes::from(format_f64(updated));
+    store_string(db, key, DataKind::from_string(formatted.clone()), now);
+    Ok(bulk(formatted))
+}
+
+/// Adds `delta` to the integer stored at `key`, creating it from 0 if missing.
+fn incr_by(
+    db: &mut DataStore,
+    key: &Bytes,
+    delta: i64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let now = clock::now();
+    let current = match lookup_mut(db, key, now) {
+        Some(obj) => match &obj.data {
+            DataKind::Int(value) => *value,
+            data => {
+                let bytes = data.string_bytes().ok_or(CommandExecutionError::WrongType)?;
+                parse_i64(&bytes).ok_or(CommandExecutionError::NotInteger)?
+            }
+        },
+        None => 0,
+    };
+
+    let updated = current.checked_add(delta).ok_or(CommandExecutionError::Overflow)?;
+    store_string(db, key, DataKind::Int(updated), now);
+    Ok(integer(updated))
+}
+
+/// Replaces the value at `key` in place so an existing TTL survives the update.
+fn store_string(db: &mut DataStore, key: &Bytes, data: DataKind, now: UnixMillis) {
+    match db.get_mut(key) {
+        Some(obj) => obj.data = data,
+        None => {
+            db.insert(key.clone(), StoreObject::new(data, now));
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use bytes::Bytes;
+use rand::seq::IndexedRandom;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{bulk_or_null, integer, ok},
+    },
+    config::CONFIG,
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        lazyfree,
+        shard::Shard,
+        types::{DataStore, StoreObject, lookup, lookup_mut},
+    },
+};
+
+/// Keys a shard samples for `RANDOMKEY` before giving up on finding one that is not expired.
+const RANDOMKEY_TRIES: usize = 100;
+
+/// Per-key step of the commands that count how many of their keys it applied to.
+type KeyOp = fn(&mut DataStore, &Bytes, UnixMillis) -> bool;
+
+pub fn handle_del(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys(args, db, delete)
+}
+
+/// `UNLINK` removes keys like `DEL` but leaves freeing large values to the background thread.
+pub fn handle_unlink(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys(args, db, unlink)
+}
+
+pub fn handle_exists(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys(args, db, exists)
+}
+
+pub fn handle_touch(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys(args, db, touch)
+}
+
+pub fn handle_type(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let name = lookup(db, key, clock::now()).map_or("none", |obj| obj.data.type_name());
+    Ok(BytesFrame::SimpleString { data: name.into(), attributes: None })
+}
+
+pub fn handle_rename(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (key, newkey) = (args.next_bytes()?, args.next_bytes()?);
+    args.finish()?;
+
+    let obj = take(db, key, clock::now()).ok_or(CommandExecutionError::NoSuchKey)?;
+    db.insert(newkey.clone(), obj);
+    Ok(ok())
+}
+
+pub fn handle_renamenx(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (key, newkey) = (args.next_bytes()?, args.next_bytes()?);
+    args.finish()?;
+
+    let now = clock::now();
+    if lookup(db, key, now).is_none() {
+        return Err(CommandExecutionError::NoSuchKey);
+    }
+    if lookup(db, newkey, now).is_some() {
+        return Ok(integer(0));
+    }
+    let obj = db.remove(key).expect("source checked above");
+    db.insert(newkey.clone(), obj);
+    Ok(integer(1))
+}
+
+pub fn handle_copy(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination, replace) = parse_copy(&mut args)?;
+    if source == destination {
+        return Err(CommandExecutionError::SameObject);
+    }
+
+    let now = clock::now();
+    let Some(copy) = duplicate(db, source, now) else {
+        return Ok(integer(0));
+    };
+    Ok(integer(place(db, destination.clone(), copy, replace, now).is_none() as i64))
+}
+
+/// `DEL` over keys owned by several shards.
+pub async fn del_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys_cross_shard(shard, args, delete).await
+}
+
+/// `UNLINK` over keys owned by several shards.
+pub async fn unlink_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys_cross_shard(shard, args, unlink).await
+}
+
+/// `EXISTS` over keys owned by several shards.
+pub async fn exists_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys_cross_shard(shard, args, exists).await
+}
+
+/// `TOUCH` over keys owned by several shards.
+pub async fn touch_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    count_keys_cross_shard(shard, args, touch).await
+}
+
+/// `RENAME` between shards: the whole object, TTL included, leaves the source shard and is
+/// stored under the new name on the destination shard.
+pub async fn rename_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (key, newkey) = (args.next_bytes()?.clone(), args.next_bytes()?.clone());
+    args.finish()?;
+
+    let obj = shard
+        .run_on(shard.owner(&key), move |db| take(db, &key, clock::now()))
+        .await
+        .ok_or(CommandExecutionError::NoSuchKey)?;
+    shard
+        .run_on(shard.owner(&newkey), move |db| {
+            db.insert(newkey, obj);
+        })
+        .await;
+    Ok(ok())
+}
+
+/// `RENAMENX` between shards. Should the new name be taken while the object is in flight, the
+/// object goes back to its old name, replacing whatever was stored there in the meantime so
+/// that it is never dropped.
+pub async fn renamenx_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (key, newkey) = (args.next_bytes()?.clone(), args.next_bytes()?.clone());
+    args.finish()?;
+    let (owner, new_owner) = (shard.owner(&key), shard.owner(&newkey));
+
+    let probe = key.clone();
+    if !shard.run_on(owner, move |db| exists(db, &probe, clock::now())).await {
+        return Err(CommandExecutionError::NoSuchKey);
+    }
+    let probe = newkey.clone();
+    if shard.run_on(new_owner, move |db| exists(db, &probe, clock::now())).await {
+        return Ok(integer(0));
+    }
+
+    let taken = key.clone();
+    let obj = shard
+        .run_on(owner, move |db| take(db, &taken, clock::now()))
+        .await
+        .ok_or(CommandExecutionError::NoSuchKey)?;
+    let placed = shard
+        .run_on(new_owner, move |db| place(db, newkey, obj, false, clock::now()))
+        .await;
+    match placed {
+        None => Ok(integer(1)),
+        Some(obj) => {
+            shard.run_on(owner, move |db| place(db, key, obj, true, clock::now())).await;
+            Ok(integer(0))
+        }
+    }
+}
+
+/// `COPY` between shards: the source shard clones the object and the destination shard stores
+/// the clone.
+pub async fn copy_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination, replace) = parse_copy(&mut args)?;
+    let (source, destination) = (source.clone(), destination.clone());
+
+    let owner = shard.owner(&source);
+    let Some(copy) = shard.run_on(owner, move |db| duplicate(db, &source, clock::now())).await
+    else {
+        return Ok(integer(0));
+    };
+    let copied = shard
+        .run_on(shard.owner(&destination), move |db| {
+            place(db, destination, copy, replace, clock::now()).is_none()
+        })
+        .await;
+    Ok(integer(copied as i64))
+}
+
+/// `DBSIZE`, summed over every shard. Keys past their TTL count until they are reclaimed.
+pub async fn handle_dbsize(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    args.finish()?;
+    let sizes = shard.broadcast(|db| db.len()).await;
+    Ok(integer(sizes.into_iter().sum::<usize>() as i64))
+}
+
+/// `FLUSHDB` and `FLUSHALL`, which are the same thing with a single database. The keyspace is
+/// empty by the time of the reply either way; `ASYNC` only moves freeing the old one to the
+/// background thread. Without a mode, `lazyfree-lazy-user-flush` decides.
+pub async fn handle_flush(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let mut lazy = CONFIG.lazyfree_lazy_user_flush();
+    if !args.is_empty() {
+        let token = args.next_token()?;
+        if !(token.is("ASYNC") || token.is("SYNC")) || !args.is_empty() {
+            return Err(token.syntax_error());
+        }
+        lazy = token.is("ASYNC");
+    }
+    shard.broadcast(move |db| db.clear(lazy)).await;
+    Ok(ok())
+}
+
+/// `RANDOMKEY`: every shard samples its table for a live key, then one shard's pick is chosen
+/// with a weight proportional to its size, so every key is about equally likely. A shard whose
+/// samples all turn out expired reports none, like Redis does after its own bounded retries.
+pub async fn handle_randomkey(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    args.finish()?;
+    let picks = shard
+        .broadcast(|db| {
+            let now = clock::now();
+            let key = (0..RANDOMKEY_TRIES).find_map(|_| {
+                let (key, obj) = db.sample()?;
+                (!obj.is_expired(now)).then(|| key.clone())
+            });
+            (db.len(), key)
+        })
+        .await;
+
+    let candidates: Vec<(usize, Bytes)> =
+        picks.into_iter().filter_map(|(len, key)| Some((len, key?))).collect();
+    let key = candidates
+        .choose_weighted(&mut rand::rng(), |(len, _)| *len)
+        .ok()
+        .map(|(_, key)| key.clone());
+    Ok(bulk_or_null(key))
+}
+
+fn count_keys(
+    mut args: Args,
+    db: &mut DataStore,
+    op: KeyOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys = args.rest_bytes()?;
+    let now = clock::now();
+    Ok(integer(keys.into_iter().filter(|key| op(db, key, now)).count() as i64))
+}
+
+async fn count_keys_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+    op: KeyOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys: Vec<Bytes> = args.rest_bytes()?.into_iter().cloned().collect();
+    let applied = shard
+        .scatter_gather(
+            keys,
+            |key| key,
+            move |db, keys| {
+                let now = clock::now();
+                keys.iter().map(|key| op(db, key, now)).collect()
+            },
+        )
+        .await;
+    Ok(integer(applied.into_iter().filter(|&applied| applied).count() as i64))
+}
+
+fn delete(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> bool {
+    discard(db, key, now, CONFIG.lazyfree_lazy_user_del())
+}
+
+fn unlink(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> bool {
+    discard(db, key, now, true)
+}
+
+fn exists(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> bool {
+    lookup(db, key, now).is_some()
+}
+
+fn touch(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> bool {
+    lookup_mut(db, key, now).is_some()
+}
+
+/// Removes `key` and hands back its object, unless it had already expired.
+fn take(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> Option<StoreObject> {
+    db.remove(key).filter(|obj| !obj.is_expired(now))
+}
+
+/// Removes `key` and frees its value, lazily if `lazy` is set; see [`lazyfree::free`]. Returns
+/// whether the key was live.
+fn discard(db: &mut DataStore, key: &Bytes, now: UnixMillis, lazy: bool) -> bool {
+    let Some(obj) = db.remove(key) else {
+        return false;
+    };
+    let live = !obj.is_expired(now);
+    lazyfree::free(obj, lazy);
+    live
+}
+
+/// Deep copy of the object at `key` that keeps its deadline but starts a fresh access history.
+fn duplicate(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> Option<StoreObject> {
+    let mut copy = lookup_mut(db, key, now)?.clone();
+    copy.last_accessed = now;
+    Some(copy)
+}
+
+/// Stores `obj` at `key`. Unless `replace` is set, a live key already there wins and `obj` is
+/// handed back instead.
+fn place(
+    db: &mut DataStore,
+    key: Bytes,
+    obj: StoreObject,
+    replace: bool,
+    now: UnixMillis,
+) -> Option<StoreObject> {
+    if !replace && lookup(db, &key, now).is_some() {
+        return Some(obj);
+    }
+    db.insert(key, obj);
+    None
+}
+
+/// Parses `source destination [DB destination-db] [REPLACE]`. There is a single database, so
+/// only `DB 0` is accepted.
+fn parse_copy<'a>(
+    args: &mut Args<'a>,
+) -> Result<(&'a Bytes, &'a Bytes, bool), CommandExecutionError> {
+    let (source, destination) = (args.next_bytes()?, args.next_bytes()?);
+    let mut replace = false;
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if token.is("REPLACE") {
+            replace = true;
+        } else if token.is("DB") {
+            if args.next_i64()? != 0 {
+                return Err(CommandExecutionError::DbIndexOutOfRange);
+            }
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+    Ok((source, destination, replace))
+}
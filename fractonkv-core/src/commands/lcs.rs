@@ -0,0 +1,312 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::{BytesFrame, FrameMap};
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{array, bulk, integer},
+        string::MAX_STRING_LEN,
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        shard::Shard,
+        types::{DataStore, lookup_mut},
+    },
+};
+
+pub fn handle_lcs(mut args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    let key_a = args.next_bytes()?;
+    let key_b = args.next_bytes()?;
+    let options = LcsOptions::parse(&mut args)?;
+
+    let a = lcs_operand(db, key_a)?;
+    let b = lcs_operand(db, key_b)?;
+    lcs_reply(&a, &b, &options)
+}
+
+/// `LCS` where the two keys live on different shards: both operands are fetched from their
+/// owners and the comparison runs here.
+pub async fn lcs_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys = vec![args.next_bytes()?.clone(), args.next_bytes()?.clone()];
+    let options = LcsOptions::parse(&mut args)?;
+
+    let operands = shard
+        .scatter_gather(
+            keys,
+            |key| key,
+            |db, keys| keys.iter().map(|key| lcs_operand(db, key)).collect(),
+        )
+        .await;
+    let [a, b]: [Result<Bytes, CommandExecutionError>; 2] =
+        operands.try_into().unwrap_or_else(|_| unreachable!("one operand per key"));
+    lcs_reply(&a?, &b?, &options)
+}
+
+#[derive(Default)]
+struct LcsOptions {
+    len_only: bool,
+    with_idx: bool,
+    with_match_len: bool,
+    min_match_len: i64,
+}
+
+impl LcsOptions {
+    fn parse(args: &mut Args) -> Result<Self, CommandExecutionError> {
+        let mut options = Self::default();
+        while !args.is_empty() {
+            let token = args.next_token()?;
+            if token.is("LEN") {
+                options.len_only = true;
+            } else if token.is("IDX") {
+                options.with_idx = true;
+            } else if token.is("WITHMATCHLEN") {
+                options.with_match_len = true;
+            } else if token.is("MINMATCHLEN") {
+                options.min_match_len = args.next_i64()?.max(0);
+            } else {
+                return Err(token.syntax_error());
+            }
+        }
+        if options.len_only && options.with_idx {
+            return Err(CommandExecutionError::LcsLenAndIdx);
+        }
+        Ok(options)
+    }
+}
+
+fn lcs_reply(
+    a: &[u8],
+    b: &[u8],
+    options: &LcsOptions,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let lcs = Lcs::compute(a, b)?;
+
+    if options.len_only {
+        return Ok(integer(lcs.len() as i64));
+    }
+    if !options.with_idx {
+        return Ok(bulk(lcs.string(a, b)));
+    }
+
+    let matches = lcs
+        .matches(a, b)
+        .into_iter()
+        .filter(|m| options.min_match_len == 0 || m.len() as i64 >= options.min_match_len)
+        .map(|m| {
+            let mut entry = vec![pair(m.a.0, m.a.1), pair(m.b.0, m.b.1)];
+            if options.with_match_len {
+                entry.push(integer(m.len() as i64));
+            }
+            array(entry)
+        })
+        .collect();
+
+    let mut map = FrameMap::new();
+    map.insert(bulk("matches".into()), array(matches));
+    map.insert(bulk("len".into()), integer(lcs.len() as i64));
+    Ok(BytesFrame::Map { data: map, attributes: None })
+}
+
+/// Reads a string operand; missing keys behave like empty strings.
+fn lcs_operand(db: &mut DataStore, key: &Bytes) -> Result<Bytes, CommandExecutionError> {
+    match lookup_mut(db, key, clock::now()) {
+        Some(obj) => obj.data.string_bytes().ok_or(CommandExecutionError::LcsNotString),
+        None => Ok(Bytes::new()),
+    }
+}
+
+fn pair(start: usize, end: usize) -> BytesFrame {
+    array(vec![integer(start as i64), integer(end as i64)])
+}
+
+/// A contiguous run of the LCS, as inclusive ranges in both inputs.
+struct LcsMatch {
+    a: (usize, usize),
+    b: (usize, usize),
+}
+
+impl LcsMatch {
+    fn len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+/// Dynamic-programming table where `cell(i, j)` is the LCS length of `a[..i]` and `b[..j]`.
+struct Lcs {
+    table: Vec<u32>,
+    cols: usize,
+}
+
+impl Lcs {
+    fn compute(a: &[u8], b: &[u8]) -> Result<Self, CommandExecutionError> {
+        let cols = b.len() + 1;
+        let cells = (a.len() + 1)
+            .checked_mul(cols)
+            .filter(|cells| cells * size_of::<u32>() <= MAX_STRING_LEN)
+            .ok_or(CommandExecutionError::LcsTooLarge)?;
+
+        let mut table = vec![0u32; cells];
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                table[i * cols + j] = if a[i - 1] == b[j - 1] {
+                    table[(i - 1) * cols + j - 1] + 1
+                } else {
+                    table[(i - 1) * cols + j].max(table[i * cols + j - 1])
+                };
+            }
+        }
+        Ok(Self { table, cols })
+    }
+
+    fn cell(&self, i: usize, j: usize) -> u32 {
+        self.table[i * self.cols + j]
+    }
+
+    fn len(&self) -> usize {
+        self.table.last().copied().unwrap_or(0) as usize
+    }
+
+    fn string(&self, a: &[u8], b: &[u8]) -> Bytes {
+        let mut out = Vec::with_capacity(self.len());
+        let (mut i, mut j) = (a.len(), b.len());
+        while i > 0 && j > 0 {
+            if a[i - 1] == b[j - 1] {
+                out.push(a[i - 1]);
+                i -= 1;
+                j -= 1;
+            } else if self.cell(i - 1, j) > self.cell(i, j - 1) {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+        }
+        out.reverse();
+        Bytes::from(out)
+    }
+
+    /// Walks the table backwards, yielding matched runs from the end of the strings to the
+    /// start, exactly in the order Redis reports them.
+    fn matches(&self, a: &[u8], b: &[u8]) -> Vec<LcsMatch> {
+        let mut out = Vec::new();
+        let mut current: Option<LcsMatch> = None;
+        let (mut i, mut j) = (a.len(), b.len());
+
+        while i > 0 && j > 0 {
+            let mut emit = false;
+            if a[i - 1] == b[j - 1] {
+                match current.as_mut() {
+                    Some(run) if run.a.0 == i && run.b.0 == j => {
+                        run.a.0 -= 1;
+                        run.b.0 -= 1;
+                    }
+                    Some(_) => emit = true,
+                    None => current = Some(LcsMatch { a: (i - 1, i - 1), b: (j - 1, j - 1) }),
+                }
+                // Nothing can follow a run that reached the start of either string.
+                if current.as_ref().is_some_and(|run| run.a.0 == 0 || run.b.0 == 0) {
+                    emit = true;
+                }
+                i -= 1;
+                j -= 1;
+            } else {
+                if self.cell(i - 1, j) > self.cell(i, j - 1) {
+                    i -= 1;
+                } else {
+                    j -= 1;
+                }
+                emit = current.is_some();
+            }
+
+            if emit && let Some(run) = current.take() {
+                out.push(run);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis_protocol::resp3::types::{BytesFrame, FrameMap};
+
+    use super::pair;
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::{run, run_err},
+            reply::{array, bulk, integer},
+        },
+        shard::types::DataStore,
+    };
+
+    fn store() -> DataStore {
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Set, &["key1", "ohmytext"]);
+        run(&mut db, CommandKind::Set, &["key2", "mynewtext"]);
+        db
+    }
+
+    fn idx_reply(matches: Vec<BytesFrame>, len: i64) -> BytesFrame {
+        let mut map = FrameMap::new();
+        map.insert(bulk("matches".into()), array(matches));
+        map.insert(bulk("len".into()), integer(len));
+        BytesFrame::Map { data: map, attributes: None }
+    }
+
+    #[test]
+    fn lcs_returns_the_string_or_its_length() {
+        let mut db = store();
+        assert_eq!(run(&mut db, CommandKind::Lcs, &["key1", "key2"]), bulk("mytext".into()));
+        assert_eq!(run(&mut db, CommandKind::Lcs, &["key1", "key2", "LEN"]), integer(6));
+        assert_eq!(run(&mut db, CommandKind::Lcs, &["key1", "missing"]), bulk("".into()));
+        assert_eq!(
+            run_err(&mut db, CommandKind::Lcs, &["key1", "key2", "LEN", "IDX"]),
+            "ERR If you want both the length and indexes, please just use IDX."
+        );
+    }
+
+    #[test]
+    fn lcs_idx_reports_runs_from_the_end_filtered_by_length() {
+        let mut db = store();
+        let text = array(vec![pair(4, 7), pair(5, 8)]);
+        let my = array(vec![pair(2, 3), pair(0, 1)]);
+        assert_eq!(
+            run(&mut db, CommandKind::Lcs, &["key1", "key2", "IDX"]),
+            idx_reply(vec![text.clone(), my], 6)
+        );
+        assert_eq!(
+            run(&mut db, CommandKind::Lcs, &["key1", "key2", "IDX", "MINMATCHLEN", "4"]),
+            idx_reply(vec![text], 6)
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                CommandKind::Lcs,
+                &["key1", "key2", "IDX", "MINMATCHLEN", "3", "WITHMATCHLEN"]
+            ),
+            idx_reply(vec![array(vec![pair(4, 7), pair(5, 8), integer(4)])], 6)
+        );
+    }
+
+    #[test]
+    fn lcs_rejects_non_strings_and_oversized_tables() {
+        let mut db = store();
+        run(&mut db, CommandKind::Rpush, &["list", "a"]);
+        assert_eq!(
+            run_err(&mut db, CommandKind::Lcs, &["key1", "list"]),
+            "ERR The specified keys must contain string values"
+        );
+
+        let long = "a".repeat(12_000);
+        run(&mut db, CommandKind::Set, &["long1", &long]);
+        run(&mut db, CommandKind::Set, &["long2", &long]);
+        assert_eq!(
+            run_err(&mut db, CommandKind::Lcs, &["long1", "long2", "LEN"]),
+            "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::{Args, Token},
+        reply::{array, bulk, bulk_or_null, integer, ok},
+        string::clamp_range,
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        quicklist::QuickList,
+        types::{DataKind, DataStore, StoreObject, lookup_mut},
+    },
+};
+
+/// End of a list that a push or pop works on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    pub fn from_token(token: Token) -> Result<Self, CommandExecutionError> {
+        if token.is("LEFT") {
+            Ok(End::Left)
+        } else if token.is("RIGHT") {
+            Ok(End::Right)
+        } else {
+            Err(token.syntax_error())
+        }
+    }
+}
+
+pub fn handle_lpush(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    push(&mut args, db, End::Left, false)
+}
+
+pub fn handle_rpush(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    push(&mut args, db, End::Right, false)
+}
+
+pub fn handle_lpushx(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    push(&mut args, db, End::Left, true)
+}
+
+pub fn handle_rpushx(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    push(&mut args, db, End::Right, true)
+}
+
+pub fn handle_lpop(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    pop(&mut args, db, End::Left)
+}
+
+pub fn handle_rpop(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    pop(&mut args, db, End::Right)
+}
+
+pub fn handle_llen(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let list = list_mut(db, key, clock::now())?;
+    Ok(integer(list.map_or(0, |list| list.len()) as i64))
+}
+
+pub fn handle_lindex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let index = args.next_i64()?;
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(BytesFrame::Null);
+    };
+    let value = resolve_index(index, list.len()).and_then(|index| list.get(index));
+    Ok(bulk_or_null(value.cloned()))
+}
+
+pub fn handle_lset(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let index = args.next_i64()?;
+    let value = args.next_bytes()?;
+    args.finish()?;
+
+    let list = list_mut(db, key, clock::now())?.ok_or(CommandExecutionError::NoSuchKey)?;
+    match resolve_index(index, list.len()) {
+        Some(index) if list.set(index, value.clone()) => Ok(ok()),
+        _ => Err(CommandExecutionError::IndexOutOfRange),
+    }
+}
+
+/// `LINSERT key BEFORE|AFTER pivot element`. Replies with the new length, -1 when the pivot is
+/// missing and 0 when the key is.
+pub fn handle_linsert(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let token = args.next_token()?;
+    let after = if token.is("AFTER") {
+        true
+    } else if token.is("BEFORE") {
+        false
+    } else {
+        return Err(token.syntax_error());
+    };
+    let pivot = args.next_bytes()?;
+    let value = args.next_bytes()?;
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let Some(position) = list.iter().position(|element| element == pivot) else {
+        return Ok(integer(-1));
+    };
+    list.insert(position + after as usize, value.clone());
+    Ok(integer(list.len() as i64))
+}
+
+pub fn handle_lrange(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let start = args.next_i64()?;
+    let end = args.next_i64()?;
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(array(Vec::new()));
+    };
+    Ok(array(match clamp_range(start, end, list.len()) {
+        Some((from, to)) => list.iter().skip(from).take(to - from + 1).cloned().map(bulk).collect(),
+        None => Vec::new(),
+    }))
+}
+
+/// `LREM key count element`: a positive count removes from the head, a negative one from the
+/// tail and zero removes every match.
+pub fn handle_lrem(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = args.next_i64()?;
+    let value = args.next_bytes()?;
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let limit = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+    let removed = list.remove_matching(value, limit, count < 0);
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_ltrim(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let start = args.next_i64()?;
+    let end = args.next_i64()?;
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(ok());
+    };
+    match clamp_range(start, end, list.len()) {
+        Some((from, to)) => list.retain_range(from, to),
+        None => {
+            db.remove(key);
+        }
+    }
+    Ok(ok())
+}
+
+/// `LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]`.
+pub fn handle_lpos(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let value = args.next_bytes()?;
+
+    let mut rank: i64 = 1;
+    let mut count: Option<usize> = None;
+    let mut max_len: usize = 0;
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if token.is("RANK") {
+            rank = args.next_i64()?;
+            if rank == 0 {
+                return Err(CommandExecutionError::LposRankZero);
+            }
+        } else if token.is("COUNT") {
+            let n = args.next_i64()?;
+            if n < 0 {
+                return Err(CommandExecutionError::LposNegativeCount);
+            }
+            count = Some(n as usize);
+        } else if token.is("MAXLEN") {
+            let n = args.next_i64()?;
+            if n < 0 {
+                return Err(CommandExecutionError::LposNegativeMaxlen);
+            }
+            max_len = n as usize;
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+
+    let list = list_mut(db, key, clock::now())?;
+    let len = list.as_ref().map_or(0, |list| list.len());
+    let scanned = if max_len == 0 { len } else { max_len.min(len) };
+    let skip = usize::try_from(rank.unsigned_abs() - 1).unwrap_or(usize::MAX);
+    // COUNT 0 means "all matches".
+    let wanted = match count {
+        Some(0) => usize::MAX,
+        Some(n) => n,
+        None => 1,
+    };
+
+    let matches: Vec<usize> = match list {
+        Some(list) if rank > 0 => list
+            .iter()
+            .take(scanned)
+            .enumerate()
+            .filter(|(_, element)| *element == value)
+            .map(|(index, _)| index)
+            .skip(skip)
+            .take(wanted)
+            .collect(),
+        Some(list) => list
+            .iter()
+            .rev()
+            .take(scanned)
+            .enumerate()
+            .filter(|(_, element)| *element == value)
+            .map(|(offset, _)| len - 1 - offset)
+            .skip(skip)
+            .take(wanted)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(match count {
+        Some(_) => array(matches.into_iter().map(|index| integer(index as i64)).collect()),
+        None => matches.first().map_or(BytesFrame::Null, |&index| integer(index as i64)),
+    })
+}
+
+/// Shared body of the push commands. `only_existing` is the `LPUSHX`/`RPUSHX` variant, which
+/// never creates the key.
+fn push(
+    args: &mut Args,
+    db: &mut DataStore,
+    end: End,
+    only_existing: bool,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let values = args.rest_bytes()?;
+    if values.is_empty() {
+        return Err(args.wrong_arity());
+    }
+
+    let now = clock::now();
+    let list = if only_existing {
+        match list_mut(db, key, now)? {
+            Some(list) => list,
+            None => return Ok(integer(0)),
+        }
+    } else {
+        list_or_create(db, key, now)?
+    };
+    for value in values {
+        push_end(list, end, value.clone());
+    }
+    Ok(integer(list.len() as i64))
+}
+
+/// Shared body of `LPOP` and `RPOP`. Without a count the reply is a single element; with one it
+/// is an array of up to `count` elements.
+fn pop(args: &mut Args, db: &mut DataStore, end: End) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        let count = args.next_i64()?;
+        if count < 0 {
+            return Err(CommandExecutionError::NotPositive);
+        }
+        Some(count as usize)
+    };
+    args.finish()?;
+
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(BytesFrame::Null);
+    };
+    let reply = match count {
+        None => bulk_or_null(pop_end(list, end)),
+        Some(count) => {
+            let popped = (0..count.min(list.len())).filter_map(|_| pop_end(list, end));
+            array(popped.map(bulk).collect())
+        }
+    };
+    remove_if_empty(db, key);
+    Ok(reply)
+}
+
+pub fn push_end(list: &mut QuickList, end: End, value: Bytes) {
+    match end {
+        End::Left => list.push_front(value),
+        End::Right => list.push_back(value),
+    }
+}
+
+pub fn pop_end(list: &mut QuickList, end: End) -> Option<Bytes> {
+    match end {
+        End::Left => list.pop_front(),
+        End::Right => list.pop_back(),
+    }
+}
+
+/// Resolves a possibly negative Redis index against a list of `len` elements.
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+/// List stored at `key`, `None` if the key is missing, or `WRONGTYPE` for other types.
+pub fn list_mut<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<Option<&'a mut QuickList>, CommandExecutionError> {
+    match lookup_mut(db, key, now) {
+        Some(StoreObject { data: DataKind::List(list), .. }) => Ok(Some(list)),
+        Some(_) => Err(CommandExecutionError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// List stored at `key`, creating an empty one if the key is missing.
+pub fn list_or_create<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<&'a mut QuickList, CommandExecutionError> {
+    if list_mut(db, key, now)?.is_none() {
+        db.insert(key.clone(), StoreObject::new(DataKind::List(QuickList::default()), now));
+    }
+    Ok(list_mut(db, key, now)?.expect("list was just looked up or created"))
+}
+
+/// Drops `key` once its list has no elements left.
+pub fn remove_if_empty(db: &mut DataStore, key: &Bytes) {
+    if matches!(db.get(key), Some(StoreObject { data: DataKind::List(list), .. }) if list.is_empty())
+    {
+        db.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::run,
+            reply::{array, bulk, integer, ok},
+        },
+        shard::types::DataStore,
+    };
+
+    /// A list holding `0..len`; its chunk edges fall every 128 elements.
+    fn store(len: usize) -> (DataStore, Vec<String>) {
+        let mut db = DataStore::default();
+        let elements: Vec<String> = (0..len).map(|i| i.to_string()).collect();
+        let mut args = vec!["list"];
+        args.extend(elements.iter().map(String::as_str));
+        run(&mut db, CommandKind::Rpush, &args);
+        (db, elements)
+    }
+
+    fn elements(expected: &[String]) -> BytesFrame {
+        array(expected.iter().map(|element| bulk(Bytes::from(element.clone()))).collect())
+    }
+
+    fn lrange_all(db: &mut DataStore) -> BytesFrame {
+        run(db, CommandKind::Lrange, &["list", "0", "-1"])
+    }
+
+    #[test]
+    fn linsert_around_chunk_edges() {
+        let (mut db, mut expected) = store(256);
+        for (pivot, place, at) in [
+            ("127", "AFTER", 128),
+            ("128", "BEFORE", 128),
+            ("255", "AFTER", 258),
+        ] {
+            let reply = run(&mut db, CommandKind::Linsert, &["list", place, pivot, "new"]);
+            expected.insert(at, "new".to_string());
+            assert_eq!(reply, integer(expected.len() as i64));
+        }
+        assert_eq!(lrange_all(&mut db), elements(&expected));
+        for index in [127, 128, 129, 130, 258] {
+            let reply = run(&mut db, CommandKind::Lindex, &["list", &index.to_string()]);
+            assert_eq!(reply, bulk(Bytes::from(expected[index].clone())), "index {index}");
+        }
+    }
+
+    #[test]
+    fn lrem_across_chunk_edges() {
+        let (mut db, mut expected) = store(384);
+        for index in [127, 128, 255, 256] {
+            run(&mut db, CommandKind::Lset, &["list", &index.to_string(), "x"]);
+            expected[index] = "x".to_string();
+        }
+
+        assert_eq!(run(&mut db, CommandKind::Lrem, &["list", "-1", "x"]), integer(1));
+        expected.remove(256);
+        assert_eq!(run(&mut db, CommandKind::Lrem, &["list", "2", "x"]), integer(2));
+        expected.drain(127..=128);
+        assert_eq!(lrange_all(&mut db), elements(&expected));
+        assert_eq!(run(&mut db, CommandKind::Lindex, &["list", "253"]), bulk("x".into()));
+        assert_eq!(run(&mut db, CommandKind::Llen, &["list"]), integer(381));
+    }
+
+    #[test]
+    fn ltrim_across_chunk_edges() {
+        let (mut db, expected) = store(512);
+        assert_eq!(run(&mut db, CommandKind::Ltrim, &["list", "127", "-128"]), ok());
+        assert_eq!(lrange_all(&mut db), elements(&expected[127..=384]));
+        assert_eq!(run(&mut db, CommandKind::Ltrim, &["list", "1", "-2"]), ok());
+        assert_eq!(lrange_all(&mut db), elements(&expected[128..=383]));
+
+        run(&mut db, CommandKind::Ltrim, &["list", "5", "1"]);
+        assert_eq!(run(&mut db, CommandKind::Exists, &["list"]), integer(0));
+    }
+}
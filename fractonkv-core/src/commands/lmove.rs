@@ -0,0 +1,225 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        list::{End, list_mut, list_or_create, pop_end, push_end, remove_if_empty},
+        reply::{array, bulk, bulk_or_null},
+    },
+    errors::CommandExecutionError,
+    shard::{clock, shard::Shard, types::DataStore},
+};
+
+pub fn handle_rpoplpush(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination) = (args.next_bytes()?, args.next_bytes()?);
+    args.finish()?;
+    move_element(db, source, destination, End::Right, End::Left).map(bulk_or_null)
+}
+
+pub fn handle_lmove(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination, from, to) = parse_lmove(&mut args)?;
+    move_element(db, source, destination, from, to).map(bulk_or_null)
+}
+
+pub fn handle_lmpop(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, end, count) = parse_lmpop(&mut args)?;
+    for key in keys {
+        if let Some(popped) = pop_many(db, key, end, count)? {
+            return Ok(lmpop_reply(key.clone(), popped));
+        }
+    }
+    Ok(BytesFrame::Null)
+}
+
+/// `RPOPLPUSH` across shards.
+pub async fn rpoplpush_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination) = (args.next_bytes()?, args.next_bytes()?);
+    args.finish()?;
+    move_between(shard, source.clone(), destination.clone(), End::Right, End::Left)
+        .await
+        .map(bulk_or_null)
+}
+
+/// `LMOVE` across shards.
+pub async fn lmove_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination, from, to) = parse_lmove(&mut args)?;
+    move_between(shard, source.clone(), destination.clone(), from, to)
+        .await
+        .map(bulk_or_null)
+}
+
+/// `LMPOP` whose keys live on several shards: the keys are tried in order, each on its owner,
+/// until one yields elements.
+pub async fn lmpop_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, end, count) = parse_lmpop(&mut args)?;
+    for key in keys {
+        let owned = key.clone();
+        let popped = shard
+            .run_on(shard.owner(key), move |db| pop_many(db, &owned, end, count))
+            .await?;
+        if let Some(popped) = popped {
+            return Ok(lmpop_reply(key.clone(), popped));
+        }
+    }
+    Ok(BytesFrame::Null)
+}
+
+/// Pops from `source` and pushes onto `destination`, which may be the same key. Both keys are
+/// type-checked before anything is popped.
+fn move_element(
+    db: &mut DataStore,
+    source: &Bytes,
+    destination: &Bytes,
+    from: End,
+    to: End,
+) -> Result<Option<Bytes>, CommandExecutionError> {
+    let now = clock::now();
+    list_mut(db, destination, now)?;
+    let Some(list) = list_mut(db, source, now)? else {
+        return Ok(None);
+    };
+    let value = pop_end(list, from).expect("stored lists are never empty");
+    remove_if_empty(db, source);
+
+    push_end(list_or_create(db, destination, now)?, to, value.clone());
+    Ok(Some(value))
+}
+
+/// Moves an element wherever the two keys live. When both are on one shard the move is a
+/// single atomic job.
+///
+/// Otherwise it takes two steps: the destination type is checked first, and if it changes
+/// before the push lands, the element is returned to the source.
+pub async fn move_between(
+    shard: &Shard,
+    source: Bytes,
+    destination: Bytes,
+    from: End,
+    to: End,
+) -> Result<Option<Bytes>, CommandExecutionError> {
+    let (source_owner, destination_owner) = (shard.owner(&source), shard.owner(&destination));
+    if source_owner == destination_owner {
+        return shard
+            .run_on(source_owner, move |db| move_element(db, &source, &destination, from, to))
+            .await;
+    }
+
+    let key = destination.clone();
+    shard
+        .run_on(destination_owner, move |db| list_mut(db, &key, clock::now()).map(|_| ()))
+        .await?;
+
+    let key = source.clone();
+    let popped = shard
+        .run_on(source_owner, move |db| {
+            let Some(list) = list_mut(db, &key, clock::now())? else {
+                return Ok(None);
+            };
+            let value = pop_end(list, from);
+            remove_if_empty(db, &key);
+            Ok(value)
+        })
+        .await?;
+    let Some(value) = popped else {
+        return Ok(None);
+    };
+
+    let pushed = value.clone();
+    let result = shard
+        .run_on(destination_owner, move |db| {
+            push_end(list_or_create(db, &destination, clock::now())?, to, pushed);
+            Ok(())
+        })
+        .await;
+    if let Err(err) = result {
+        let restored = value.clone();
+        shard
+            .run_on(source_owner, move |db| {
+                if let Ok(list) = list_or_create(db, &source, clock::now()) {
+                    push_end(list, from, restored);
+                }
+            })
+            .await;
+        return Err(err);
+    }
+    Ok(Some(value))
+}
+
+/// Pops up to `count` elements from `key`, or `None` if it does not exist.
+pub fn pop_many(
+    db: &mut DataStore,
+    key: &Bytes,
+    end: End,
+    count: usize,
+) -> Result<Option<Vec<Bytes>>, CommandExecutionError> {
+    let Some(list) = list_mut(db, key, clock::now())? else {
+        return Ok(None);
+    };
+    let popped = (0..count.min(list.len())).filter_map(|_| pop_end(list, end)).collect();
+    remove_if_empty(db, key);
+    Ok(Some(popped))
+}
+
+fn lmpop_reply(key: Bytes, popped: Vec<Bytes>) -> BytesFrame {
+    array(vec![bulk(key), array(popped.into_iter().map(bulk).collect())])
+}
+
+fn parse_lmove<'a>(
+    args: &mut Args<'a>,
+) -> Result<(&'a Bytes, &'a Bytes, End, End), CommandExecutionError> {
+    let source = args.next_bytes()?;
+    let destination = args.next_bytes()?;
+    let from = End::from_token(args.next_token()?)?;
+    let to = End::from_token(args.next_token()?)?;
+    args.finish()?;
+    Ok((source, destination, from, to))
+}
+
+/// Parses `numkeys key [key ...] LEFT|RIGHT [COUNT count]`.
+pub fn parse_lmpop<'a>(
+    args: &mut Args<'a>,
+) -> Result<(Vec<&'a Bytes>, End, usize), CommandExecutionError> {
+    let numkeys = args.next_i64()?;
+    if numkeys <= 0 {
+        return Err(CommandExecutionError::NumKeysNotPositive);
+    }
+    if numkeys as u64 > args.remaining() as u64 {
+        return Err(CommandExecutionError::NumKeysTooMany);
+    }
+    let keys = (0..numkeys).map(|_| args.next_bytes()).collect::<Result<Vec<_>, _>>()?;
+    let end = End::from_token(args.next_token()?)?;
+
+    let mut count = 1;
+    if !args.is_empty() {
+        let token = args.next_token()?;
+        if !token.is("COUNT") {
+            return Err(token.syntax_error());
+        }
+        let n = args.next_i64()?;
+        if n <= 0 {
+            return Err(CommandExecutionError::CountNotPositive);
+        }
+        count = n as usize;
+    }
+    args.finish()?;
+    Ok((keys, end, count))
+}
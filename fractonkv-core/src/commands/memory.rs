@@ -0,0 +1,237 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::{BytesFrame, FrameMap};
+
+use crate::{
+    allocator,
+    commands::{
+        args::Args,
+        reply::{bulk, double, integer},
+    },
+    errors::CommandExecutionError,
+    shard::{clock, lazyfree, memory::DEFAULT_SAMPLES, shard::Shard, types::lookup},
+};
+
+/// Below this much heap the doctor has too little to go on.
+const DOCTOR_MIN_ALLOCATED: usize = 5 * 1024 * 1024;
+
+/// `MEMORY USAGE key [SAMPLES count]`, `MEMORY STATS` and `MEMORY DOCTOR`.
+pub async fn handle_memory(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let subcommand = args.next_token()?;
+
+    if subcommand.is("USAGE") {
+        let key = args.next_bytes()?.clone();
+        let mut samples = DEFAULT_SAMPLES;
+        if !args.is_empty() {
+            let token = args.next_token()?;
+            if !token.is("SAMPLES") {
+                return Err(token.syntax_error());
+            }
+            let count = args.next_i64()?;
+            if count < 0 {
+                return Err(token.syntax_error());
+            }
+            samples = count as usize;
+        }
+        args.finish()?;
+
+        let usage = shard
+            .run_on(shard.owner(&key), move |db| {
+                lookup(db, &key, clock::now())?;
+                db.usage(&key, samples)
+            })
+            .await;
+        return Ok(usage.map_or(BytesFrame::Null, |bytes| integer(bytes as i64)));
+    }
+
+    if subcommand.is("STATS") {
+        args.finish()?;
+        return Ok(MemoryStats::collect(shard).await.to_frame());
+    }
+
+    if subcommand.is("DOCTOR") {
+        args.finish()?;
+        let report = MemoryStats::collect(shard).await.diagnose();
+        return Ok(bulk(report.into()));
+    }
+
+    Err(subcommand.unknown_subcommand("MEMORY"))
+}
+
+/// What one shard's keyspace holds, by its running estimate.
+struct ShardMemory {
+    keys: usize,
+    dataset: usize,
+    overhead: usize,
+}
+
+impl ShardMemory {
+    fn used(&self) -> usize {
+        self.dataset + self.overhead
+    }
+}
+
+/// Snapshot of the process heap and of every shard's keyspace.
+struct MemoryStats {
+    allocated: usize,
+    peak: usize,
+    resident: Option<usize>,
+    /// Each shard's share of `maxmemory`, if there is a limit.
+    limit: Option<usize>,
+    /// Values still waiting for the lazyfree thread.
+    lazyfree_pending: usize,
+    shards: Vec<ShardMemory>,
+}
+
+impl MemoryStats {
+    async fn collect(shard: &Shard) -> Self {
+        let shards = shard
+            .broadcast(|db| ShardMemory {
+                keys: db.len(),
+                dataset: db.dataset_size(),
+                overhead: db.overhead(),
+            })
+            .await;
+        Self {
+            allocated: allocator::allocated(),
+            peak: allocator::peak_allocated(),
+            resident: allocator::resident(),
+            limit: shard.memory_limit(),
+            lazyfree_pending: lazyfree::pending_objects(),
+            shards,
+        }
+    }
+
+    fn keys(&self) -> usize {
+        self.shards.iter().map(|shard| shard.keys).sum()
+    }
+
+    fn dataset(&self) -> usize {
+        self.shards.iter().map(|shard| shard.dataset).sum()
+    }
+
+    fn overhead(&self) -> usize {
+        self.shards.iter().map(|shard| shard.overhead).sum()
+    }
+
+    /// Resident set size over allocated heap, or 0 when the RSS is unknown.
+    fn fragmentation(&self) -> f64 {
+        match self.resident {
+            Some(resident) if self.allocated > 0 => resident as f64 / self.allocated as f64,
+            _ => 0.0,
+        }
+    }
+
+    fn to_frame(&self) -> BytesFrame {
+        let keys = self.keys();
+        let dataset = self.dataset();
+        let overhead = self.overhead();
+        let resident = self.resident.unwrap_or(0);
+
+        let mut map = FrameMap::new();
+        let mut put = |name: &str, value: BytesFrame| {
+            map.insert(bulk(Bytes::copy_from_slice(name.as_bytes())), value);
+        };
+        put("peak.allocated", integer(self.peak as i64));
+        put("total.allocated", integer(self.allocated as i64));
+        put("overhead.total", integer(overhead as i64));
+        put("keys.count", integer(keys as i64));
+        put("keys.bytes-per-key", integer(((dataset + overhead) / keys.max(1)) as i64));
+        put("dataset.bytes", integer(dataset as i64));
+        put("dataset.percentage", double(percentage(dataset, self.allocated)));
+        put("peak.percentage", double(percentage(self.allocated, self.peak)));
+        put("allocator.resident", integer(resident as i64));
+        put("fragmentation", double(self.fragmentation()));
+        put("fragmentation.bytes", integer(resident as i64 - self.allocated as i64));
+        put("lazyfree.pending_objects", integer(self.lazyfree_pending as i64));
+        for (id, shard) in self.shards.iter().enumerate() {
+            let mut stats = FrameMap::new();
+            stats.insert(bulk("keys".into()), integer(shard.keys as i64));
+            stats.insert(bulk("dataset.bytes".into()), integer(shard.dataset as i64));
+            stats.insert(bulk("overhead.hashtable.main".into()), integer(shard.overhead as i64));
+            if let Some(limit) = self.limit {
+                stats.insert(bulk("maxmemory".into()), integer(limit as i64));
+            }
+            put(&format!("shard.{id}"), BytesFrame::Map { data: stats, attributes: None });
+        }
+        BytesFrame::Map { data: map, attributes: None }
+    }
+
+    /// Plain-text report of anything unusual about memory use, in the manner of Redis'
+    /// `MEMORY DOCTOR`.
+    fn diagnose(&self) -> String {
+        if self.allocated < DOCTOR_MIN_ALLOCATED {
+            return "This instance is empty or is using very little memory, so there is not \
+                    enough to analyze. Fill it with some data and ask again."
+                .to_string();
+        }
+
+        let mut issues = Vec::new();
+        if self.peak as f64 > self.allocated as f64 * 1.5 {
+            issues.push(
+                "Peak memory: at some point this instance used more than 150% of the memory it \
+                 uses now. The allocator usually keeps memory freed after a peak, which shows \
+                 up as fragmentation; it will be reused as the dataset grows again."
+                    .to_string(),
+            );
+        }
+        if self.fragmentation() > 1.4 {
+            issues.push(format!(
+                "High fragmentation: the resident set is {:.2} times the allocated heap. This \
+                 is typical after many deletions or after values shrank; restarting the \
+                 instance is the only way to give the memory back to the OS.",
+                self.fragmentation()
+            ));
+        }
+
+        let used: Vec<usize> = self.shards.iter().map(ShardMemory::used).collect();
+        let mean = used.iter().sum::<usize>() / used.len().max(1);
+        if let Some((id, &largest)) = used.iter().enumerate().max_by_key(|(_, used)| **used)
+            && mean > 0
+            && largest > mean * 2
+        {
+            issues.push(format!(
+                "Shard imbalance: shard {id} holds {largest} bytes against an average of \
+                 {mean}. Keys sharing a {{hashtag}} always land on the same shard; spreading \
+                 them over more hashtags evens the load."
+            ));
+        }
+        if let Some(limit) = self.limit {
+            let full: Vec<String> = used
+                .iter()
+                .enumerate()
+                .filter(|(_, used)| **used * 10 > limit * 9)
+                .map(|(id, _)| id.to_string())
+                .collect();
+            if !full.is_empty() {
+                issues.push(format!(
+                    "Near maxmemory: shard(s) {} are above 90% of their {limit} byte share of \
+                     maxmemory, so writes there will start evicting keys or failing depending \
+                     on maxmemory-policy.",
+                    full.join(", ")
+                ));
+            }
+        }
+
+        if issues.is_empty() {
+            return "I can't find any memory issue in this instance.".to_string();
+        }
+        let mut report = String::from("A few memory issues were detected in this instance:\n\n");
+        for issue in issues {
+            report.push_str(" * ");
+            report.push_str(&issue);
+            report.push_str("\n\n");
+        }
+        report
+    }
+}
+
+fn percentage(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
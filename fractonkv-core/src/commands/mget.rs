@@ -0,0 +1,50 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{array, bulk_or_null},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        shard::Shard,
+        types::{DataStore, lookup_mut},
+    },
+};
+
+pub fn handle_mget(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys = args.rest_bytes()?;
+    let now = clock::now();
+    Ok(array(
+        keys.into_iter().map(|key| bulk_or_null(read_string(db, key, now))).collect(),
+    ))
+}
+
+/// `MGET` over keys owned by several shards.
+pub async fn mget_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys: Vec<Bytes> = args.rest_bytes()?.into_iter().cloned().collect();
+    let values = shard
+        .scatter_gather(
+            keys,
+            |key| key,
+            |db, keys| {
+                let now = clock::now();
+                keys.iter().map(|key| read_string(db, key, now)).collect()
+            },
+        )
+        .await;
+    Ok(array(values.into_iter().map(bulk_or_null).collect()))
+}
+
+/// String value of `key`; missing keys and other types read as `None`, as `MGET` requires.
+fn read_string(db: &mut DataStore, key: &Bytes, now: UnixMillis) -> Option<Bytes> {
+    lookup_mut(db, key, now)?.data.string_bytes()
+}
@@ -1,13 +1,50 @@
+pub(crate) mod args;
 pub(crate) mod dispatcher;
 
+mod blocking;
+mod client;
+mod config;
+mod expire;
 mod get;
+mod glob;
+mod hash;
+mod hash_ttl;
+mod incr;
+mod keyspace;
+mod lcs;
+mod list;
+mod lmove;
+mod memory;
+mod mget;
+mod mset;
+mod object;
+mod random;
+pub(crate) mod reply;
+mod scan;
 mod set;
+mod sets;
+mod string;
+mod zset;
+mod zset_algebra;
 
+use crate::commands::args::parse_i64;
 use crate::errors::FrameError;
 
+use bytes::Bytes;
 use fractonkv_macros::generate_command_kind;
 use redis_protocol::resp3::types::BytesFrame;
 
+/// Location of key arguments in a command line, generated from the `key_specs` of the command
+/// JSON. Indexes are absolute argv positions, where the command name is 0.
+#[derive(Debug, Clone, Copy)]
+pub enum KeySpec {
+    /// Keys from `first` through `last` every `step` arguments; a negative `last` counts from
+    /// the end of argv (-1 is the last argument).
+    Range { first: usize, last: isize, step: usize },
+    /// The argument at `numkeys` holds a key count; keys start at `first`, every `step`.
+    KeyNum { numkeys: usize, first: usize, step: usize },
+}
+
 #[generate_command_kind]
 pub enum CommandKind {}
 
@@ -27,4 +64,50 @@ impl CommandKind {
 
         cmd_str.parse::<CommandKind>().map_err(|_| FrameError::UnknownCommand)
     }
+
+    /// Checks an argument count (including the command name) against the spec arity: a positive
+    /// arity is exact, a negative one is a minimum.
+    pub fn accepts_argc(&self, argc: usize) -> bool {
+        let arity = self.arity() as isize;
+        let argc = argc as isize;
+        if arity >= 0 {
+            argc == arity
+        } else {
+            argc >= -arity
+        }
+    }
+
+    /// Extracts the key arguments from `args` (argv without the command name) according to the
+    /// command's key specs. Malformed key counts yield no keys; the handler reports the error.
+    pub fn keys<'a>(&self, args: &'a [BytesFrame]) -> Vec<&'a Bytes> {
+        let argc = args.len() as isize + 1;
+        let arg = |index: usize| match args.get(index.wrapping_sub(1)) {
+            Some(BytesFrame::BlobString { data, .. } | BytesFrame::SimpleString { data, .. }) => {
+                Some(data)
+            }
+            _ => None,
+        };
+
+        let mut keys = Vec::new();
+        for spec in self.key_specs() {
+            let (first, last, step) = match *spec {
+                KeySpec::Range { first, last, step } => {
+                    let last = if last < 0 { argc + last } else { last };
+                    (first, last, step)
+                }
+                KeySpec::KeyNum { numkeys, first, step } => {
+                    let Some(count) = arg(numkeys).and_then(|n| parse_i64(n)) else {
+                        continue;
+                    };
+                    (first, first as isize + (count - 1) as isize * step as isize, step)
+                }
+            };
+            let last = last.min(argc - 1);
+            if last < first as isize {
+                continue;
+            }
+            keys.extend((first..=last as usize).step_by(step).filter_map(arg));
+        }
+        keys
+    }
 }
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{integer, ok},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        shard::Shard,
+        types::{DataKind, DataStore, StoreObject},
+    },
+};
+
+pub fn handle_mset(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let pairs = parse_pairs(&mut args)?;
+    write_pairs(db, pairs);
+    Ok(ok())
+}
+
+pub fn handle_msetnx(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let pairs = parse_pairs(&mut args)?;
+    Ok(integer(insert_if_all_absent(db, pairs) as i64))
+}
+
+/// `MSET` over keys owned by several shards. Each shard applies its own pairs atomically, but
+/// the shards are not synchronised with each other.
+pub async fn mset_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let pairs = parse_pairs(&mut args)?;
+    shard
+        .scatter_gather(
+            pairs,
+            |(key, _)| key,
+            |db, pairs| {
+                let written = pairs.len();
+                write_pairs(db, pairs);
+                vec![(); written]
+            },
+        )
+        .await;
+    Ok(ok())
+}
+
+/// `MSETNX` over keys owned by several shards, all-or-nothing.
+///
+/// Every shard first reserves its keys if none of them exists; nothing is written unless all
+/// shards agree. The reservations are then committed, or released if some key was taken. A
+/// key written by another command between the two steps supersedes its reservation and keeps
+/// that command's value, as if it had run right after this one.
+pub async fn msetnx_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let pairs = parse_pairs(&mut args)?;
+    let keys: Vec<Bytes> = pairs.iter().map(|(key, _)| key.clone()).collect();
+    let reserved = shard
+        .scatter_gather(
+            keys.clone(),
+            |key| key,
+            |db, keys| vec![db.reserve(&keys, clock::now()); keys.len()],
+        )
+        .await;
+
+    if reserved.iter().all(|&reserved| reserved) {
+        shard.scatter_gather(pairs, |(key, _)| key, commit_pairs).await;
+        return Ok(integer(1));
+    }
+
+    let held = keys.into_iter().zip(reserved).filter(|(_, reserved)| *reserved);
+    shard
+        .scatter_gather(
+            held.map(|(key, _)| key).collect(),
+            |key| key,
+            |db, keys| keys.iter().map(|key| _ = db.release(key)).collect(),
+        )
+        .await;
+    Ok(integer(0))
+}
+
+fn parse_pairs(args: &mut Args) -> Result<Vec<(Bytes, Bytes)>, CommandExecutionError> {
+    if !args.remaining().is_multiple_of(2) {
+        return Err(args.wrong_arity());
+    }
+    let mut pairs = Vec::with_capacity(args.remaining() / 2);
+    while !args.is_empty() {
+        pairs.push((args.next_bytes()?.clone(), args.next_bytes()?.clone()));
+    }
+    Ok(pairs)
+}
+
+fn write_pairs(db: &mut DataStore, pairs: Vec<(Bytes, Bytes)>) {
+    let now = clock::now();
+    for (key, value) in pairs {
+        db.insert(key, StoreObject::new(DataKind::from_string(value), now));
+    }
+}
+
+/// Second step of [`msetnx_cross_shard`]: writes the pairs whose key is still reserved.
+fn commit_pairs(db: &mut DataStore, pairs: Vec<(Bytes, Bytes)>) -> Vec<()> {
+    let written = pairs.len();
+    let held: HashSet<Bytes> = pairs
+        .iter()
+        .filter(|(key, _)| db.release(key))
+        .map(|(key, _)| key.clone())
+        .collect();
+    write_pairs(db, pairs.into_iter().filter(|(key, _)| held.contains(key)).collect());
+    vec![(); written]
+}
+
+/// Writes every pair if none of the keys currently exists; returns whether it wrote.
+fn insert_if_all_absent(db: &mut DataStore, pairs: Vec<(Bytes, Bytes)>) -> bool {
+    let now = clock::now();
+    if pairs.iter().any(|(key, _)| db.get(key).is_some_and(|obj| !obj.is_expired(now))) {
+        return false;
+    }
+    write_pairs(db, pairs);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use crate::{
+        commands::reply::{array, bulk, integer},
+        shard::testing::Cluster,
+    };
+
+    #[tokio::test]
+    async fn msetnx_across_shards_writes_nothing_if_any_key_exists() {
+        let cluster = Cluster::start(3);
+        let mut client = cluster.connect().await;
+        let keys = cluster.keys_on_distinct_shards("msetnx:", 3);
+        let (a, b, c) = (keys[0].as_str(), keys[1].as_str(), keys[2].as_str());
+
+        client.call(&["SET", c, "old"]).await;
+        let reply = client.call(&["MSETNX", a, "1", b, "2", c, "3"]).await;
+        assert_eq!(reply, integer(0));
+        assert_eq!(
+            client.call(&["MGET", a, b, c]).await,
+            array(vec![BytesFrame::Null, BytesFrame::Null, bulk("old".into())])
+        );
+
+        client.call(&["DEL", c]).await;
+        let reply = client.call(&["MSETNX", a, "1", b, "2", c, "3", a, "4"]).await;
+        assert_eq!(reply, integer(1));
+        assert_eq!(
+            client.call(&["MGET", a, b, c]).await,
+            array(vec![bulk("4".into()), bulk("2".into()), bulk("3".into())])
+        );
+
+        // Released reservations do not hold keys back from later writes.
+        let keys = cluster.keys_on_distinct_shards("released:", 2);
+        client.call(&["SET", &keys[1], "old"]).await;
+        assert_eq!(client.call(&["MSETNX", &keys[0], "1", &keys[1], "2"]).await, integer(0));
+        client.call(&["DEL", &keys[1]]).await;
+        assert_eq!(client.call(&["MSETNX", &keys[0], "1", &keys[1], "2"]).await, integer(1));
+    }
+}
@@ -0,0 +1,124 @@
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{array, integer},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        memory::{self, DEFAULT_SAMPLES},
+        types::{DataKind, DataStore, StoreObject, lookup},
+    },
+};
+
+/// Mask of Redis' 24-bit LRU clock, which `DEBUG OBJECT` reports in seconds.
+const LRU_CLOCK_MAX: u64 = (1 << 24) - 1;
+
+const OBJECT_HELP: &[&str] = &[
+    "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+    "ENCODING <key>",
+    "    Return the kind of internal representation used in order to store the value",
+    "    associated with a <key>.",
+    "FREQ <key>",
+    "    Return the access frequency index of the <key>. The returned integer is",
+    "    proportional to the logarithm of the recent access frequency of the key.",
+    "IDLETIME <key>",
+    "    Return the idle time of the <key>, that is the approximated number of",
+    "    seconds elapsed since the last access to the key.",
+    "REFCOUNT <key>",
+    "    Return the number of references of the value associated with the specified",
+    "    <key>.",
+    "HELP",
+    "    Print this help.",
+];
+
+const DEBUG_HELP: &[&str] = &[
+    "DEBUG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+    "OBJECT <key>",
+    "    Show low-level info about the <key> and associated value.",
+    "HELP",
+    "    Print this help.",
+];
+
+/// `OBJECT ENCODING|FREQ|IDLETIME|REFCOUNT key` and `OBJECT HELP`. Missing keys reply nil,
+/// and inspecting a key this way does not count as an access.
+///
+/// Access times and LFU counters are kept whatever the `maxmemory-policy`, so unlike Redis
+/// `IDLETIME` and `FREQ` both work under every policy.
+pub fn handle_object(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let subcommand = args.next_token()?;
+    if subcommand.is("HELP") {
+        args.finish()?;
+        return Ok(help(OBJECT_HELP));
+    }
+
+    let reply: fn(&StoreObject, UnixMillis) -> BytesFrame = if subcommand.is("ENCODING") {
+        |obj, _| BytesFrame::BlobString { data: obj.data.encoding().into(), attributes: None }
+    } else if subcommand.is("FREQ") {
+        |obj, now| integer(obj.lfu.value(now) as i64)
+    } else if subcommand.is("IDLETIME") {
+        |obj, now| integer((now.saturating_sub(obj.last_accessed) / 1000) as i64)
+    } else if subcommand.is("REFCOUNT") {
+        // Values are never shared between keys.
+        |_, _| integer(1)
+    } else {
+        return Err(subcommand.unknown_subcommand("OBJECT"));
+    };
+
+    let key = args.next_bytes()?;
+    args.finish()?;
+    let now = clock::now();
+    Ok(lookup(db, key, now).map_or(BytesFrame::Null, |obj| reply(obj, now)))
+}
+
+/// `DEBUG OBJECT key`, a one-line dump of a key's internals in Redis' format, and
+/// `DEBUG HELP`.
+///
+/// `serializedlength` is the estimated size of the value's payload, as there is no RDB
+/// encoding to measure; `lru` is the last access on Redis' 24-bit seconds clock. Lists add
+/// their chunk count as `ql_nodes`.
+pub fn handle_debug(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let subcommand = args.next_token()?;
+    if subcommand.is("HELP") {
+        args.finish()?;
+        return Ok(help(DEBUG_HELP));
+    }
+    if !subcommand.is("OBJECT") {
+        return Err(subcommand.unknown_subcommand("DEBUG"));
+    }
+
+    let key = args.next_bytes()?;
+    args.finish()?;
+    let now = clock::now();
+    let obj = lookup(db, key, now).ok_or(CommandExecutionError::NoSuchKey)?;
+
+    let mut info = format!(
+        "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru:{} lru_seconds_idle:{}",
+        obj,
+        obj.data.encoding(),
+        memory::value_size(&obj.data, DEFAULT_SAMPLES),
+        (obj.last_accessed / 1000) & LRU_CLOCK_MAX,
+        now.saturating_sub(obj.last_accessed) / 1000,
+    );
+    if let DataKind::List(list) = &obj.data {
+        info.push_str(&format!(" ql_nodes:{}", list.chunk_count()));
+    }
+    Ok(BytesFrame::SimpleString { data: info.into(), attributes: None })
+}
+
+fn help(lines: &[&'static str]) -> BytesFrame {
+    array(
+        lines
+            .iter()
+            .map(|line| BytesFrame::SimpleString { data: (*line).into(), attributes: None })
+            .collect(),
+    )
+}
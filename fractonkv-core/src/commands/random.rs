@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use rand::seq::IteratorRandom;
+
+use crate::errors::CommandExecutionError;
+
+/// Most elements a negative count returns. Redis streams any number of repeated picks, but
+/// replies are built whole here, so a huge count would have to be allocated up front.
+const MAX_REPEATED: usize = 1 << 20;
+
+/// Picks elements for the `count` argument of `HRANDFIELD`, `SRANDMEMBER`, `ZRANDMEMBER` and
+/// `SPOP`, from a collection of `len` elements keyed by field or member.
+///
+/// A positive count returns up to `count` distinct elements: a large share of the collection
+/// is sampled from a full walk of `all`, a small one from repeated `random` picks, so that a
+/// few elements out of millions cost a few lookups. A negative count returns `|count|`
+/// elements, capped at [`MAX_REPEATED`], that may repeat. Counts below `-(i64::MAX / 2)` are
+/// out of range, as in Redis.
+pub fn sample<V>(
+    count: i64,
+    len: usize,
+    all: impl Iterator<Item = (Bytes, V)>,
+    mut random: impl FnMut() -> Option<(Bytes, V)>,
+) -> Result<Vec<(Bytes, V)>, CommandExecutionError> {
+    if count < -(i64::MAX / 2) {
+        return Err(CommandExecutionError::ValueOutOfRange);
+    }
+    if count < 0 {
+        let picks = (count.unsigned_abs() as usize).min(MAX_REPEATED);
+        return Ok(std::iter::from_fn(random).take(picks).collect());
+    }
+
+    let count = (count as u64).min(len as u64) as usize;
+    if count.saturating_mul(3) > len {
+        return Ok(all.choose_multiple(&mut rand::rng(), count));
+    }
+    let mut seen = HashSet::with_capacity(count);
+    let mut picked = Vec::with_capacity(count);
+    while picked.len() < count {
+        let Some((key, value)) = random() else {
+            break;
+        };
+        if seen.insert(key.clone()) {
+            picked.push((key, value));
+        }
+    }
+    Ok(picked)
+}
@@ -0,0 +1,46 @@
+//! Shorthand constructors for the reply frames handlers return most often.
+
+use bytes::Bytes;
+use redis_protocol::resp3::types::{BytesFrame, FrameSet};
+
+pub fn ok() -> BytesFrame {
+    BytesFrame::SimpleString { data: "OK".into(), attributes: None }
+}
+
+pub fn integer(data: i64) -> BytesFrame {
+    BytesFrame::Number { data, attributes: None }
+}
+
+pub fn bulk(data: Bytes) -> BytesFrame {
+    BytesFrame::BlobString { data, attributes: None }
+}
+
+pub fn double(data: f64) -> BytesFrame {
+    BytesFrame::Double { data, attributes: None }
+}
+
+pub fn bulk_or_null(value: Option<Bytes>) -> BytesFrame {
+    value.map_or(BytesFrame::Null, bulk)
+}
+
+/// Formats a double the way `INCRBYFLOAT` does: plain notation, no trailing zeros, and rounded
+/// to 16 significant digits so that `0.1 + 0.2` reads back as `0.3`.
+pub fn format_f64(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let rounded: f64 = format!("{value:.15e}").parse().unwrap_or(value);
+    format!("{rounded}")
+}
+
+pub fn array(data: Vec<BytesFrame>) -> BytesFrame {
+    BytesFrame::Array { data, attributes: None }
+}
+
+/// RESP3 set of bulk strings, the reply of the set commands that return members.
+pub fn set_of(members: impl IntoIterator<Item = Bytes>) -> BytesFrame {
+    BytesFrame::Set {
+        data: members.into_iter().map(bulk).collect::<FrameSet<_>>(),
+        attributes: None,
+    }
+}
@@ -0,0 +1,379 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        glob::glob_match,
+        hash::hash_mut,
+        reply::{array, bulk, format_f64},
+        sets::set_mut,
+        zset::zset_mut,
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        shard::Shard,
+        types::{DataStore, StoreObject, lookup},
+    },
+};
+
+/// Low bits of a `SCAN` cursor that hold the index of the shard being walked; the bits above
+/// are the position inside that shard's [`Dict`](crate::shard::dict::Dict) walk. This allows
+/// up to 1024 shards.
+const SHARD_BITS: u32 = 10;
+
+const SHARD_MASK: u64 = (1 << SHARD_BITS) - 1;
+
+/// `COUNT` when the client gives none.
+const DEFAULT_COUNT: usize = 10;
+
+/// Buckets visited per requested key before a call gives up on filling `COUNT`, so that
+/// sparse tables cannot make a single call walk all of them.
+const BUCKETS_PER_KEY: usize = 10;
+
+/// Values accepted by `SCAN ... TYPE`.
+const TYPE_NAMES: [&str; 5] = ["string", "list", "set", "zset", "hash"];
+
+/// What a scan command walks, which decides the options it accepts on top of `MATCH` and
+/// `COUNT`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Walk {
+    /// `SCAN`, which also takes `TYPE`.
+    Keyspace,
+    /// `HSCAN`, which also takes `NOVALUES`.
+    Hash,
+    /// `SSCAN` and `ZSCAN`.
+    Members,
+}
+
+/// Options of the scan commands.
+#[derive(Clone)]
+struct ScanOptions {
+    pattern: Option<Bytes>,
+    count: usize,
+    type_name: Option<&'static str>,
+    no_values: bool,
+}
+
+impl ScanOptions {
+    /// Parses the options following the cursor.
+    fn parse(args: &mut Args, walk: Walk) -> Result<Self, CommandExecutionError> {
+        let mut options = ScanOptions {
+            pattern: None,
+            count: DEFAULT_COUNT,
+            type_name: None,
+            no_values: false,
+        };
+        while !args.is_empty() {
+            let token = args.next_token()?;
+            if token.is("MATCH") {
+                let pattern = args.next_bytes()?;
+                options.pattern = (pattern.as_ref() != b"*").then(|| pattern.clone());
+            } else if token.is("COUNT") {
+                let count = args.next_i64()?;
+                if count < 1 {
+                    return Err(token.syntax_error());
+                }
+                options.count = count as usize;
+            } else if walk == Walk::Keyspace && token.is("TYPE") {
+                let name = args.next_bytes()?;
+                let known =
+                    TYPE_NAMES.iter().find(|known| name.eq_ignore_ascii_case(known.as_bytes()));
+                options.type_name = Some(*known.ok_or_else(|| {
+                    CommandExecutionError::UnknownTypeName(
+                        String::from_utf8_lossy(name).into_owned(),
+                    )
+                })?);
+            } else if walk == Walk::Hash && token.is("NOVALUES") {
+                options.no_values = true;
+            } else {
+                return Err(token.syntax_error());
+            }
+        }
+        Ok(options)
+    }
+
+    fn matches(&self, name: &[u8]) -> bool {
+        self.pattern.as_ref().is_none_or(|pattern| glob_match(pattern, name, false))
+    }
+
+    fn accepts(&self, obj: &StoreObject) -> bool {
+        self.type_name.is_none_or(|name| obj.data.type_name() == name)
+    }
+}
+
+/// `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]` over every shard in turn.
+///
+/// The cursor names a shard and a position in its walk, so a call only ever touches one shard
+/// at a time, moving on to the next once a shard is exhausted and `COUNT` is not reached yet.
+/// Every key present for the whole scan is returned at least once.
+pub async fn handle_scan(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let cursor = parse_cursor(args.next_bytes()?)?;
+    let options = ScanOptions::parse(&mut args, Walk::Keyspace)?;
+
+    let shards = shard.peers.len() as u64;
+    let (mut target, mut position) = (cursor & SHARD_MASK, cursor >> SHARD_BITS);
+    let mut keys = Vec::new();
+    let next = loop {
+        if target >= shards {
+            break 0;
+        }
+        let job_options = options.clone();
+        let (next, found) = shard
+            .run_on(target as usize, move |db| scan_shard(db, position, &job_options))
+            .await;
+        keys.extend(found);
+        if next != 0 {
+            break next << SHARD_BITS | target;
+        }
+        (target, position) = (target + 1, 0);
+        if keys.len() >= options.count {
+            break if target < shards { target } else { 0 };
+        }
+    };
+
+    Ok(scan_reply(next, keys))
+}
+
+/// `HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]`. Fields past their TTL are
+/// dropped before the walk step and never returned.
+pub fn handle_hscan(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let cursor = parse_cursor(args.next_bytes()?)?;
+    let options = ScanOptions::parse(&mut args, Walk::Hash)?;
+
+    let Some(hash) = hash_mut(db, key, clock::now())? else {
+        return Ok(scan_reply(0, Vec::new()));
+    };
+    let (next, pairs) = walk(cursor, options.count, |cursor, pairs| {
+        hash.scan(cursor, |field, value| pairs.push((field.clone(), value.clone())))
+    });
+    let items = pairs
+        .into_iter()
+        .filter(|(field, _)| options.matches(field))
+        .flat_map(|(field, value)| [Some(field), (!options.no_values).then_some(value)])
+        .flatten()
+        .collect();
+    Ok(scan_reply(next, items))
+}
+
+/// `SSCAN key cursor [MATCH pattern] [COUNT count]`.
+pub fn handle_sscan(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let cursor = parse_cursor(args.next_bytes()?)?;
+    let options = ScanOptions::parse(&mut args, Walk::Members)?;
+
+    let Some(set) = set_mut(db, key, clock::now())? else {
+        return Ok(scan_reply(0, Vec::new()));
+    };
+    let (next, mut members) = walk(cursor, options.count, |cursor, members| {
+        set.scan(cursor, |member| members.push(member.clone()))
+    });
+    members.retain(|member| options.matches(member));
+    Ok(scan_reply(next, members))
+}
+
+/// `ZSCAN key cursor [MATCH pattern] [COUNT count]`. Members come in hash order, each followed
+/// by its score as a string.
+pub fn handle_zscan(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let cursor = parse_cursor(args.next_bytes()?)?;
+    let options = ScanOptions::parse(&mut args, Walk::Members)?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(scan_reply(0, Vec::new()));
+    };
+    let (next, entries) = walk(cursor, options.count, |cursor, entries| {
+        zset.scan(cursor, |member, score| entries.push((member.clone(), score)))
+    });
+    let items = entries
+        .into_iter()
+        .filter(|(member, _)| options.matches(member))
+        .flat_map(|(member, score)| [member, Bytes::from(format_f64(score))])
+        .collect();
+    Ok(scan_reply(next, items))
+}
+
+/// `KEYS pattern`, gathered from every shard. Each shard is busy for a full pass over its
+/// keys, which is why `SCAN` is preferred on large databases.
+pub async fn handle_keys(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let pattern = args.next_bytes()?.clone();
+    args.finish()?;
+
+    let keys = shard
+        .broadcast(move |db| {
+            let now = clock::now();
+            db.iter()
+                .filter(|(key, obj)| glob_match(&pattern, key, false) && !obj.is_expired(now))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        })
+        .await;
+    Ok(array(keys.into_iter().flatten().map(bulk).collect()))
+}
+
+/// One step of a `SCAN` on the shard holding `db`: a [`walk`] from `position`, then filtering.
+/// Keys found expired are reclaimed on the way.
+fn scan_shard(db: &mut DataStore, position: u64, options: &ScanOptions) -> (u64, Vec<Bytes>) {
+    let (position, mut keys) = walk(position, options.count, |position, keys| {
+        db.scan(position, |key, _| keys.push(key.clone()))
+    });
+
+    let now = clock::now();
+    keys.retain(|key| {
+        options.matches(key) && lookup(db, key, now).is_some_and(|obj| options.accepts(obj))
+    });
+    (position, keys)
+}
+
+/// Runs `step`, one bucket of a [`Dict`](crate::shard::dict::Dict) walk that pushes what it
+/// finds, from `cursor` until `count` items are collected, the walk ends or the bucket budget
+/// runs out. Returns the cursor to resume from along with the items.
+fn walk<T>(
+    mut cursor: u64,
+    count: usize,
+    mut step: impl FnMut(u64, &mut Vec<T>) -> u64,
+) -> (u64, Vec<T>) {
+    let mut items = Vec::new();
+    let mut budget = count.saturating_mul(BUCKETS_PER_KEY);
+    loop {
+        cursor = step(cursor, &mut items);
+        budget -= 1;
+        if cursor == 0 || budget == 0 || items.len() >= count {
+            return (cursor, items);
+        }
+    }
+}
+
+/// `[cursor, [item ...]]`, the reply shape shared by the scan commands.
+fn scan_reply(cursor: u64, items: Vec<Bytes>) -> BytesFrame {
+    array(vec![
+        bulk(cursor.to_string().into()),
+        array(items.into_iter().map(bulk).collect()),
+    ])
+}
+
+/// Parses a scan cursor, an unsigned 64-bit integer.
+fn parse_cursor(bytes: &[u8]) -> Result<u64, CommandExecutionError> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandExecutionError::InvalidCursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use super::{SHARD_BITS, SHARD_MASK, ScanOptions, scan_shard};
+    use crate::{
+        commands::{CommandKind, dispatcher::run},
+        shard::{testing::Cluster, types::DataStore},
+    };
+
+    fn options(count: usize) -> ScanOptions {
+        ScanOptions {
+            pattern: None,
+            count,
+            type_name: None,
+            no_values: false,
+        }
+    }
+
+    /// Cursor and keys of a `SCAN` reply.
+    fn parse_reply(reply: BytesFrame) -> (u64, Vec<String>) {
+        let BytesFrame::Array { data, .. } = reply else {
+            panic!("SCAN did not reply with an array: {reply:?}");
+        };
+        let text = |frame: &BytesFrame| match frame {
+            BytesFrame::BlobString { data, .. } => String::from_utf8(data.to_vec()).unwrap(),
+            other => panic!("expected a bulk string, got {other:?}"),
+        };
+        let BytesFrame::Array { data: keys, .. } = &data[1] else {
+            panic!("SCAN did not reply with a key array: {data:?}");
+        };
+        (text(&data[0]).parse().unwrap(), keys.iter().map(text).collect())
+    }
+
+    #[test]
+    fn keys_present_throughout_are_returned_across_grow_and_shrink() {
+        let mut db = DataStore::default();
+        let original: HashSet<Bytes> = (0..100).map(|i| Bytes::from(format!("key:{i}"))).collect();
+        for key in &original {
+            run(&mut db, CommandKind::Set, &[std::str::from_utf8(key).unwrap(), "v"]);
+        }
+
+        let mut seen = HashSet::new();
+        let mut position = 0;
+        for step in 0.. {
+            let (next, keys) = scan_shard(&mut db, position, &options(10));
+            seen.extend(keys);
+            position = next;
+            if position == 0 {
+                break;
+            }
+            // Grow the table well past its size early in the walk, then shrink it back.
+            let extra = (0..1000).map(|i| format!("extra:{i}"));
+            match step {
+                1 => extra.for_each(|key| drop(run(&mut db, CommandKind::Set, &[&key, "v"]))),
+                4 => extra.for_each(|key| drop(run(&mut db, CommandKind::Del, &[&key]))),
+                _ => {}
+            }
+        }
+        assert!(original.is_subset(&seen), "missed {:?}", original.difference(&seen));
+    }
+
+    #[tokio::test]
+    async fn the_cursor_walks_every_shard_in_turn() {
+        let cluster = Cluster::start(3);
+        let mut client = cluster.connect().await;
+        let keys: HashSet<String> = (0..60).map(|i| format!("scan:{i}")).collect();
+        for key in &keys {
+            client.call(&["SET", key, "v"]).await;
+        }
+
+        let mut seen = HashSet::new();
+        let mut shards_visited = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, found) =
+                parse_reply(client.call(&["SCAN", &cursor.to_string(), "COUNT", "4"]).await);
+            seen.extend(found);
+            if next == 0 {
+                break;
+            }
+            let shard = next & SHARD_MASK;
+            assert!(shard < 3, "cursor {next} names shard {shard}");
+            assert!(shards_visited.last().is_none_or(|&last| last <= shard));
+            if shards_visited.last() != Some(&shard) {
+                shards_visited.push(shard);
+            }
+            cursor = next;
+        }
+        assert_eq!(seen, keys);
+        assert!(shards_visited.len() > 1, "the scan never moved past one shard");
+
+        // A cursor naming a shard past the last ends the scan.
+        let past_the_end = (5 << SHARD_BITS | 3).to_string();
+        assert_eq!(parse_reply(client.call(&["SCAN", &past_the_end]).await), (0, Vec::new()));
+    }
+}
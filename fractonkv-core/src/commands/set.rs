@@ -1,128 +1,330 @@
+use crate::commands::args::{Args, Token};
+use crate::commands::reply::{bulk_or_null, integer, ok};
+use crate::config::CONFIG;
 use crate::errors::CommandExecutionError;
-use crate::shard::types::DataStore;
+use crate::shard::clock::{self, UnixMillis};
+use crate::shard::types::{DataKind, DataStore, StoreObject};
+use bytes::Bytes;
 use redis_protocol::resp3::types::BytesFrame;
 
-pub fn handle_set(
-    args: &[BytesFrame],
+/// Condition that must hold on the existing key before `SET` writes.
+#[derive(Debug, Default)]
+pub enum SetCondition {
+    #[default]
+    Always,
+    /// Only set if the key does not exist.
+    Nx,
+    /// Only set if the key already exists.
+    Xx,
+    /// Only set if the key holds a string equal to the given value.
+    IfEq(Bytes),
+}
+
+/// What happens to the key's TTL when it is overwritten.
+#[derive(Debug, Default)]
+pub enum SetExpiry {
+    /// Drop any existing TTL.
+    #[default]
+    Persist,
+    /// Retain the TTL of the previous value.
+    KeepTtl,
+    /// Expire at the given Unix time; one already past deletes the key right after the write.
+    At(UnixMillis),
+    /// Expire after the given milliseconds, restarted by every access.
+    Sliding(u64),
+}
+
+#[derive(Debug, Default)]
+pub struct SetOptions {
+    pub condition: SetCondition,
+    pub expiry: SetExpiry,
+    /// Expire after this many milliseconds without access, on top of `expiry`.
+    pub idle_timeout: Option<u64>,
+    pub get: bool,
+}
+
+/// Result of a conditional write.
+pub struct SetOutcome {
+    pub applied: bool,
+    /// Previous string value; only looked up when `SetOptions::get` is set.
+    pub old: Option<Bytes>,
+}
+
+pub fn handle_set(mut args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?.clone();
+    let value = args.next_bytes()?.clone();
+    let options = parse_set_options(&mut args)?;
+    let get = options.get;
+
+    let outcome = set_value(db, key, value, options)?;
+    if get {
+        return Ok(bulk_or_null(outcome.old));
+    }
+    Ok(if outcome.applied {
+        ok()
+    } else {
+        BytesFrame::Null
+    })
+}
+
+pub fn handle_setnx(
+    mut args: Args,
     db: &mut DataStore,
 ) -> Result<BytesFrame, CommandExecutionError> {
-    // match args {
-    //     [key_frame, value_frame, rest @ ..] => {
-    //         if rest.len() % 2 != 0 {
-    //             return Err(CommandExecutionError::WrongArity("SET"));
-    //         }
-    //
-    //         let key = match key_frame {
-    //             BytesFrame::SimpleString { data, .. } => data.as_ref(),
-    //             _ => return Err(CommandExecutionError::InvalidParams("Invalid key type")),
-    //         };
-    //
-    //         let value = match value_frame {
-    //             BytesFrame::SimpleString { data, .. } => data.as_ref(),
-    //             _ => {
-    //                 return Err(CommandExecutionError::InvalidParams("Invalid value type"));
-    //             }
-    //         };
-    //
-    //         // Parse flags into pairs
-    //         let flags: Vec<(&[u8], &[u8])> = rest
-    //             .chunks_exact(2)
-    //             .filter_map(|chunk| match chunk {
-    //                 [
-    //                     BytesFrame::SimpleString { data: flag, .. },
-    //                     BytesFrame::SimpleString { data: val, .. },
-    //                 ] => Some((flag.as_ref(), val.as_ref())),
-    //                 _ => None,
-    //             })
-    //             .collect();
-    //
-    //         let mut ttl: Option<u64> = None;
-    //         let mut existence_check: Option<bool> = None;
-    //         let mut get_old = false;
-    //
-    //         for (flag, val) in flags {
-    //             match flag {
-    //                 b"EX" | b"PX" => {
-    //                     if ttl.is_some() {
-    //                         return Err(CommandExecutionError::InvalidParams(
-    //                             "Only one TTL option allowed",
-    //                         ));
-    //                     }
-    //
-    //                     let parsed: u64 = std::str::from_utf8(val)
-    //                         .map_err(|_| CommandExecutionError::InvalidParams("Invalid TTL value"))?
-    //                         .parse()
-    //                         .map_err(|_| {
-    //                             CommandExecutionError::InvalidParams("Invalid TTL value")
-    //                         })?;
-    //
-    //                     ttl = Some(if *flag == *b"PX" {
-    //                         parsed / 1000
-    //                     } else {
-    //                         parsed
-    //                     });
-    //                 }
-    //
-    //                 b"NX" => {
-    //                     if existence_check.is_some() {
-    //                         return Err(CommandExecutionError::InvalidParams(
-    //                             "NX and XX cannot coexist",
-    //                         ));
-    //                     }
-    //                     existence_check = Some(true);
-    //                 }
-    //
-    //                 b"XX" => {
-    //                     if existence_check.is_some() {
-    //                         return Err(CommandExecutionError::InvalidParams(
-    //                             "NX and XX cannot coexist",
-    //                         ));
-    //                     }
-    //                     existence_check = Some(false);
-    //                 }
-    //
-    //                 b"GET" => get_old = true,
-    //                 b"KEEPTTL" => { /* optional: preserve TTL */ }
-    //
-    //                 _ => {
-    //                     return Err(CommandExecutionError::InvalidParams("Unknown option"));
-    //                 }
-    //             }
-    //         }
-    //
-    //         // Fetch previous value if GET
-    //         let old_value = if get_old { db.get(key) } else { None };
-    //
-    //         // Respect NX/XX
-    //         if let Some(nx) = existence_check {
-    //             if nx && db.contains_key(key) {
-    //                 return Ok(old_value
-    //                     .map(|v| BytesFrame::SimpleString { data: v.into(), attributes: None })
-    //                     .unwrap_or(BytesFrame::Null));
-    //             }
-    //             if !nx && !db.contains_key(key) {
-    //                 return Ok(BytesFrame::Null);
-    //             }
-    //         }
-    //
-    //         // Insert/update value
-    //         db.insert(key.to_vec().into(), value.to_vec());
-    //
-    //         // Set TTL if any (assuming you have a TTL map)
-    //         if let Some(ttl_secs) = ttl {
-    //             ttl_map.insert(key.to_vec(), ttl_secs);
-    //         }
-    //
-    //         // Return GET old value or OK
-    //         if get_old {
-    //             Ok(old_value
-    //                 .map(|v| BytesFrame::SimpleString { data: v.into(), attributes: None })
-    //                 .unwrap_or(BytesFrame::Null))
-    //         } else {
-    //             Ok(BytesFrame::SimpleString { data: "OK".into(), attributes: None })
-    //         }
-    //     }
-    //     _ => return Err(CommandExecutionError::WrongArity("SET")),
-    // }
-    Ok(BytesFrame::SimpleString { data: "OK".into(), attributes: None })
+    let key = args.next_bytes()?.clone();
+    let value = args.next_bytes()?.clone();
+    args.finish()?;
+
+    let options = SetOptions { condition: SetCondition::Nx, ..Default::default() };
+    let outcome = set_value(db, key, value, options)?;
+    Ok(integer(outcome.applied as i64))
+}
+
+pub fn handle_getset(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?.clone();
+    let value = args.next_bytes()?.clone();
+    args.finish()?;
+
+    let options = SetOptions { get: true, ..Default::default() };
+    let outcome = set_value(db, key, value, options)?;
+    Ok(bulk_or_null(outcome.old))
+}
+
+pub fn handle_setex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    set_with_expiry(&mut args, db, 1000)
+}
+
+pub fn handle_psetex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    set_with_expiry(&mut args, db, 1)
+}
+
+/// Shared body of `SETEX` and `PSETEX`, whose TTL argument comes before the value.
+fn set_with_expiry(
+    args: &mut Args,
+    db: &mut DataStore,
+    unit_ms: i64,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?.clone();
+    let deadline = relative_deadline(args.next_i64()?, unit_ms, clock::now())?;
+    let value = args.next_bytes()?.clone();
+    args.finish()?;
+
+    let options = SetOptions {
+        expiry: SetExpiry::At(deadline),
+        ..Default::default()
+    };
+    set_value(db, key, value, options)?;
+    Ok(ok())
+}
+
+fn parse_set_options(args: &mut Args) -> Result<SetOptions, CommandExecutionError> {
+    let mut options = SetOptions::default();
+    let mut has_condition = false;
+    let mut has_expiry = false;
+
+    while !args.is_empty() {
+        let token = args.next_token()?;
+
+        if token.is("NX") || token.is("XX") || token.is("IFEQ") {
+            if has_condition {
+                return Err(token.syntax_error());
+            }
+            has_condition = true;
+            options.condition = if token.is("NX") {
+                SetCondition::Nx
+            } else if token.is("XX") {
+                SetCondition::Xx
+            } else {
+                let expected = args.next_bytes().map_err(|_| token.syntax_error())?;
+                SetCondition::IfEq(expected.clone())
+            };
+        } else if token.is("GET") {
+            options.get = true;
+        } else if token.is("KEEPTTL") {
+            if has_expiry {
+                return Err(token.syntax_error());
+            }
+            has_expiry = true;
+            options.expiry = SetExpiry::KeepTtl;
+        } else if let Some(idle_timeout) = parse_idle_timeout(token, args)? {
+            if options.idle_timeout.is_some() {
+                return Err(token.syntax_error());
+            }
+            options.idle_timeout = Some(idle_timeout);
+        } else if let Some(expiry) = parse_expiry(token, args, clock::now())? {
+            if has_expiry {
+                return Err(token.syntax_error());
+            }
+            has_expiry = true;
+            options.expiry = expiry;
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+    Ok(options)
+}
+
+/// Parses an `EX | PX | EXAT | PXAT | SLIDE | PSLIDE` option. Returns `None`, consuming
+/// nothing, if `token` is none of them.
+pub fn parse_expiry(
+    token: Token,
+    args: &mut Args,
+    now: UnixMillis,
+) -> Result<Option<SetExpiry>, CommandExecutionError> {
+    let unit_ms = if token.is("EX") || token.is("EXAT") || token.is("SLIDE") {
+        1000
+    } else if token.is("PX") || token.is("PXAT") || token.is("PSLIDE") {
+        1
+    } else {
+        return Ok(None);
+    };
+    if args.is_empty() {
+        return Err(token.syntax_error());
+    }
+    let amount = args.next_i64()?;
+    Ok(Some(if token.is("EX") || token.is("PX") {
+        SetExpiry::At(relative_deadline(amount, unit_ms, now)?)
+    } else if token.is("EXAT") || token.is("PXAT") {
+        SetExpiry::At(absolute_deadline(amount, unit_ms)?)
+    } else {
+        SetExpiry::Sliding(positive_ms(amount, unit_ms)?)
+    }))
+}
+
+/// Parses an `IDLE seconds | PIDLE milliseconds` option into milliseconds. Returns `None`,
+/// consuming nothing, if `token` is neither.
+pub fn parse_idle_timeout(
+    token: Token,
+    args: &mut Args,
+) -> Result<Option<u64>, CommandExecutionError> {
+    let unit_ms = if token.is("IDLE") {
+        1000
+    } else if token.is("PIDLE") {
+        1
+    } else {
+        return Ok(None);
+    };
+    if args.is_empty() {
+        return Err(token.syntax_error());
+    }
+    positive_ms(args.next_i64()?, unit_ms).map(Some)
+}
+
+/// Converts a relative `EX`/`PX` amount into a deadline counted from `now`, rejecting
+/// non-positive values and overflow.
+pub fn relative_deadline(
+    amount: i64,
+    unit_ms: i64,
+    now: UnixMillis,
+) -> Result<UnixMillis, CommandExecutionError> {
+    positive_ms(amount, unit_ms)?
+        .checked_add(now)
+        .ok_or(CommandExecutionError::InvalidExpire)
+}
+
+/// Converts an absolute `EXAT`/`PXAT` Unix timestamp into a deadline, rejecting non-positive
+/// values and overflow. The deadline may already be in the past.
+pub fn absolute_deadline(amount: i64, unit_ms: i64) -> Result<UnixMillis, CommandExecutionError> {
+    positive_ms(amount, unit_ms)
+}
+
+/// Converts `amount` units of `unit_ms` into milliseconds, rejecting non-positive values and
+/// overflow.
+pub fn positive_ms(amount: i64, unit_ms: i64) -> Result<u64, CommandExecutionError> {
+    if amount <= 0 {
+        return Err(CommandExecutionError::InvalidExpire);
+    }
+    let ms = amount.checked_mul(unit_ms).ok_or(CommandExecutionError::InvalidExpire)?;
+    Ok(ms as u64)
+}
+
+/// Writes `value` under `key` honouring the condition, expiry and `GET` flag of `options`.
+///
+/// A `GET` (or `IFEQ`) against a key holding a non-string value fails with `WRONGTYPE` and
+/// leaves the key untouched.
+pub fn set_value(
+    db: &mut DataStore,
+    key: Bytes,
+    value: Bytes,
+    options: SetOptions,
+) -> Result<SetOutcome, CommandExecutionError> {
+    let now = clock::now();
+    let existing = db.get(&key).filter(|obj| !obj.is_expired(now));
+
+    let old = match existing {
+        Some(obj) if options.get || matches!(options.condition, SetCondition::IfEq(_)) => {
+            Some(obj.data.string_bytes().ok_or(CommandExecutionError::WrongType)?)
+        }
+        _ => None,
+    };
+
+    let applied = match &options.condition {
+        SetCondition::Always => true,
+        SetCondition::Nx => existing.is_none(),
+        SetCondition::Xx => existing.is_some(),
+        SetCondition::IfEq(expected) => old.as_ref() == Some(expected),
+    };
+    if !applied {
+        return Ok(SetOutcome { applied, old });
+    }
+
+    let mut obj = StoreObject::new(DataKind::from_string(value), now);
+    match options.expiry {
+        SetExpiry::Persist => {}
+        SetExpiry::KeepTtl => {
+            if let Some(existing) = existing {
+                obj.expires_at = existing.expires_at;
+                obj.sliding_ttl = existing.sliding_ttl;
+                obj.idle_timeout = existing.idle_timeout;
+            }
+        }
+        SetExpiry::At(deadline) if deadline <= now => {
+            // An absolute deadline in the past: the write succeeds but the key is gone at once.
+            db.delete(&key, CONFIG.lazyfree_lazy_server_del());
+            return Ok(SetOutcome { applied, old });
+        }
+        SetExpiry::At(deadline) => obj.expires_at = Some(deadline),
+        SetExpiry::Sliding(ttl) => obj.set_sliding_ttl(ttl, now),
+    }
+    if options.idle_timeout.is_some() {
+        obj.idle_timeout = options.idle_timeout;
+    }
+    db.insert(key, obj);
+    Ok(SetOutcome { applied, old })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::{run, run_err},
+            reply::integer,
+        },
+        shard::{clock::manual_clock, types::DataStore},
+    };
+
+    #[test]
+    fn expiry_options_without_a_value_are_syntax_errors() {
+        manual_clock();
+        let mut db = DataStore::default();
+        for option in [
+            "EX", "PX", "EXAT", "PXAT", "SLIDE", "PSLIDE", "IDLE", "PIDLE",
+        ] {
+            let err = run_err(&mut db, CommandKind::Set, &["k", "v", option]);
+            assert_eq!(err, format!("ERR syntax error near '{option}'"));
+        }
+        let err = run_err(&mut db, CommandKind::Getex, &["k", "EX"]);
+        assert_eq!(err, "ERR syntax error near 'EX'");
+        assert_eq!(run(&mut db, CommandKind::Exists, &["k"]), integer(0));
+    }
 }
@@ -0,0 +1,554 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        random::sample,
+        reply::{array, bulk, bulk_or_null, integer, set_of},
+    },
+    config::CONFIG,
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        shard::Shard,
+        types::{DataKind, DataStore, SetValue, StoreObject, lookup_mut},
+    },
+};
+
+/// Algebra of `SINTER`, `SUNION` and `SDIFF` and their `STORE` variants.
+#[derive(Clone, Copy, Debug)]
+enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+pub fn handle_sadd(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let members = args.rest_bytes()?;
+
+    let set = set_or_create(db, key, clock::now())?;
+    let added = members.into_iter().filter(|member| set.insert((*member).clone())).count();
+    Ok(integer(added as i64))
+}
+
+pub fn handle_srem(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let members = args.rest_bytes()?;
+
+    let Some(set) = set_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = members.into_iter().filter(|member| set.remove(member)).count();
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_smembers(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let set = set_mut(db, key, clock::now())?;
+    Ok(set_of(set.into_iter().flat_map(|set| set.iter())))
+}
+
+pub fn handle_sismember(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let member = args.next_bytes()?;
+    args.finish()?;
+
+    let set = set_mut(db, key, clock::now())?;
+    Ok(integer(set.is_some_and(|set| set.contains(member)) as i64))
+}
+
+pub fn handle_smismember(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let members = args.rest_bytes()?;
+
+    let set = set_mut(db, key, clock::now())?;
+    Ok(array(
+        members
+            .into_iter()
+            .map(|member| integer(set.as_ref().is_some_and(|set| set.contains(member)) as i64))
+            .collect(),
+    ))
+}
+
+pub fn handle_scard(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let set = set_mut(db, key, clock::now())?;
+    Ok(integer(set.map_or(0, |set| set.len()) as i64))
+}
+
+/// `SPOP key [count]`. Without a count the reply is a single member; with one it is a set of
+/// up to `count` distinct members.
+pub fn handle_spop(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        let count = args.next_i64()?;
+        if count < 0 {
+            return Err(CommandExecutionError::NotPositive);
+        }
+        Some(count)
+    };
+    args.finish()?;
+
+    let Some(set) = set_mut(db, key, clock::now())? else {
+        return Ok(match count {
+            Some(_) => set_of(Vec::new()),
+            None => BytesFrame::Null,
+        });
+    };
+    let picked: Vec<Bytes> = match count {
+        Some(count) => sample_members(set, count)?,
+        None => set.random().into_iter().collect(),
+    };
+    for member in &picked {
+        set.remove(member);
+    }
+    remove_if_empty(db, key);
+
+    Ok(match count {
+        Some(_) => set_of(picked),
+        None => bulk_or_null(picked.into_iter().next()),
+    })
+}
+
+/// `SRANDMEMBER key [count]`. A positive count returns distinct members, a negative one may
+/// repeat members; see [`sample`].
+pub fn handle_srandmember(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        Some(args.next_i64()?)
+    };
+    args.finish()?;
+
+    let set = set_mut(db, key, clock::now())?;
+    let Some(count) = count else {
+        return Ok(bulk_or_null(set.and_then(|set| set.random())));
+    };
+    let Some(set) = set else {
+        return Ok(array(Vec::new()));
+    };
+    Ok(array(sample_members(set, count)?.into_iter().map(bulk).collect()))
+}
+
+/// Members of `set` picked for `count`; see [`sample`].
+fn sample_members(set: &SetValue, count: i64) -> Result<Vec<Bytes>, CommandExecutionError> {
+    let all = set.iter().map(|member| (member, ()));
+    let picked = sample(count, set.len(), all, || set.random().map(|member| (member, ())))?;
+    Ok(picked.into_iter().map(|(member, ())| member).collect())
+}
+
+/// `SMOVE source destination member`. Both keys are type-checked before anything moves.
+pub fn handle_smove(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination) = (args.next_bytes()?, args.next_bytes()?);
+    let member = args.next_bytes()?;
+    args.finish()?;
+
+    Ok(integer(move_member(db, source, destination, member)? as i64))
+}
+
+pub fn handle_sinter(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, SetOp::Inter)
+}
+
+pub fn handle_sunion(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, SetOp::Union)
+}
+
+pub fn handle_sdiff(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, SetOp::Diff)
+}
+
+pub fn handle_sinterstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, SetOp::Inter)
+}
+
+pub fn handle_sunionstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, SetOp::Union)
+}
+
+pub fn handle_sdiffstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, SetOp::Diff)
+}
+
+/// `SINTERCARD numkeys key [key ...] [LIMIT limit]`.
+pub fn handle_sintercard(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, limit) = parse_sintercard(&mut args)?;
+    let sets = load_sets(db, &keys)?;
+    Ok(integer(intersection(&sets).take(limit).count() as i64))
+}
+
+/// `SMOVE` across shards.
+///
+/// The destination type is checked first and the member is then removed from the source; if
+/// the destination changes type before the insert lands, the member is put back.
+pub async fn smove_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (source, destination) = (args.next_bytes()?.clone(), args.next_bytes()?.clone());
+    let member = args.next_bytes()?.clone();
+    args.finish()?;
+    let (source_owner, destination_owner) = (shard.owner(&source), shard.owner(&destination));
+
+    let key = destination.clone();
+    shard
+        .run_on(destination_owner, move |db| set_mut(db, &key, clock::now()).map(|_| ()))
+        .await?;
+
+    let (key, removed) = (source.clone(), member.clone());
+    let moved = shard
+        .run_on(source_owner, move |db| {
+            let Some(set) = set_mut(db, &key, clock::now())? else {
+                return Ok(false);
+            };
+            let moved = set.remove(&removed);
+            remove_if_empty(db, &key);
+            Ok(moved)
+        })
+        .await?;
+    if !moved {
+        return Ok(integer(0));
+    }
+
+    let inserted = member.clone();
+    let result = shard
+        .run_on(destination_owner, move |db| {
+            set_or_create(db, &destination, clock::now())?.insert(inserted);
+            Ok(())
+        })
+        .await;
+    if let Err(err) = result {
+        shard
+            .run_on(source_owner, move |db| {
+                if let Ok(set) = set_or_create(db, &source, clock::now()) {
+                    set.insert(member);
+                }
+            })
+            .await;
+        return Err(err);
+    }
+    Ok(integer(1))
+}
+
+pub async fn sinter_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, SetOp::Inter).await
+}
+
+pub async fn sunion_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, SetOp::Union).await
+}
+
+pub async fn sdiff_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, SetOp::Diff).await
+}
+
+pub async fn sinterstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, SetOp::Inter).await
+}
+
+pub async fn sunionstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, SetOp::Union).await
+}
+
+pub async fn sdiffstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, SetOp::Diff).await
+}
+
+/// `SINTERCARD` across shards: the operand sets are copied from their owners and intersected
+/// here.
+pub async fn sintercard_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, limit) = parse_sintercard(&mut args)?;
+    let sets = fetch_sets(shard, keys.into_iter().cloned().collect()).await?;
+    let sets: Vec<Option<&SetValue>> = sets.iter().map(Option::as_ref).collect();
+    Ok(integer(intersection(&sets).take(limit).count() as i64))
+}
+
+/// Shared body of `SINTER`, `SUNION` and `SDIFF` when all keys are local.
+fn combine_keys(
+    mut args: Args,
+    db: &mut DataStore,
+    op: SetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys = args.rest_bytes()?;
+    let sets = load_sets(db, &keys)?;
+    Ok(set_of(combine(op, &sets)))
+}
+
+/// Shared body of the `STORE` variants when all keys are local.
+fn combine_keys_into(
+    mut args: Args,
+    db: &mut DataStore,
+    op: SetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let destination = args.next_bytes()?;
+    let keys = args.rest_bytes()?;
+    let result = combine(op, &load_sets(db, &keys)?);
+    Ok(integer(store_set(db, destination, result) as i64))
+}
+
+/// Copies the operand sets from their owning shards and combines them on this one.
+async fn combine_keys_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+    op: SetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let keys = args.rest_bytes()?.into_iter().cloned().collect();
+    let sets = fetch_sets(shard, keys).await?;
+    let sets: Vec<Option<&SetValue>> = sets.iter().map(Option::as_ref).collect();
+    Ok(set_of(combine(op, &sets)))
+}
+
+/// Combines the operand sets on this shard and writes the result on the destination's owner.
+async fn combine_keys_into_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+    op: SetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let destination = args.next_bytes()?.clone();
+    let keys = args.rest_bytes()?.into_iter().cloned().collect();
+    let sets = fetch_sets(shard, keys).await?;
+    let sets: Vec<Option<&SetValue>> = sets.iter().map(Option::as_ref).collect();
+    let result = combine(op, &sets);
+
+    let stored = shard
+        .run_on(shard.owner(&destination), move |db| store_set(db, &destination, result))
+        .await;
+    Ok(integer(stored as i64))
+}
+
+/// Parses `numkeys key [key ...] [LIMIT limit]`; a limit of 0 means no limit.
+fn parse_sintercard<'a>(
+    args: &mut Args<'a>,
+) -> Result<(Vec<&'a Bytes>, usize), CommandExecutionError> {
+    let numkeys = args.next_i64()?;
+    if numkeys <= 0 {
+        return Err(CommandExecutionError::NumKeysNotPositive);
+    }
+    if numkeys as u64 > args.remaining() as u64 {
+        return Err(CommandExecutionError::NumKeysTooMany);
+    }
+    let keys = (0..numkeys).map(|_| args.next_bytes()).collect::<Result<Vec<_>, _>>()?;
+
+    let mut limit = usize::MAX;
+    if !args.is_empty() {
+        let token = args.next_token()?;
+        if !token.is("LIMIT") {
+            return Err(token.syntax_error());
+        }
+        let n = args.next_i64()?;
+        if n < 0 {
+            return Err(CommandExecutionError::LimitNegative);
+        }
+        if n > 0 {
+            limit = n as usize;
+        }
+    }
+    args.finish()?;
+    Ok((keys, limit))
+}
+
+/// Moves `member` between two local sets; the source key may equal the destination.
+fn move_member(
+    db: &mut DataStore,
+    source: &Bytes,
+    destination: &Bytes,
+    member: &Bytes,
+) -> Result<bool, CommandExecutionError> {
+    let now = clock::now();
+    set_mut(db, destination, now)?;
+    let Some(set) = set_mut(db, source, now)? else {
+        return Ok(false);
+    };
+    if source == destination {
+        return Ok(set.contains(member));
+    }
+    if !set.remove(member) {
+        return Ok(false);
+    }
+    remove_if_empty(db, source);
+
+    set_or_create(db, destination, now)?.insert(member.clone());
+    Ok(true)
+}
+
+/// Type-checks and expires every key, then borrows the sets they hold; missing keys are `None`.
+fn load_sets<'a>(
+    db: &'a mut DataStore,
+    keys: &[&Bytes],
+) -> Result<Vec<Option<&'a SetValue>>, CommandExecutionError> {
+    let now = clock::now();
+    for key in keys {
+        set_mut(db, key, now)?;
+    }
+    let db: &'a DataStore = db;
+    Ok(keys
+        .iter()
+        .map(|key| match db.get(key) {
+            Some(StoreObject { data: DataKind::Set(set), .. }) => Some(set),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Copies the sets stored at `keys` from their owning shards, in the order of `keys`.
+async fn fetch_sets(
+    shard: &Shard,
+    keys: Vec<Bytes>,
+) -> Result<Vec<Option<SetValue>>, CommandExecutionError> {
+    shard
+        .scatter_gather(
+            keys,
+            |key| key,
+            |db, keys| {
+                let now = clock::now();
+                keys.iter().map(|key| Ok(set_mut(db, key, now)?.cloned())).collect()
+            },
+        )
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Applies `op` to the operand sets in order. A missing key counts as an empty set.
+fn combine(op: SetOp, sets: &[Option<&SetValue>]) -> SetValue {
+    match op {
+        SetOp::Inter => intersection(sets).collect(),
+        SetOp::Union => sets.iter().flatten().flat_map(|set| set.iter()).collect(),
+        SetOp::Diff => {
+            let Some((Some(first), rest)) = sets.split_first() else {
+                return SetValue::default();
+            };
+            first
+                .iter()
+                .filter(|member| !rest.iter().flatten().any(|set| set.contains(member)))
+                .collect()
+        }
+    }
+}
+
+/// Members of every operand set, found by probing the others with each member of the smallest.
+fn intersection<'a>(sets: &[Option<&'a SetValue>]) -> impl Iterator<Item = Bytes> + 'a {
+    // One missing key empties the whole intersection.
+    let mut sets: Vec<&'a SetValue> =
+        sets.iter().copied().collect::<Option<_>>().unwrap_or_default();
+    sets.sort_by_key(|set| set.len());
+    let smallest = sets.first().copied();
+    smallest
+        .into_iter()
+        .flat_map(|set| set.iter())
+        .filter(move |member| sets[1..].iter().all(|set| set.contains(member)))
+}
+
+/// Replaces `destination` with `members`, or deletes it when the result is empty. Returns the
+/// stored cardinality.
+fn store_set(db: &mut DataStore, destination: &Bytes, members: SetValue) -> usize {
+    let len = members.len();
+    if len == 0 {
+        db.delete(destination, CONFIG.lazyfree_lazy_server_del());
+    } else {
+        db.insert(destination.clone(), StoreObject::new(DataKind::Set(members), clock::now()));
+    }
+    len
+}
+
+/// Set stored at `key`, `None` if the key is missing, or `WRONGTYPE` for other types.
+pub fn set_mut<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<Option<&'a mut SetValue>, CommandExecutionError> {
+    match lookup_mut(db, key, now) {
+        Some(StoreObject { data: DataKind::Set(set), .. }) => Ok(Some(set)),
+        Some(_) => Err(CommandExecutionError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// Set stored at `key`, creating an empty one if the key is missing.
+pub fn set_or_create<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<&'a mut SetValue, CommandExecutionError> {
+    if set_mut(db, key, now)?.is_none() {
+        db.insert(key.clone(), StoreObject::new(DataKind::Set(SetValue::default()), now));
+    }
+    Ok(set_mut(db, key, now)?.expect("set was just looked up or created"))
+}
+
+/// Drops `key` once its set has no members left.
+pub fn remove_if_empty(db: &mut DataStore, key: &Bytes) {
+    if matches!(db.get(key), Some(StoreObject { data: DataKind::Set(set), .. }) if set.is_empty()) {
+        db.remove(key);
+    }
+}
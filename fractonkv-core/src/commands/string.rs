@@ -0,0 +1,207 @@
+use bytes::{Bytes, BytesMut};
+use redis_protocol::resp3::types::BytesFrame;
+
+use crate::{
+    commands::{
+        args::Args,
+        reply::{bulk, integer},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        types::{DataKind, DataStore, StoreObject, lookup_mut},
+    },
+};
+
+/// Largest string a write may produce, matching Redis' default `proto-max-bulk-len`.
+pub const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+pub fn handle_append(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let suffix = args.next_bytes()?;
+    args.finish()?;
+
+    let now = clock::now();
+    let Some(obj) = lookup_mut(db, key, now) else {
+        let len = suffix.len();
+        db.insert(key.clone(), StoreObject::new(DataKind::from_string(suffix.clone()), now));
+        return Ok(integer(len as i64));
+    };
+
+    let current = obj.data.string_bytes().ok_or(CommandExecutionError::WrongType)?;
+    let len = current.len() + suffix.len();
+    if len > MAX_STRING_LEN {
+        return Err(CommandExecutionError::StringTooLong);
+    }
+
+    let mut buf = BytesMut::with_capacity(len);
+    buf.extend_from_slice(&current);
+    buf.extend_from_slice(suffix);
+    obj.data = DataKind::BulkString(buf.freeze());
+    Ok(integer(len as i64))
+}
+
+pub fn handle_strlen(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let len = match lookup_mut(db, key, clock::now()) {
+        Some(obj) => match &obj.data {
+            DataKind::BulkString(bytes) => bytes.len(),
+            DataKind::Int(value) => value.to_string().len(),
+            _ => return Err(CommandExecutionError::WrongType),
+        },
+        None => 0,
+    };
+    Ok(integer(len as i64))
+}
+
+pub fn handle_getrange(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let start = args.next_i64()?;
+    let end = args.next_i64()?;
+    args.finish()?;
+
+    let now = clock::now();
+    let value = match lookup_mut(db, key, now) {
+        Some(obj) => obj.data.string_bytes().ok_or(CommandExecutionError::WrongType)?,
+        None => Bytes::new(),
+    };
+
+    Ok(bulk(match clamp_range(start, end, value.len()) {
+        Some((from, to)) => value.slice(from..=to),
+        None => Bytes::new(),
+    }))
+}
+
+pub fn handle_setrange(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let offset = args.next_i64()?;
+    let patch = args.next_bytes()?;
+    args.finish()?;
+
+    if offset < 0 {
+        return Err(CommandExecutionError::OffsetOutOfRange);
+    }
+    let offset = offset as usize;
+
+    let now = clock::now();
+    let current = match lookup_mut(db, key, now) {
+        Some(obj) => Some(obj.data.string_bytes().ok_or(CommandExecutionError::WrongType)?),
+        None => None,
+    };
+
+    // An empty patch never creates or grows the key.
+    if patch.is_empty() {
+        return Ok(integer(current.map_or(0, |bytes| bytes.len()) as i64));
+    }
+    if offset + patch.len() > MAX_STRING_LEN {
+        return Err(CommandExecutionError::StringTooLong);
+    }
+
+    let current = current.unwrap_or_default();
+    let len = current.len().max(offset + patch.len());
+    let mut buf = BytesMut::zeroed(len);
+    buf[..current.len()].copy_from_slice(&current);
+    buf[offset..offset + patch.len()].copy_from_slice(patch);
+
+    match db.get_mut(key) {
+        Some(obj) => obj.data = DataKind::BulkString(buf.freeze()),
+        None => {
+            db.insert(key.clone(), StoreObject::new(DataKind::BulkString(buf.freeze()), now));
+        }
+    }
+    Ok(integer(len as i64))
+}
+
+/// Resolves inclusive Redis-style `start`/`end` offsets (negative counts from the end) against
+/// a sequence of `len` elements. Returns `None` when the range is empty.
+pub fn clamp_range(start: i64, end: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    if len == 0 || (start < 0 && end < 0 && start > end) {
+        return None;
+    }
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 {
+        (len + end).max(0)
+    } else {
+        end.min(len - 1)
+    };
+    if start > end {
+        return None;
+    }
+    Some((start as usize, end as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::{run, run_err},
+            reply::{bulk, integer},
+        },
+        shard::types::DataStore,
+    };
+
+    #[test]
+    fn getrange_resolves_negative_offsets_from_the_end() {
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Set, &["k", "This is a string"]);
+        let getrange = |db: &mut DataStore, start: &str, end: &str| {
+            run(db, CommandKind::Getrange, &["k", start, end])
+        };
+
+        assert_eq!(getrange(&mut db, "0", "3"), bulk("This".into()));
+        assert_eq!(getrange(&mut db, "-3", "-1"), bulk("ing".into()));
+        assert_eq!(getrange(&mut db, "0", "-1"), bulk("This is a string".into()));
+        assert_eq!(getrange(&mut db, "10", "100"), bulk("string".into()));
+        assert_eq!(getrange(&mut db, "-100", "3"), bulk("This".into()));
+        assert_eq!(getrange(&mut db, "5", "3"), bulk("".into()));
+        assert_eq!(getrange(&mut db, "-1", "-5"), bulk("".into()));
+        assert_eq!(getrange(&mut db, "100", "200"), bulk("".into()));
+        assert_eq!(run(&mut db, CommandKind::Getrange, &["missing", "0", "-1"]), bulk("".into()));
+    }
+
+    #[test]
+    fn setrange_pads_with_zeros_and_enforces_the_size_cap() {
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Set, &["k", "Hello World"]);
+        assert_eq!(run(&mut db, CommandKind::Setrange, &["k", "6", "Redis"]), integer(11));
+        assert_eq!(run(&mut db, CommandKind::Get, &["k"]), bulk("Hello Redis".into()));
+
+        assert_eq!(run(&mut db, CommandKind::Setrange, &["new", "3", "ab"]), integer(5));
+        assert_eq!(run(&mut db, CommandKind::Get, &["new"]), bulk("\0\0\0ab".into()));
+
+        // An empty patch neither creates nor grows the key.
+        assert_eq!(run(&mut db, CommandKind::Setrange, &["empty", "10", ""]), integer(0));
+        assert_eq!(run(&mut db, CommandKind::Exists, &["empty"]), integer(0));
+        assert_eq!(run(&mut db, CommandKind::Setrange, &["k", "100", ""]), integer(11));
+
+        assert_eq!(
+            run_err(&mut db, CommandKind::Setrange, &["k", "-1", "x"]),
+            "ERR offset is out of range"
+        );
+        assert_eq!(
+            run_err(&mut db, CommandKind::Setrange, &["k", "536870911", "xy"]),
+            "ERR string exceeds maximum allowed size (proto-max-bulk-len)"
+        );
+        assert_eq!(run(&mut db, CommandKind::Setrange, &["k", "536870911", ""]), integer(11));
+    }
+}
@@ -0,0 +1,841 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+use std::ops::Range;
+
+use crate::{
+    commands::{
+        args::{Args, parse_f64, parse_i64},
+        random::sample,
+        reply::{array, bulk, double, integer},
+        string::clamp_range,
+    },
+    config::CONFIG,
+    errors::CommandExecutionError,
+    shard::{
+        clock::{self, UnixMillis},
+        shard::Shard,
+        types::{DataKind, DataStore, StoreObject, lookup_mut},
+        zset::SortedSet,
+    },
+};
+
+/// Options of `ZADD`.
+#[derive(Debug, Default)]
+struct ZaddFlags {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+    ch: bool,
+    incr: bool,
+}
+
+/// A score interval as taken by `ZCOUNT` and friends, e.g. `(1 +inf`.
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreRange {
+    min: f64,
+    min_exclusive: bool,
+    max: f64,
+    max_exclusive: bool,
+}
+
+impl ScoreRange {
+    pub fn parse(min: &[u8], max: &[u8]) -> Result<Self, CommandExecutionError> {
+        let (min, min_exclusive) = parse_score_bound(min)?;
+        let (max, max_exclusive) = parse_score_bound(max)?;
+        Ok(Self { min, min_exclusive, max, max_exclusive })
+    }
+
+    fn below_min(&self, score: f64) -> bool {
+        score < self.min || (self.min_exclusive && score == self.min)
+    }
+
+    fn within_max(&self, score: f64) -> bool {
+        score < self.max || (!self.max_exclusive && score == self.max)
+    }
+
+    /// Ranks of the members of `zset` whose score lies in the interval.
+    pub fn ranks(&self, zset: &SortedSet) -> Range<usize> {
+        let start = zset.count_while(|score, _| self.below_min(score));
+        let end = zset.count_while(|score, _| self.within_max(score));
+        start..end.max(start)
+    }
+}
+
+/// A lexicographic interval as taken by `ZRANGEBYLEX` and friends, e.g. `[a (c` or `- +`.
+/// Only meaningful when every member has the same score.
+#[derive(Clone, Debug)]
+pub struct LexRange {
+    min: LexBound,
+    max: LexBound,
+}
+
+#[derive(Clone, Debug)]
+enum LexBound {
+    /// `-`, before every member.
+    Lowest,
+    /// `+`, after every member.
+    Highest,
+    /// `[member`.
+    Inclusive(Bytes),
+    /// `(member`.
+    Exclusive(Bytes),
+}
+
+impl LexRange {
+    pub fn parse(min: &Bytes, max: &Bytes) -> Result<Self, CommandExecutionError> {
+        Ok(Self {
+            min: parse_lex_bound(min)?,
+            max: parse_lex_bound(max)?,
+        })
+    }
+
+    fn below_min(&self, member: &Bytes) -> bool {
+        match &self.min {
+            LexBound::Lowest => false,
+            LexBound::Highest => true,
+            LexBound::Inclusive(min) => member < min,
+            LexBound::Exclusive(min) => member <= min,
+        }
+    }
+
+    fn within_max(&self, member: &Bytes) -> bool {
+        match &self.max {
+            LexBound::Lowest => false,
+            LexBound::Highest => true,
+            LexBound::Inclusive(max) => member <= max,
+            LexBound::Exclusive(max) => member < max,
+        }
+    }
+
+    /// Ranks of the members of `zset` that lie in the interval.
+    pub fn ranks(&self, zset: &SortedSet) -> Range<usize> {
+        let start = zset.count_while(|_, member| self.below_min(member));
+        let end = zset.count_while(|_, member| self.within_max(member));
+        start..end.max(start)
+    }
+}
+
+/// What a range command selects.
+#[derive(Clone, Debug)]
+enum RangeBy {
+    /// Inclusive `start`/`stop` ranks; negative ones count from the end.
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// A parsed `ZRANGE`, or one of the legacy range commands expressed as one.
+#[derive(Clone, Debug)]
+struct RangeQuery {
+    by: RangeBy,
+    /// Highest score first. Rank offsets then count from the highest score too.
+    rev: bool,
+    /// `LIMIT offset count`; a negative count means no limit.
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+impl RangeQuery {
+    fn new(by: RangeBy, rev: bool) -> Self {
+        Self { by, rev, limit: None, with_scores: false }
+    }
+
+    /// The selected members of `zset` with their scores, in reply order.
+    fn run(&self, zset: &SortedSet) -> Vec<(Bytes, f64)> {
+        let len = zset.len();
+        let mut window = match &self.by {
+            RangeBy::Rank(start, stop) => match clamp_range(*start, *stop, len) {
+                Some((from, to)) if self.rev => len - 1 - to..len - from,
+                Some((from, to)) => from..to + 1,
+                None => 0..0,
+            },
+            RangeBy::Score(range) => range.ranks(zset),
+            RangeBy::Lex(range) => range.ranks(zset),
+        };
+
+        // LIMIT is applied in reply order, so with REV it trims from the top of the window.
+        if let Some((offset, count)) = self.limit {
+            if offset < 0 {
+                return Vec::new();
+            }
+            let offset = (offset as usize).min(window.len());
+            let count = usize::try_from(count).unwrap_or(usize::MAX);
+            if self.rev {
+                window.end -= offset;
+                window.start = window.start.max(window.end.saturating_sub(count));
+            } else {
+                window.start += offset;
+                window.end = window.end.min(window.start.saturating_add(count));
+            }
+        }
+
+        if window.is_empty() {
+            Vec::new()
+        } else if self.rev {
+            zset.range_rev(window.start, window.end - 1).collect()
+        } else {
+            zset.range(window.start, window.end - 1).collect()
+        }
+    }
+}
+
+/// `ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]`.
+pub fn handle_zadd(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let mut flags = ZaddFlags::default();
+    loop {
+        if args.eat_token("NX") {
+            flags.nx = true;
+        } else if args.eat_token("XX") {
+            flags.xx = true;
+        } else if args.eat_token("GT") {
+            flags.gt = true;
+        } else if args.eat_token("LT") {
+            flags.lt = true;
+        } else if args.eat_token("CH") {
+            flags.ch = true;
+        } else if args.eat_token("INCR") {
+            flags.incr = true;
+        } else {
+            break;
+        }
+    }
+    if flags.nx && flags.xx {
+        return Err(CommandExecutionError::ZaddNxXx);
+    }
+    if [flags.nx, flags.gt, flags.lt].into_iter().filter(|&flag| flag).count() > 1 {
+        return Err(CommandExecutionError::ZaddGtLtNx);
+    }
+    if args.is_empty() {
+        return Err(args.wrong_arity());
+    }
+    if !args.remaining().is_multiple_of(2) {
+        return Err(args.next_token()?.syntax_error());
+    }
+    if flags.incr && args.remaining() > 2 {
+        return Err(CommandExecutionError::ZaddIncrPairs);
+    }
+
+    let mut pairs = Vec::with_capacity(args.remaining() / 2);
+    while !args.is_empty() {
+        let score = parse_f64(args.next_bytes()?).ok_or(CommandExecutionError::NotFloat)?;
+        pairs.push((score, args.next_bytes()?));
+    }
+
+    let now = clock::now();
+    if zset_mut(db, key, now)?.is_none() && flags.xx {
+        return Ok(if flags.incr {
+            BytesFrame::Null
+        } else {
+            integer(0)
+        });
+    }
+    let zset = zset_or_create(db, key, now)?;
+
+    let (mut added, mut updated) = (0, 0);
+    let mut incremented = None;
+    for (score, member) in pairs {
+        incremented = None;
+        let Some(current) = zset.score(member) else {
+            if !flags.xx {
+                zset.insert(member.clone(), score);
+                added += 1;
+                incremented = Some(score);
+            }
+            continue;
+        };
+        if flags.nx {
+            continue;
+        }
+        let new = if flags.incr { current + score } else { score };
+        if new.is_nan() {
+            return Err(CommandExecutionError::ScoreNan);
+        }
+        if (flags.gt && new <= current) || (flags.lt && new >= current) {
+            continue;
+        }
+        if new != current {
+            zset.insert(member.clone(), new);
+            updated += 1;
+        }
+        incremented = Some(new);
+    }
+    remove_if_empty(db, key);
+
+    Ok(if flags.incr {
+        incremented.map_or(BytesFrame::Null, double)
+    } else if flags.ch {
+        integer(added + updated)
+    } else {
+        integer(added)
+    })
+}
+
+pub fn handle_zrem(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let members = args.rest_bytes()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = members.into_iter().filter(|member| zset.remove(member)).count();
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_zscore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let member = args.next_bytes()?;
+    args.finish()?;
+
+    let score = zset_mut(db, key, clock::now())?.and_then(|zset| zset.score(member));
+    Ok(score.map_or(BytesFrame::Null, double))
+}
+
+pub fn handle_zincrby(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let increment = args.next_f64()?;
+    let member = args.next_bytes()?;
+    args.finish()?;
+
+    let zset = zset_or_create(db, key, clock::now())?;
+    let score = zset.score(member).unwrap_or(0.0) + increment;
+    if score.is_nan() {
+        remove_if_empty(db, key);
+        return Err(CommandExecutionError::ScoreNan);
+    }
+    zset.insert(member.clone(), score);
+    Ok(double(score))
+}
+
+pub fn handle_zcard(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    args.finish()?;
+
+    let zset = zset_mut(db, key, clock::now())?;
+    Ok(integer(zset.map_or(0, |zset| zset.len()) as i64))
+}
+
+pub fn handle_zcount(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = ScoreRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    args.finish()?;
+
+    let zset = zset_mut(db, key, clock::now())?;
+    Ok(integer(zset.map_or(0, |zset| range.ranks(zset).len()) as i64))
+}
+
+pub fn handle_zrank(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    rank(&mut args, db, false)
+}
+
+pub fn handle_zrevrank(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    rank(&mut args, db, true)
+}
+
+/// `ZREMRANGEBYRANK key start stop`, with Redis-style negative offsets.
+pub fn handle_zremrangebyrank(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let start = args.next_i64()?;
+    let stop = args.next_i64()?;
+    args.finish()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = match clamp_range(start, stop, zset.len()) {
+        Some((from, to)) => zset.remove_range(from, to),
+        None => 0,
+    };
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_zremrangebyscore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = ScoreRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    args.finish()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = remove_ranks(zset, range.ranks(zset));
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+/// `ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`.
+pub fn handle_zrange(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let query = parse_zrange(&mut args, true)?;
+    range_reply(db, key, &query)
+}
+
+pub fn handle_zrangebyscore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = ScoreRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    let mut query = RangeQuery::new(RangeBy::Score(range), false);
+    parse_range_options(&mut args, &mut query, true, true)?;
+    range_reply(db, key, &query)
+}
+
+pub fn handle_zrevrangebyscore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let (max, min) = (args.next_bytes()?, args.next_bytes()?);
+    let mut query = RangeQuery::new(RangeBy::Score(ScoreRange::parse(min, max)?), true);
+    parse_range_options(&mut args, &mut query, true, true)?;
+    range_reply(db, key, &query)
+}
+
+pub fn handle_zrevrange(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let (start, stop) = (args.next_i64()?, args.next_i64()?);
+    let mut query = RangeQuery::new(RangeBy::Rank(start, stop), true);
+    parse_range_options(&mut args, &mut query, true, false)?;
+    range_reply(db, key, &query)
+}
+
+pub fn handle_zrangebylex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = LexRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    let mut query = RangeQuery::new(RangeBy::Lex(range), false);
+    parse_range_options(&mut args, &mut query, false, true)?;
+    range_reply(db, key, &query)
+}
+
+pub fn handle_zrevrangebylex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let (max, min) = (args.next_bytes()?, args.next_bytes()?);
+    let mut query = RangeQuery::new(RangeBy::Lex(LexRange::parse(min, max)?), true);
+    parse_range_options(&mut args, &mut query, false, true)?;
+    range_reply(db, key, &query)
+}
+
+/// `ZRANGESTORE dst src min max [BYSCORE | BYLEX] [REV] [LIMIT offset count]`.
+pub fn handle_zrangestore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (destination, source) = (args.next_bytes()?, args.next_bytes()?);
+    let query = parse_zrange(&mut args, false)?;
+
+    let selected = select_owned(db, source, &query)?;
+    Ok(integer(store_zset(db, destination, selected.into_iter().collect()) as i64))
+}
+
+/// `ZRANGESTORE` whose source and destination live on different shards: the range is read on
+/// the source's owner and written on the destination's.
+pub async fn zrangestore_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (destination, source) = (args.next_bytes()?.clone(), args.next_bytes()?.clone());
+    let query = parse_zrange(&mut args, false)?;
+
+    let owner = shard.owner(&source);
+    let selected = shard.run_on(owner, move |db| select_owned(db, &source, &query)).await?;
+    let stored = shard
+        .run_on(shard.owner(&destination), move |db| {
+            store_zset(db, &destination, selected.into_iter().collect())
+        })
+        .await;
+    Ok(integer(stored as i64))
+}
+
+pub fn handle_zlexcount(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = LexRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    args.finish()?;
+
+    let zset = zset_mut(db, key, clock::now())?;
+    Ok(integer(zset.map_or(0, |zset| range.ranks(zset).len()) as i64))
+}
+
+pub fn handle_zremrangebylex(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let range = LexRange::parse(args.next_bytes()?, args.next_bytes()?)?;
+    args.finish()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(integer(0));
+    };
+    let removed = remove_ranks(zset, range.ranks(zset));
+    remove_if_empty(db, key);
+    Ok(integer(removed as i64))
+}
+
+pub fn handle_zpopmin(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    pop(&mut args, db, false)
+}
+
+pub fn handle_zpopmax(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    pop(&mut args, db, true)
+}
+
+pub fn handle_zmscore(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let members = args.rest_bytes()?;
+
+    let zset = zset_mut(db, key, clock::now())?;
+    Ok(array(
+        members
+            .into_iter()
+            .map(|member| {
+                let score = zset.as_ref().and_then(|zset| zset.score(member));
+                score.map_or(BytesFrame::Null, double)
+            })
+            .collect(),
+    ))
+}
+
+/// `ZRANDMEMBER key [count [WITHSCORES]]`. A positive count returns distinct members, a
+/// negative one may repeat members; see [`sample`].
+pub fn handle_zrandmember(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        Some(args.next_i64()?)
+    };
+    let with_scores = if count.is_some() && !args.is_empty() {
+        let token = args.next_token()?;
+        if !token.is("WITHSCORES") {
+            return Err(token.syntax_error());
+        }
+        true
+    } else {
+        false
+    };
+    args.finish()?;
+
+    let zset = zset_mut(db, key, clock::now())?;
+    let Some(count) = count else {
+        let member = zset.and_then(|zset| zset.random()).map(|(member, _)| member);
+        return Ok(member.map_or(BytesFrame::Null, bulk));
+    };
+    let Some(zset) = zset else {
+        return Ok(array(Vec::new()));
+    };
+    let picked = sample(count, zset.len(), zset.iter(), || zset.random())?;
+    Ok(members_reply(picked, with_scores))
+}
+
+/// Shared body of `ZRANK` and `ZREVRANK`, which take an optional `WITHSCORE`.
+fn rank(
+    args: &mut Args,
+    db: &mut DataStore,
+    reverse: bool,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let member = args.next_bytes()?;
+    let with_score = if args.is_empty() {
+        false
+    } else {
+        let token = args.next_token()?;
+        if !token.is("WITHSCORE") {
+            return Err(token.syntax_error());
+        }
+        true
+    };
+    args.finish()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(BytesFrame::Null);
+    };
+    let (Some(rank), Some(score)) = (zset.rank(member), zset.score(member)) else {
+        return Ok(BytesFrame::Null);
+    };
+    let rank = if reverse { zset.len() - 1 - rank } else { rank };
+    Ok(if with_score {
+        array(vec![integer(rank as i64), double(score)])
+    } else {
+        integer(rank as i64)
+    })
+}
+
+/// Shared body of `ZPOPMIN` and `ZPOPMAX`. Without a count the reply is one `[member, score]`
+/// pair; with one it is an array of such pairs.
+fn pop(
+    args: &mut Args,
+    db: &mut DataStore,
+    max: bool,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let key = args.next_bytes()?;
+    let count = if args.is_empty() {
+        None
+    } else {
+        let count = args.next_i64()?;
+        if count < 0 {
+            return Err(CommandExecutionError::NotPositive);
+        }
+        Some(count as usize)
+    };
+    args.finish()?;
+
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(array(Vec::new()));
+    };
+    let popped: Vec<(Bytes, f64)> = (0..count.unwrap_or(1).min(zset.len()))
+        .filter_map(|_| if max { zset.pop_max() } else { zset.pop_min() })
+        .collect();
+    remove_if_empty(db, key);
+
+    if count.is_none() {
+        let pair = popped.into_iter().next();
+        return Ok(array(
+            pair.map_or(Vec::new(), |(member, score)| vec![bulk(member), double(score)]),
+        ));
+    }
+    Ok(members_reply(popped, true))
+}
+
+/// Parses the unified `ZRANGE` arguments that follow the key(s):
+/// `start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`.
+fn parse_zrange(
+    args: &mut Args,
+    allow_with_scores: bool,
+) -> Result<RangeQuery, CommandExecutionError> {
+    let (start, stop) = (args.next_bytes()?, args.next_bytes()?);
+    let (mut by_score, mut by_lex, mut rev) = (false, false, false);
+    let mut limit = None;
+    let mut with_scores = false;
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if token.is("BYSCORE") && !by_lex {
+            by_score = true;
+        } else if token.is("BYLEX") && !by_score {
+            by_lex = true;
+        } else if token.is("REV") {
+            rev = true;
+        } else if token.is("LIMIT") {
+            limit = Some((args.next_i64()?, args.next_i64()?));
+        } else if token.is("WITHSCORES") && allow_with_scores {
+            with_scores = true;
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+    if limit.is_some() && !by_score && !by_lex {
+        return Err(CommandExecutionError::LimitWithoutBy);
+    }
+    if with_scores && by_lex {
+        return Err(CommandExecutionError::WithScoresByLex);
+    }
+
+    // With REV the score and lex forms take the interval as `max min`.
+    let (min, max) = if rev { (stop, start) } else { (start, stop) };
+    let by = if by_score {
+        RangeBy::Score(ScoreRange::parse(min, max)?)
+    } else if by_lex {
+        RangeBy::Lex(LexRange::parse(min, max)?)
+    } else {
+        let start = parse_i64(start).ok_or(CommandExecutionError::NotInteger)?;
+        let stop = parse_i64(stop).ok_or(CommandExecutionError::NotInteger)?;
+        RangeBy::Rank(start, stop)
+    };
+    Ok(RangeQuery { by, rev, limit, with_scores })
+}
+
+/// Parses the `[WITHSCORES] [LIMIT offset count]` tail of the legacy range commands.
+fn parse_range_options(
+    args: &mut Args,
+    query: &mut RangeQuery,
+    allow_with_scores: bool,
+    allow_limit: bool,
+) -> Result<(), CommandExecutionError> {
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if token.is("WITHSCORES") && allow_with_scores {
+            query.with_scores = true;
+        } else if token.is("LIMIT") && allow_limit {
+            query.limit = Some((args.next_i64()?, args.next_i64()?));
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+    Ok(())
+}
+
+fn range_reply(
+    db: &mut DataStore,
+    key: &Bytes,
+    query: &RangeQuery,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(array(Vec::new()));
+    };
+    Ok(members_reply(query.run(zset), query.with_scores))
+}
+
+/// Members as bulk strings, or as `[member, score]` pairs when `with_scores` is set.
+pub fn members_reply(members: Vec<(Bytes, f64)>, with_scores: bool) -> BytesFrame {
+    array(
+        members
+            .into_iter()
+            .map(|(member, score)| {
+                if with_scores {
+                    array(vec![bulk(member), double(score)])
+                } else {
+                    bulk(member)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Runs `query` against the sorted set at `key`, copying out the selection.
+fn select_owned(
+    db: &mut DataStore,
+    key: &Bytes,
+    query: &RangeQuery,
+) -> Result<Vec<(Bytes, f64)>, CommandExecutionError> {
+    let Some(zset) = zset_mut(db, key, clock::now())? else {
+        return Ok(Vec::new());
+    };
+    Ok(query
+        .run(zset)
+        .into_iter()
+        .map(|(member, score)| (member.clone(), score))
+        .collect())
+}
+
+/// Replaces `destination` with `zset`, or deletes it when `zset` is empty. Returns the stored
+/// cardinality.
+pub fn store_zset(db: &mut DataStore, destination: &Bytes, zset: SortedSet) -> usize {
+    let len = zset.len();
+    if zset.is_empty() {
+        db.delete(destination, CONFIG.lazyfree_lazy_server_del());
+    } else {
+        db.insert(destination.clone(), StoreObject::new(DataKind::SortedSet(zset), clock::now()));
+    }
+    len
+}
+
+/// Removes the members whose ranks fall in `ranks`; returns how many.
+fn remove_ranks(zset: &mut SortedSet, ranks: Range<usize>) -> usize {
+    if ranks.is_empty() {
+        return 0;
+    }
+    zset.remove_range(ranks.start, ranks.end - 1)
+}
+
+/// Parses one end of a lex interval: `-`, `+`, `[member` or `(member`.
+fn parse_lex_bound(bytes: &Bytes) -> Result<LexBound, CommandExecutionError> {
+    match bytes.first() {
+        Some(b'-') if bytes.len() == 1 => Ok(LexBound::Lowest),
+        Some(b'+') if bytes.len() == 1 => Ok(LexBound::Highest),
+        Some(b'[') => Ok(LexBound::Inclusive(bytes.slice(1..))),
+        Some(b'(') => Ok(LexBound::Exclusive(bytes.slice(1..))),
+        _ => Err(CommandExecutionError::MinMaxNotLex),
+    }
+}
+
+/// Parses one end of a score interval: a float, optionally prefixed by `(` to exclude it.
+fn parse_score_bound(bytes: &[u8]) -> Result<(f64, bool), CommandExecutionError> {
+    let (bytes, exclusive) = match bytes.strip_prefix(b"(") {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+    let value = parse_f64(bytes).ok_or(CommandExecutionError::MinMaxNotFloat)?;
+    Ok((value, exclusive))
+}
+
+/// Sorted set stored at `key`, `None` if the key is missing, or `WRONGTYPE` for other types.
+pub fn zset_mut<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<Option<&'a mut SortedSet>, CommandExecutionError> {
+    match lookup_mut(db, key, now) {
+        Some(StoreObject { data: DataKind::SortedSet(zset), .. }) => Ok(Some(zset)),
+        Some(_) => Err(CommandExecutionError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// Sorted set stored at `key`, creating an empty one if the key is missing.
+pub fn zset_or_create<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Result<&'a mut SortedSet, CommandExecutionError> {
+    if zset_mut(db, key, now)?.is_none() {
+        db.insert(key.clone(), StoreObject::new(DataKind::SortedSet(SortedSet::default()), now));
+    }
+    Ok(zset_mut(db, key, now)?.expect("sorted set was just looked up or created"))
+}
+
+/// Drops `key` once its sorted set has no members left.
+pub fn remove_if_empty(db: &mut DataStore, key: &Bytes) {
+    if matches!(db.get(key), Some(StoreObject { data: DataKind::SortedSet(zset), .. }) if zset.is_empty())
+    {
+        db.remove(key);
+    }
+}
@@ -0,0 +1,596 @@
+use bytes::Bytes;
+use redis_protocol::resp3::types::BytesFrame;
+use std::collections::HashMap;
+
+use crate::{
+    commands::{
+        args::{Args, parse_f64},
+        reply::integer,
+        zset::{members_reply, store_zset},
+    },
+    errors::CommandExecutionError,
+    shard::{
+        clock,
+        shard::Shard,
+        types::{DataKind, DataStore, SetValue, lookup_mut},
+        zset::SortedSet,
+    },
+};
+
+/// Algebra of `ZUNION`, `ZINTER` and `ZDIFF` and their `STORE` variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ZsetOp {
+    Union,
+    Inter,
+    Diff,
+}
+
+/// How the weighted scores of a member found in several inputs are merged.
+#[derive(Clone, Copy, Debug, Default)]
+enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, acc: f64, score: f64) -> f64 {
+        match self {
+            // inf + -inf is NaN, which Redis stores as 0.
+            Aggregate::Sum => zero_if_nan(acc + score),
+            Aggregate::Min => acc.min(score),
+            Aggregate::Max => acc.max(score),
+        }
+    }
+}
+
+/// Parsed `numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]`.
+#[derive(Debug)]
+struct AlgebraArgs {
+    keys: Vec<Bytes>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
+
+/// An input of the algebra. Plain sets take part too, every member scoring 1.
+#[derive(Clone, Copy)]
+enum Operand<'a> {
+    Sorted(&'a SortedSet),
+    Plain(&'a SetValue),
+}
+
+/// An input copied out of another shard.
+enum OwnedOperand {
+    Sorted(SortedSet),
+    Plain(SetValue),
+}
+
+impl<'a> Operand<'a> {
+    fn len(self) -> usize {
+        match self {
+            Operand::Sorted(zset) => zset.len(),
+            Operand::Plain(set) => set.len(),
+        }
+    }
+
+    fn score(self, member: &Bytes) -> Option<f64> {
+        match self {
+            Operand::Sorted(zset) => zset.score(member),
+            Operand::Plain(set) => set.contains(member).then_some(1.0),
+        }
+    }
+
+    fn members(self) -> Box<dyn Iterator<Item = (Bytes, f64)> + 'a> {
+        match self {
+            Operand::Sorted(zset) => zset.iter(),
+            Operand::Plain(set) => Box::new(set.iter().map(|member| (member, 1.0))),
+        }
+    }
+}
+
+impl OwnedOperand {
+    fn borrow(&self) -> Operand<'_> {
+        match self {
+            OwnedOperand::Sorted(zset) => Operand::Sorted(zset),
+            OwnedOperand::Plain(set) => Operand::Plain(set),
+        }
+    }
+}
+
+pub fn handle_zunion(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, ZsetOp::Union)
+}
+
+pub fn handle_zinter(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, ZsetOp::Inter)
+}
+
+pub fn handle_zdiff(args: Args, db: &mut DataStore) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys(args, db, ZsetOp::Diff)
+}
+
+pub fn handle_zunionstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, ZsetOp::Union)
+}
+
+pub fn handle_zinterstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, ZsetOp::Inter)
+}
+
+pub fn handle_zdiffstore(
+    args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into(args, db, ZsetOp::Diff)
+}
+
+/// `ZINTERCARD numkeys key [key ...] [LIMIT limit]`.
+pub fn handle_zintercard(
+    mut args: Args,
+    db: &mut DataStore,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, limit) = parse_zintercard(&mut args)?;
+    let operands = load_operands(db, &keys)?;
+    Ok(integer(intersection(&operands).take(limit).count() as i64))
+}
+
+pub async fn zunion_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, ZsetOp::Union).await
+}
+
+pub async fn zinter_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, ZsetOp::Inter).await
+}
+
+pub async fn zdiff_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_cross_shard(shard, args, ZsetOp::Diff).await
+}
+
+pub async fn zunionstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, ZsetOp::Union).await
+}
+
+pub async fn zinterstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, ZsetOp::Inter).await
+}
+
+pub async fn zdiffstore_cross_shard(
+    shard: &Shard,
+    args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    combine_keys_into_cross_shard(shard, args, ZsetOp::Diff).await
+}
+
+/// `ZINTERCARD` across shards: the inputs are copied from their owners and intersected here.
+pub async fn zintercard_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let (keys, limit) = parse_zintercard(&mut args)?;
+    let fetched = fetch_operands(shard, keys).await?;
+    let operands: Vec<Option<Operand>> = fetched
+        .iter()
+        .map(|operand| operand.as_ref().map(OwnedOperand::borrow))
+        .collect();
+    Ok(integer(intersection(&operands).take(limit).count() as i64))
+}
+
+/// Shared body of `ZUNION`, `ZINTER` and `ZDIFF` when all keys are local.
+fn combine_keys(
+    mut args: Args,
+    db: &mut DataStore,
+    op: ZsetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let parsed = parse_algebra(&mut args, op, true)?;
+    let result = combine(op, &load_operands(db, &parsed.keys)?, &parsed);
+    Ok(members_reply(result.iter().collect(), parsed.with_scores))
+}
+
+/// Shared body of the `STORE` variants when all keys are local.
+fn combine_keys_into(
+    mut args: Args,
+    db: &mut DataStore,
+    op: ZsetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let destination = args.next_bytes()?;
+    let parsed = parse_algebra(&mut args, op, false)?;
+    let result = combine(op, &load_operands(db, &parsed.keys)?, &parsed);
+    Ok(integer(store_zset(db, destination, result) as i64))
+}
+
+/// Copies the inputs from their owning shards and combines them on this one.
+async fn combine_keys_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+    op: ZsetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let mut parsed = parse_algebra(&mut args, op, true)?;
+    let fetched = fetch_operands(shard, std::mem::take(&mut parsed.keys)).await?;
+    let operands: Vec<Option<Operand>> = fetched
+        .iter()
+        .map(|operand| operand.as_ref().map(OwnedOperand::borrow))
+        .collect();
+    let result = combine(op, &operands, &parsed);
+    Ok(members_reply(result.iter().collect(), parsed.with_scores))
+}
+
+/// Combines the inputs on this shard and writes the result on the destination's owner.
+async fn combine_keys_into_cross_shard(
+    shard: &Shard,
+    mut args: Args<'_>,
+    op: ZsetOp,
+) -> Result<BytesFrame, CommandExecutionError> {
+    let destination = args.next_bytes()?.clone();
+    let mut parsed = parse_algebra(&mut args, op, false)?;
+    let fetched = fetch_operands(shard, std::mem::take(&mut parsed.keys)).await?;
+    let operands: Vec<Option<Operand>> = fetched
+        .iter()
+        .map(|operand| operand.as_ref().map(OwnedOperand::borrow))
+        .collect();
+    let result = combine(op, &operands, &parsed);
+
+    let stored = shard
+        .run_on(shard.owner(&destination), move |db| store_zset(db, &destination, result))
+        .await;
+    Ok(integer(stored as i64))
+}
+
+/// Parses the arguments after the destination (if any). `ZDIFF` takes neither `WEIGHTS` nor
+/// `AGGREGATE`, and only the non-storing commands take `WITHSCORES`.
+fn parse_algebra(
+    args: &mut Args,
+    op: ZsetOp,
+    allow_with_scores: bool,
+) -> Result<AlgebraArgs, CommandExecutionError> {
+    let numkeys = args.next_i64()?;
+    if numkeys <= 0 {
+        return Err(args.no_input_keys());
+    }
+    if numkeys as u64 > args.remaining() as u64 {
+        return Err(CommandExecutionError::NumKeysTooMany);
+    }
+    let keys = (0..numkeys)
+        .map(|_| args.next_bytes().cloned())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut parsed = AlgebraArgs {
+        weights: vec![1.0; keys.len()],
+        keys,
+        aggregate: Aggregate::default(),
+        with_scores: false,
+    };
+    while !args.is_empty() {
+        let token = args.next_token()?;
+        if token.is("WEIGHTS") && op != ZsetOp::Diff {
+            if args.remaining() < parsed.keys.len() {
+                return Err(token.syntax_error());
+            }
+            for weight in &mut parsed.weights {
+                *weight =
+                    parse_f64(args.next_bytes()?).ok_or(CommandExecutionError::WeightNotFloat)?;
+            }
+        } else if token.is("AGGREGATE") && op != ZsetOp::Diff {
+            let kind = args.next_token()?;
+            parsed.aggregate = if kind.is("SUM") {
+                Aggregate::Sum
+            } else if kind.is("MIN") {
+                Aggregate::Min
+            } else if kind.is("MAX") {
+                Aggregate::Max
+            } else {
+                return Err(kind.syntax_error());
+            };
+        } else if token.is("WITHSCORES") && allow_with_scores {
+            parsed.with_scores = true;
+        } else {
+            return Err(token.syntax_error());
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses `numkeys key [key ...] [LIMIT limit]`; a limit of 0 means no limit.
+fn parse_zintercard(args: &mut Args) -> Result<(Vec<Bytes>, usize), CommandExecutionError> {
+    let numkeys = args.next_i64()?;
+    if numkeys <= 0 {
+        return Err(CommandExecutionError::NumKeysNotPositive);
+    }
+    if numkeys as u64 > args.remaining() as u64 {
+        return Err(CommandExecutionError::NumKeysTooMany);
+    }
+    let keys = (0..numkeys)
+        .map(|_| args.next_bytes().cloned())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut limit = usize::MAX;
+    if !args.is_empty() {
+        let token = args.next_token()?;
+        if !token.is("LIMIT") {
+            return Err(token.syntax_error());
+        }
+        let n = args.next_i64()?;
+        if n < 0 {
+            return Err(CommandExecutionError::LimitNegative);
+        }
+        if n > 0 {
+            limit = n as usize;
+        }
+    }
+    args.finish()?;
+    Ok((keys, limit))
+}
+
+/// Type-checks and expires every key, then borrows the inputs they hold; missing keys are
+/// `None`.
+fn load_operands<'a>(
+    db: &'a mut DataStore,
+    keys: &[Bytes],
+) -> Result<Vec<Option<Operand<'a>>>, CommandExecutionError> {
+    let now = clock::now();
+    for key in keys {
+        check_operand(lookup_mut(db, key, now).map(|obj| &obj.data))?;
+    }
+    let db: &'a DataStore = db;
+    Ok(keys
+        .iter()
+        .map(|key| match db.get(key).map(|obj| &obj.data) {
+            Some(DataKind::SortedSet(zset)) => Some(Operand::Sorted(zset)),
+            Some(DataKind::Set(set)) => Some(Operand::Plain(set)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Copies the inputs stored at `keys` from their owning shards, in the order of `keys`.
+async fn fetch_operands(
+    shard: &Shard,
+    keys: Vec<Bytes>,
+) -> Result<Vec<Option<OwnedOperand>>, CommandExecutionError> {
+    shard
+        .scatter_gather(
+            keys,
+            |key| key,
+            |db, keys| {
+                let now = clock::now();
+                keys.iter()
+                    .map(|key| {
+                        let data = lookup_mut(db, key, now).map(|obj| &obj.data);
+                        check_operand(data)?;
+                        Ok(match data {
+                            Some(DataKind::SortedSet(zset)) => {
+                                Some(OwnedOperand::Sorted(zset.clone()))
+                            }
+                            Some(DataKind::Set(set)) => Some(OwnedOperand::Plain(set.clone())),
+                            _ => None,
+                        })
+                    })
+                    .collect()
+            },
+        )
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Only sorted sets and plain sets can be inputs.
+fn check_operand(data: Option<&DataKind>) -> Result<(), CommandExecutionError> {
+    match data {
+        None | Some(DataKind::SortedSet(_) | DataKind::Set(_)) => Ok(()),
+        Some(_) => Err(CommandExecutionError::WrongType),
+    }
+}
+
+/// Applies `op` to the inputs in order. A missing key counts as an empty set.
+fn combine(op: ZsetOp, operands: &[Option<Operand>], parsed: &AlgebraArgs) -> SortedSet {
+    let weighted = |index: usize, score: f64| zero_if_nan(score * parsed.weights[index]);
+    match op {
+        ZsetOp::Union => {
+            let mut scores: HashMap<Bytes, f64> = HashMap::new();
+            for (index, operand) in operands.iter().enumerate() {
+                let Some(operand) = operand else { continue };
+                for (member, score) in operand.members() {
+                    let score = weighted(index, score);
+                    scores
+                        .entry(member)
+                        .and_modify(|acc| *acc = parsed.aggregate.apply(*acc, score))
+                        .or_insert(score);
+                }
+            }
+            scores.into_iter().collect()
+        }
+        ZsetOp::Inter => intersection(operands)
+            .map(|member| {
+                let mut scores = operands.iter().enumerate().map(|(index, operand)| {
+                    let score = operand.and_then(|operand| operand.score(&member));
+                    weighted(index, score.expect("member is in every input"))
+                });
+                let first = scores.next().expect("intersections have an input");
+                let score = scores.fold(first, |acc, score| parsed.aggregate.apply(acc, score));
+                (member, score)
+            })
+            .collect(),
+        ZsetOp::Diff => {
+            let Some((Some(first), rest)) = operands.split_first() else {
+                return SortedSet::default();
+            };
+            first
+                .members()
+                .filter(|(member, _)| {
+                    !rest.iter().flatten().any(|operand| operand.score(member).is_some())
+                })
+                .collect()
+        }
+    }
+}
+
+/// Members of every input, found by probing the others with each member of the smallest.
+fn intersection<'a>(operands: &[Option<Operand<'a>>]) -> impl Iterator<Item = Bytes> + 'a {
+    // One missing key empties the whole intersection.
+    let mut operands: Vec<Operand<'a>> =
+        operands.iter().copied().collect::<Option<_>>().unwrap_or_default();
+    operands.sort_by_key(|operand| operand.len());
+    let smallest = operands.first().copied();
+    smallest
+        .into_iter()
+        .flat_map(|operand| operand.members())
+        .map(|(member, _)| member)
+        .filter(move |member| operands[1..].iter().all(|operand| operand.score(member).is_some()))
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() { 0.0 } else { score }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis_protocol::resp3::types::BytesFrame;
+
+    use crate::{
+        commands::{
+            CommandKind,
+            dispatcher::{run, run_err},
+            reply::{array, bulk, double, integer},
+        },
+        shard::types::DataStore,
+    };
+
+    fn scored(pairs: &[(&str, f64)]) -> BytesFrame {
+        array(
+            pairs
+                .iter()
+                .map(|(member, score)| array(vec![bulk(member.to_string().into()), double(*score)]))
+                .collect(),
+        )
+    }
+
+    /// `a` = {x: 1, y: 2, z: 3}, `b` = {y: 10, z: 20, w: 5}, and the plain set `s` = {z, w}.
+    fn store() -> DataStore {
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Zadd, &["a", "1", "x", "2", "y", "3", "z"]);
+        run(&mut db, CommandKind::Zadd, &["b", "10", "y", "20", "z", "5", "w"]);
+        run(&mut db, CommandKind::Sadd, &["s", "z", "w"]);
+        db
+    }
+
+    #[test]
+    fn union_weighs_then_aggregates() {
+        let mut db = store();
+        let union = |db: &mut DataStore, extra: &[&str]| {
+            run(
+                db,
+                CommandKind::Zunion,
+                &[
+                    &["2", "a", "b", "WEIGHTS", "2", "0.5"],
+                    extra,
+                    &["WITHSCORES"],
+                ]
+                .concat(),
+            )
+        };
+        assert_eq!(union(&mut db, &[]), scored(&[("x", 2.0), ("w", 2.5), ("y", 9.0), ("z", 16.0)]));
+        assert_eq!(
+            union(&mut db, &["AGGREGATE", "MIN"]),
+            scored(&[("x", 2.0), ("w", 2.5), ("y", 4.0), ("z", 6.0)])
+        );
+        assert_eq!(
+            union(&mut db, &["AGGREGATE", "MAX"]),
+            scored(&[("x", 2.0), ("w", 2.5), ("y", 5.0), ("z", 10.0)])
+        );
+    }
+
+    #[test]
+    fn inter_scores_plain_set_members_as_one() {
+        let mut db = store();
+        assert_eq!(
+            run(&mut db, CommandKind::Zinter, &["3", "a", "b", "s", "WITHSCORES"]),
+            scored(&[("z", 24.0)])
+        );
+        assert_eq!(
+            run(&mut db, CommandKind::Zinter, &["2", "b", "s", "AGGREGATE", "MAX", "WITHSCORES"]),
+            scored(&[("w", 5.0), ("z", 20.0)])
+        );
+        assert_eq!(run(&mut db, CommandKind::Zinter, &["2", "a", "missing"]), array(vec![]));
+        assert_eq!(run(&mut db, CommandKind::Zinterstore, &["dst", "2", "a", "b"]), integer(2));
+        assert_eq!(
+            run(&mut db, CommandKind::Zrange, &["dst", "0", "-1", "WITHSCORES"]),
+            scored(&[("y", 12.0), ("z", 23.0)])
+        );
+    }
+
+    #[test]
+    fn infinite_weights_and_nan_sums_score_zero() {
+        let mut db = DataStore::default();
+        run(&mut db, CommandKind::Zadd, &["pos", "1", "x", "0", "y", "inf", "z"]);
+        run(&mut db, CommandKind::Zadd, &["neg", "-inf", "z"]);
+
+        // 0 * inf is NaN, stored as 0 like Redis does.
+        assert_eq!(
+            run(&mut db, CommandKind::Zunion, &["1", "pos", "WEIGHTS", "inf", "WITHSCORES"]),
+            scored(&[("y", 0.0), ("x", f64::INFINITY), ("z", f64::INFINITY)])
+        );
+        assert_eq!(
+            run(&mut db, CommandKind::Zunion, &["1", "pos", "WEIGHTS", "-inf", "WITHSCORES"]),
+            scored(&[
+                ("x", f64::NEG_INFINITY),
+                ("z", f64::NEG_INFINITY),
+                ("y", 0.0)
+            ])
+        );
+        // inf + -inf is NaN as well.
+        let combine = |db: &mut DataStore, cmd, aggregate: &str| {
+            run(db, cmd, &["2", "pos", "neg", "AGGREGATE", aggregate, "WITHSCORES"])
+        };
+        assert_eq!(combine(&mut db, CommandKind::Zinter, "SUM"), scored(&[("z", 0.0)]));
+        assert_eq!(
+            combine(&mut db, CommandKind::Zinter, "MIN"),
+            scored(&[("z", f64::NEG_INFINITY)])
+        );
+        assert_eq!(combine(&mut db, CommandKind::Zinter, "MAX"), scored(&[("z", f64::INFINITY)]));
+        assert_eq!(
+            combine(&mut db, CommandKind::Zunion, "SUM"),
+            scored(&[("y", 0.0), ("z", 0.0), ("x", 1.0)])
+        );
+    }
+
+    #[test]
+    fn diff_keeps_first_input_order_and_scores() {
+        let mut db = store();
+        run(&mut db, CommandKind::Zadd, &["a", "2", "v", "0", "u"]);
+        assert_eq!(
+            run(&mut db, CommandKind::Zdiff, &["2", "a", "b", "WITHSCORES"]),
+            scored(&[("u", 0.0), ("x", 1.0), ("v", 2.0)])
+        );
+        assert_eq!(
+            run(&mut db, CommandKind::Zdiff, &["3", "a", "s", "missing"]),
+            array(["u", "x", "v", "y"].map(|m| bulk(m.into())).into())
+        );
+        assert_eq!(run(&mut db, CommandKind::Zdiff, &["2", "missing", "a"]), array(vec![]));
+        assert_eq!(
+            run_err(&mut db, CommandKind::Zdiff, &["2", "a", "b", "WEIGHTS", "1", "2"]),
+            "ERR syntax error near 'WEIGHTS'"
+        );
+    }
+}
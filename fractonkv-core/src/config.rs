@@ -0,0 +1,351 @@
+//! Server settings shared by every shard.
+//!
+//! Each setting is an atomic in the global [`CONFIG`], so shards read it without locking and
+//! `CONFIG SET` takes effect on all of them at once. Settings are given on the command line as
+//! `--name value` pairs and can be inspected and changed at runtime with `CONFIG GET` and
+//! `CONFIG SET`, using the same names and value formats as Redis.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::errors::CommandExecutionError;
+use crate::shard::evict::EvictionPolicy;
+
+pub static CONFIG: Config = Config::new();
+
+pub struct Config {
+    maxmemory: AtomicU64,
+    maxmemory_policy: AtomicU64,
+    maxmemory_samples: AtomicU64,
+    lfu_log_factor: AtomicU64,
+    lfu_decay_time: AtomicU64,
+    hash_max_listpack_entries: AtomicU64,
+    hash_max_listpack_value: AtomicU64,
+    set_max_intset_entries: AtomicU64,
+    set_max_listpack_entries: AtomicU64,
+    set_max_listpack_value: AtomicU64,
+    zset_max_listpack_entries: AtomicU64,
+    zset_max_listpack_value: AtomicU64,
+    lazyfree_lazy_eviction: AtomicU64,
+    lazyfree_lazy_expire: AtomicU64,
+    lazyfree_lazy_server_del: AtomicU64,
+    lazyfree_lazy_user_del: AtomicU64,
+    lazyfree_lazy_user_flush: AtomicU64,
+    activerehashing: AtomicU64,
+}
+
+/// A setting as seen by `CONFIG`: its name, where it is stored and how its value is written.
+struct Param {
+    name: &'static str,
+    cell: fn(&Config) -> &AtomicU64,
+    parse: fn(&str) -> Result<u64, String>,
+    format: fn(u64) -> String,
+}
+
+const PARAMS: &[Param] = &[
+    Param {
+        name: "maxmemory",
+        cell: |config| &config.maxmemory,
+        parse: parse_memory,
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "maxmemory-policy",
+        cell: |config| &config.maxmemory_policy,
+        parse: |value| {
+            EvictionPolicy::from_name(value).map(|policy| policy as u64).ok_or_else(|| {
+                let names: Vec<_> = EvictionPolicy::ALL.iter().map(|p| p.name()).collect();
+                format!("argument(s) must be one of the following: {}", names.join(", "))
+            })
+        },
+        format: |value| EvictionPolicy::ALL[value as usize].name().to_string(),
+    },
+    Param {
+        name: "maxmemory-samples",
+        cell: |config| &config.maxmemory_samples,
+        parse: |value| parse_bounded(value, 1, 64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "lfu-log-factor",
+        cell: |config| &config.lfu_log_factor,
+        parse: |value| parse_bounded(value, 0, i32::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "lfu-decay-time",
+        cell: |config| &config.lfu_decay_time,
+        parse: |value| parse_bounded(value, 0, i32::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "hash-max-listpack-entries",
+        cell: |config| &config.hash_max_listpack_entries,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "hash-max-listpack-value",
+        cell: |config| &config.hash_max_listpack_value,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "set-max-intset-entries",
+        cell: |config| &config.set_max_intset_entries,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "set-max-listpack-entries",
+        cell: |config| &config.set_max_listpack_entries,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "set-max-listpack-value",
+        cell: |config| &config.set_max_listpack_value,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "zset-max-listpack-entries",
+        cell: |config| &config.zset_max_listpack_entries,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "zset-max-listpack-value",
+        cell: |config| &config.zset_max_listpack_value,
+        parse: |value| parse_bounded(value, 0, i64::MAX as u64),
+        format: |value| value.to_string(),
+    },
+    Param {
+        name: "lazyfree-lazy-eviction",
+        cell: |config| &config.lazyfree_lazy_eviction,
+        parse: parse_bool,
+        format: format_bool,
+    },
+    Param {
+        name: "lazyfree-lazy-expire",
+        cell: |config| &config.lazyfree_lazy_expire,
+        parse: parse_bool,
+        format: format_bool,
+    },
+    Param {
+        name: "lazyfree-lazy-server-del",
+        cell: |config| &config.lazyfree_lazy_server_del,
+        parse: parse_bool,
+        format: format_bool,
+    },
+    Param {
+        name: "lazyfree-lazy-user-del",
+        cell: |config| &config.lazyfree_lazy_user_del,
+        parse: parse_bool,
+        format: format_bool,
+    },
+    Param {
+        name: "lazyfree-lazy-user-flush",
+        cell: |config| &config.lazyfree_lazy_user_flush,
+        parse: parse_bool,
+        format: format_bool,
+    },
+    Param {
+        name: "activerehashing",
+        cell: |config| &config.activerehashing,
+        parse: parse_bool,
+        format: format_bool,
+    },
+];
+
+impl Config {
+    const fn new() -> Self {
+        Self {
+            maxmemory: AtomicU64::new(0),
+            maxmemory_policy: AtomicU64::new(EvictionPolicy::NoEviction as u64),
+            maxmemory_samples: AtomicU64::new(5),
+            lfu_log_factor: AtomicU64::new(10),
+            lfu_decay_time: AtomicU64::new(1),
+            hash_max_listpack_entries: AtomicU64::new(128),
+            hash_max_listpack_value: AtomicU64::new(64),
+            set_max_intset_entries: AtomicU64::new(512),
+            set_max_listpack_entries: AtomicU64::new(128),
+            set_max_listpack_value: AtomicU64::new(64),
+            zset_max_listpack_entries: AtomicU64::new(128),
+            zset_max_listpack_value: AtomicU64::new(64),
+            lazyfree_lazy_eviction: AtomicU64::new(0),
+            lazyfree_lazy_expire: AtomicU64::new(0),
+            lazyfree_lazy_server_del: AtomicU64::new(0),
+            lazyfree_lazy_user_del: AtomicU64::new(0),
+            lazyfree_lazy_user_flush: AtomicU64::new(0),
+            activerehashing: AtomicU64::new(1),
+        }
+    }
+
+    /// Memory limit of the whole server in bytes, shared evenly between shards; 0 means none.
+    pub fn maxmemory(&self) -> u64 {
+        self.maxmemory.load(Ordering::Relaxed)
+    }
+
+    /// How keys are chosen for eviction once a shard reaches its share of `maxmemory`.
+    pub fn maxmemory_policy(&self) -> EvictionPolicy {
+        EvictionPolicy::ALL[self.maxmemory_policy.load(Ordering::Relaxed) as usize]
+    }
+
+    /// Keys sampled to pick each eviction victim.
+    pub fn maxmemory_samples(&self) -> usize {
+        self.maxmemory_samples.load(Ordering::Relaxed) as usize
+    }
+
+    /// How many hits it takes to saturate an LFU counter: higher factors grow it more slowly.
+    pub fn lfu_log_factor(&self) -> u64 {
+        self.lfu_log_factor.load(Ordering::Relaxed)
+    }
+
+    /// Minutes without access that lower a key's LFU counter by one; 0 disables decay.
+    pub fn lfu_decay_time(&self) -> u64 {
+        self.lfu_decay_time.load(Ordering::Relaxed)
+    }
+
+    /// Most fields a hash keeps in a listpack before converting to a hash table.
+    pub fn hash_max_listpack_entries(&self) -> usize {
+        self.hash_max_listpack_entries.load(Ordering::Relaxed) as usize
+    }
+
+    /// Longest field or value, in bytes, a hash keeps in a listpack.
+    pub fn hash_max_listpack_value(&self) -> usize {
+        self.hash_max_listpack_value.load(Ordering::Relaxed) as usize
+    }
+
+    /// Most members an all-integer set keeps in an intset before converting to a hash table.
+    pub fn set_max_intset_entries(&self) -> usize {
+        self.set_max_intset_entries.load(Ordering::Relaxed) as usize
+    }
+
+    /// Most members a set keeps in a listpack before converting to a hash table.
+    pub fn set_max_listpack_entries(&self) -> usize {
+        self.set_max_listpack_entries.load(Ordering::Relaxed) as usize
+    }
+
+    /// Longest member, in bytes, a set keeps in a listpack.
+    pub fn set_max_listpack_value(&self) -> usize {
+        self.set_max_listpack_value.load(Ordering::Relaxed) as usize
+    }
+
+    /// Most members a sorted set keeps in a listpack before converting to a skiplist.
+    pub fn zset_max_listpack_entries(&self) -> usize {
+        self.zset_max_listpack_entries.load(Ordering::Relaxed) as usize
+    }
+
+    /// Longest member, in bytes, a sorted set keeps in a listpack.
+    pub fn zset_max_listpack_value(&self) -> usize {
+        self.zset_max_listpack_value.load(Ordering::Relaxed) as usize
+    }
+
+    /// Whether values evicted under `maxmemory` are freed on the background thread.
+    pub fn lazyfree_lazy_eviction(&self) -> bool {
+        self.lazyfree_lazy_eviction.load(Ordering::Relaxed) != 0
+    }
+
+    /// Whether values dropped for being past their TTL are freed on the background thread.
+    pub fn lazyfree_lazy_expire(&self) -> bool {
+        self.lazyfree_lazy_expire.load(Ordering::Relaxed) != 0
+    }
+
+    /// Whether values deleted as a side effect of a write, such as one overwritten by `SET` or
+    /// `RENAME`, are freed on the background thread.
+    pub fn lazyfree_lazy_server_del(&self) -> bool {
+        self.lazyfree_lazy_server_del.load(Ordering::Relaxed) != 0
+    }
+
+    /// Whether `DEL` frees values on the background thread like `UNLINK`.
+    pub fn lazyfree_lazy_user_del(&self) -> bool {
+        self.lazyfree_lazy_user_del.load(Ordering::Relaxed) != 0
+    }
+
+    /// Whether `FLUSHALL` and `FLUSHDB` without `SYNC` or `ASYNC` free the keyspace on the
+    /// background thread.
+    pub fn lazyfree_lazy_user_flush(&self) -> bool {
+        self.lazyfree_lazy_user_flush.load(Ordering::Relaxed) != 0
+    }
+
+    /// Whether shards spend idle ticks moving their keyspace along an ongoing resize, rather
+    /// than only as keys are written.
+    pub fn activerehashing(&self) -> bool {
+        self.activerehashing.load(Ordering::Relaxed) != 0
+    }
+
+    /// Names and current values of the settings whose name matches `matches`.
+    pub fn get(&self, mut matches: impl FnMut(&str) -> bool) -> Vec<(&'static str, String)> {
+        PARAMS
+            .iter()
+            .filter(|param| matches(param.name))
+            .map(|param| (param.name, (param.format)((param.cell)(self).load(Ordering::Relaxed))))
+            .collect()
+    }
+
+    /// Changes several settings at once. Every value is validated before any is applied, so a
+    /// failure leaves the configuration untouched.
+    pub fn set(&self, pairs: &[(&str, &str)]) -> Result<(), CommandExecutionError> {
+        let mut parsed = Vec::with_capacity(pairs.len());
+        for &(name, value) in pairs {
+            let param = PARAMS
+                .iter()
+                .find(|param| param.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| CommandExecutionError::UnknownConfig(name.to_string()))?;
+            let value = (param.parse)(value)
+                .map_err(|reason| CommandExecutionError::ConfigSetFailed(param.name, reason))?;
+            parsed.push((param, value));
+        }
+        for (param, value) in parsed {
+            (param.cell)(self).store(value, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Parses a memory amount such as `100mb` the way Redis does: `k`, `m` and `g` are powers of
+/// 1000, `kb`, `mb` and `gb` powers of 1024, and a bare number or `b` is bytes.
+fn parse_memory(value: &str) -> Result<u64, String> {
+    let lower = value.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(multiplier))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+fn parse_bounded(value: &str, min: u64, max: u64) -> Result<u64, String> {
+    let value: i64 = value
+        .parse()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    if value < min as i64 || value > max as i64 {
+        return Err(format!("argument must be between {min} and {max} inclusive"));
+    }
+    Ok(value as u64)
+}
+
+/// Parses a `yes`/`no` switch, stored as 1 or 0.
+fn parse_bool(value: &str) -> Result<u64, String> {
+    if value.eq_ignore_ascii_case("yes") {
+        Ok(1)
+    } else if value.eq_ignore_ascii_case("no") {
+        Ok(0)
+    } else {
+        Err("argument must be 'yes' or 'no'".to_string())
+    }
+}
+
+fn format_bool(value: u64) -> String {
+    if value != 0 { "yes" } else { "no" }.to_string()
+}
@@ -41,8 +41,17 @@ pub enum CommandExecutionError {
     #[error("ERR invalid expire time")]
     InvalidExpire,
 
-    #[error("ERR missing expire time")]
-    MissingExpire,
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireIn(&'static str),
+
+    #[error("ERR Unsupported option {0}")]
+    UnsupportedOption(String),
+
+    #[error("ERR NX and XX, GT or LT options at the same time are not compatible")]
+    ExpireNxAndOthers,
+
+    #[error("ERR GT and LT options at the same time are not compatible")]
+    ExpireGtLt,
 
     #[error("ERR syntax error near '{0}'")]
     SyntaxError(String),
@@ -52,6 +61,160 @@ pub enum CommandExecutionError {
 
     #[error("ERR unknown command")]
     UnknownCommand,
+
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+
+    #[error("ERR increment would produce NaN or Infinity")]
+    NanOrInfinity,
+
+    #[error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+
+    #[error("ERR offset is out of range")]
+    OffsetOutOfRange,
+
+    #[error("ERR If you want both the length and indexes, please just use IDX.")]
+    LcsLenAndIdx,
+
+    #[error("ERR The specified keys must contain string values")]
+    LcsNotString,
+
+    #[error("ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len")]
+    LcsTooLarge,
+
+    #[error("ERR hash value is not an integer")]
+    HashNotInteger,
+
+    #[error("ERR hash value is not a float")]
+    HashNotFloat,
+
+    #[error("ERR invalid expire time, must be >= 0")]
+    NegativeExpire,
+
+    #[error("ERR Mandatory argument FIELDS is missing or not at the right position")]
+    FieldsMissing,
+
+    #[error("ERR Parameter `numFields` should be greater than 0")]
+    NumFieldsNotPositive,
+
+    #[error("ERR The `numfields` parameter must match the number of arguments")]
+    NumFieldsMismatch,
+
+    #[error("ERR no such key")]
+    NoSuchKey,
+
+    #[error("ERR source and destination objects are the same")]
+    SameObject,
+
+    #[error("ERR DB index is out of range")]
+    DbIndexOutOfRange,
+
+    #[error("ERR invalid cursor")]
+    InvalidCursor,
+
+    #[error("ERR unknown type name '{0}'")]
+    UnknownTypeName(String),
+
+    #[error("ERR index out of range")]
+    IndexOutOfRange,
+
+    #[error("ERR value is out of range, must be positive")]
+    NotPositive,
+
+    #[error("ERR value is out of range")]
+    ValueOutOfRange,
+
+    #[error("ERR numkeys should be greater than 0")]
+    NumKeysNotPositive,
+
+    #[error("ERR Number of keys can't be greater than number of args")]
+    NumKeysTooMany,
+
+    #[error("ERR count should be greater than 0")]
+    CountNotPositive,
+
+    #[error(
+        "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list"
+    )]
+    LposRankZero,
+
+    #[error("ERR COUNT can't be negative")]
+    LposNegativeCount,
+
+    #[error("ERR MAXLEN can't be negative")]
+    LposNegativeMaxlen,
+
+    #[error("ERR XX and NX options at the same time are not compatible")]
+    ZaddNxXx,
+
+    #[error("ERR GT, LT, and/or NX options at the same time are not compatible")]
+    ZaddGtLtNx,
+
+    #[error("ERR INCR option supports a single increment-element pair")]
+    ZaddIncrPairs,
+
+    #[error("ERR resulting score is not a number (NaN)")]
+    ScoreNan,
+
+    #[error("ERR min or max is not a float")]
+    MinMaxNotFloat,
+
+    #[error("ERR min or max not valid string range item")]
+    MinMaxNotLex,
+
+    #[error(
+        "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+    )]
+    LimitWithoutBy,
+
+    #[error("ERR syntax error, WITHSCORES not supported in combination with BYLEX")]
+    WithScoresByLex,
+
+    #[error("ERR at least 1 input key is needed for '{0}' command")]
+    NoInputKeys(&'static str),
+
+    #[error("ERR weight value is not a float")]
+    WeightNotFloat,
+
+    #[error("ERR LIMIT can't be negative")]
+    LimitNegative,
+
+    #[error("ERR timeout is not a float or out of range")]
+    TimeoutNotFloat,
+
+    #[error("ERR timeout is negative")]
+    TimeoutNegative,
+
+    #[error("UNBLOCKED client unblocked via CLIENT UNBLOCK")]
+    Unblocked,
+
+    #[error("ERR unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, &'static str),
+
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossShard,
+
+    #[error("ERR '{0}' command is not allowed in this context")]
+    WrongContext(&'static str),
+
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+
+    #[error("ERR Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownConfig(String),
+
+    #[error("ERR CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    ConfigSetFailed(&'static str, String),
 }
 
 impl From<CommandExecutionError> for BytesFrame {
@@ -1,18 +1,30 @@
+use crate::allocator::CountingAllocator;
+use crate::config::CONFIG;
 use crate::shard::manager::ShardManager;
 use tracing::Level;
 
+mod allocator;
 mod commands;
+mod config;
 mod errors;
 mod shard;
 
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 fn main() {
     tracing_subscriber::fmt().with_target(false).with_max_level(Level::DEBUG).init();
 
+    if let Err(message) = apply_cli_config() {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
+
     print_banner();
 
     let num_shards = num_cpus::get();
     let mut shard_manager = ShardManager::new(num_shards);
-    let handles = shard_manager.start();
+    let handles = shard_manager.start("127.0.0.1:6380".parse().unwrap());
 
     // main decides to block until threads finish
     for handle in handles {
@@ -20,6 +32,19 @@ fn main() {
     }
 }
 
+/// Applies `--name value` pairs from the command line, e.g. `--maxmemory 100mb`.
+fn apply_cli_config() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    for pair in args.chunks(2) {
+        match pair {
+            [name, value] if name.starts_with("--") => pairs.push((&name[2..], value.as_str())),
+            _ => return Err(format!("Invalid arguments: expected --name value, got {pair:?}")),
+        }
+    }
+    CONFIG.set(&pairs).map_err(|err| err.to_string())
+}
+
 fn print_banner() {
     println!(
         r#"
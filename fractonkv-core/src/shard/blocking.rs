@@ -0,0 +1,202 @@
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+use crate::shard::clock::{self, UnixMillis};
+use crate::shard::shard::{Client, Shard};
+use crate::shard::types::{DataKind, DataStore};
+
+/// Orders blocked clients across every shard: lower numbers blocked first.
+static NEXT_WAIT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Allocates the FIFO position of a client that is about to block. The client keeps it for
+/// the whole blocking command, so being woken without getting an element does not cost it its
+/// place in line.
+pub fn next_wait_seq() -> u64 {
+    NEXT_WAIT_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How `CLIENT UNBLOCK` ends a blocking command.
+#[derive(Clone, Copy, Debug)]
+pub enum UnblockMode {
+    /// Reply as if the timeout had elapsed.
+    Timeout,
+    /// Reply with an `UNBLOCKED` error.
+    Error,
+}
+
+/// The type of value a blocked client pops.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockedOn {
+    List,
+    SortedSet,
+}
+
+impl BlockedOn {
+    fn accepts(self, data: &DataKind) -> bool {
+        matches!(
+            (self, data),
+            (BlockedOn::List, DataKind::List(_)) | (BlockedOn::SortedSet, DataKind::SortedSet(_))
+        )
+    }
+}
+
+/// Why a parked client resumed.
+pub enum WaitOutcome {
+    /// One of its keys may hold data now; it should retry.
+    Woken,
+    TimedOut,
+    Unblocked(UnblockMode),
+    /// The client closed its connection.
+    Disconnected,
+}
+
+/// A blocked client, as seen by the shard owning one of its keys.
+struct Waiter {
+    seq: u64,
+    kind: BlockedOn,
+    wake: UnboundedSender<()>,
+}
+
+/// Per-shard FIFO queues of clients blocked on keys owned by the shard.
+///
+/// Only keys reported by [`DataStore::take_ready`] are checked after a command, so waiters cost
+/// nothing until their key is written. Only one waiter per key is woken at a time. It stays
+/// recorded in `woken` until it reports back through [`Shard::release_keys`], which hands the
+/// key to the next waiter if data is still left; this keeps a single push from waking the
+/// whole queue.
+#[derive(Default)]
+pub struct WaitQueues {
+    queues: HashMap<Bytes, VecDeque<Waiter>>,
+    woken: HashMap<Bytes, u64>,
+}
+
+impl WaitQueues {
+    fn register(&mut self, key: Bytes, waiter: Waiter) {
+        let queue = self.queues.entry(key).or_default();
+        let at = queue.partition_point(|queued| queued.seq < waiter.seq);
+        queue.insert(at, waiter);
+    }
+
+    fn unregister(&mut self, key: &Bytes, seq: u64) {
+        if let Some(queue) = self.queues.get_mut(key) {
+            queue.retain(|waiter| waiter.seq != seq);
+            if queue.is_empty() {
+                self.queues.remove(key);
+            }
+        }
+        if self.woken.get(key) == Some(&seq) {
+            self.woken.remove(key);
+        }
+    }
+
+    /// Wakes, for each of `keys`, the oldest live waiter that pops the type of value the key
+    /// holds, unless a woken waiter is already on its way to that key.
+    fn wake_ready(&mut self, db: &DataStore, keys: Vec<Bytes>, now: UnixMillis) {
+        for key in keys {
+            if self.woken.contains_key(&key) {
+                continue;
+            }
+            let Some(queue) = self.queues.get_mut(&key) else {
+                continue;
+            };
+            let Some(obj) = db.get(&key).filter(|obj| !obj.is_expired(now)) else {
+                continue;
+            };
+            // Waiters whose client already gave up have a closed channel; drop them.
+            queue.retain(|waiter| !waiter.wake.is_closed());
+            let ready = queue.iter().position(|waiter| waiter.kind.accepts(&obj.data));
+            if let Some(waiter) = ready.and_then(|at| queue.remove(at))
+                && waiter.wake.send(()).is_ok()
+            {
+                self.woken.insert(key.clone(), waiter.seq);
+            }
+            if queue.is_empty() {
+                self.queues.remove(&key);
+            }
+        }
+    }
+}
+
+impl Shard {
+    /// Wakes clients blocked on the keys of this shard that were stored since the last call.
+    /// Runs after every command and every forwarded job, so pushes from any code path are
+    /// noticed.
+    pub fn wake_blocked(&self) {
+        let ready = self.db.borrow_mut().take_ready();
+        if !ready.is_empty() {
+            self.wake_keys(ready);
+        }
+    }
+
+    fn wake_keys(&self, keys: Vec<Bytes>) {
+        let mut blocked = self.blocked.borrow_mut();
+        if !blocked.queues.is_empty() {
+            blocked.wake_ready(&self.db.borrow(), keys, clock::now());
+        }
+    }
+
+    /// Parks `client` on `keys` until one of them may hold a value of type `kind`, `deadline`
+    /// passes, the client is unblocked or it disconnects. The client stays registered on its
+    /// keys afterwards and must call [`Shard::release_keys`] once it has retried.
+    pub async fn wait_for_keys(
+        &self,
+        client: &Client<'_>,
+        seq: u64,
+        keys: &[Bytes],
+        kind: BlockedOn,
+        deadline: Option<Instant>,
+    ) -> WaitOutcome {
+        let (wake, mut woken) = unbounded_channel();
+        for key in keys {
+            let (key, wake) = (key.clone(), wake.clone());
+            self.run_on_shard(self.owner(&key), move |shard| {
+                shard.blocked.borrow_mut().register(key.clone(), Waiter { seq, kind, wake });
+                // The key may have been filled between the failed attempt and this point.
+                shard.wake_keys(vec![key]);
+            })
+            .await;
+        }
+
+        let (unblock, mut unblocked) = unbounded_channel();
+        self.blocked_clients.borrow_mut().insert(client.id, unblock);
+        let timeout = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let outcome = tokio::select! {
+            Some(()) = woken.recv() => WaitOutcome::Woken,
+            Some(mode) = unblocked.recv() => WaitOutcome::Unblocked(mode),
+            _ = timeout => WaitOutcome::TimedOut,
+            _ = client.closed() => WaitOutcome::Disconnected,
+        };
+        self.blocked_clients.borrow_mut().remove(&client.id);
+        outcome
+    }
+
+    /// Removes the waiter `seq` from the queues of `keys`, passing any key it was woken for
+    /// on to the next waiter.
+    pub async fn release_keys(&self, seq: u64, keys: &[Bytes]) {
+        for key in keys {
+            let key = key.clone();
+            self.run_on_shard(self.owner(&key), move |shard| {
+                shard.blocked.borrow_mut().unregister(&key, seq);
+                shard.wake_keys(vec![key]);
+            })
+            .await;
+        }
+    }
+
+    /// Ends the blocking command of `client_id` if it is blocked on this shard.
+    pub fn unblock_client(&self, client_id: u64, mode: UnblockMode) -> bool {
+        match self.blocked_clients.borrow().get(&client_id) {
+            Some(unblock) => unblock.send(mode).is_ok(),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Wall-clock time as seen by the store.
+//!
+//! Every deadline and access time is kept as [`UnixMillis`] and read through the [`Clock`]
+//! installed on the current thread. Each shard owns a thread, so the clock governs all of that
+//! shard's keys; it defaults to [`SystemClock`], and tests install a `ManualClock` to run TTL
+//! logic deterministically without sleeping.
+
+#[cfg(test)]
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Milliseconds since the Unix epoch.
+pub type UnixMillis = u64;
+
+/// Source of the current time.
+pub trait Clock {
+    fn now(&self) -> UnixMillis;
+}
+
+/// The operating system's wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> UnixMillis {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as UnixMillis)
+    }
+}
+
+/// Clock that stands still until told to move.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Cell<UnixMillis>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(now: UnixMillis) -> Self {
+        Self { now: Cell::new(now) }
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.set(self.now.get() + ms);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> UnixMillis {
+        self.now.get()
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Rc<dyn Clock>> = RefCell::new(Rc::new(SystemClock));
+}
+
+/// Current time according to this thread's clock.
+pub fn now() -> UnixMillis {
+    CLOCK.with(|clock| clock.borrow().now())
+}
+
+/// Makes `clock` this thread's clock and returns the one it replaces.
+#[cfg(test)]
+pub fn install(clock: Rc<dyn Clock>) -> Rc<dyn Clock> {
+    CLOCK.with(|current| current.replace(clock))
+}
+
+/// Time at which [`manual_clock`] starts.
+#[cfg(test)]
+pub const TEST_START: UnixMillis = 1_700_000_000_000;
+
+/// Installs a [`ManualClock`] standing at [`TEST_START`] on this thread and returns it.
+#[cfg(test)]
+pub fn manual_clock() -> Rc<ManualClock> {
+    let clock = Rc::new(ManualClock::new(TEST_START));
+    install(clock.clone());
+    clock
+}
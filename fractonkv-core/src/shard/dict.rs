@@ -0,0 +1,566 @@
+use rand::Rng;
+use std::alloc::{self, Layout};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::time::{Duration, Instant};
+
+/// Buckets allocated by the first insert; the table never shrinks below this.
+const INITIAL_BUCKETS: usize = 4;
+
+/// The table shrinks once fewer than one bucket in this many holds an entry on average.
+const SHRINK_RATIO: usize = 8;
+
+/// Empty buckets a single rehash step may skip over per bucket it is asked to migrate, so that
+/// a sparse old table cannot make one step arbitrarily long.
+const EMPTY_VISITS_PER_STEP: usize = 10;
+
+/// Buckets migrated between two clock reads by [`Dict::rehash_for`].
+const BUCKETS_PER_TIMED_STEP: usize = 100;
+
+/// Head of a bucket's chain of entries.
+type Bucket<K, V> = Option<Box<Node<K, V>>>;
+
+#[derive(Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    next: Bucket<K, V>,
+}
+
+/// Chained hash table with a power-of-two bucket count, in the manner of Redis' `dict`.
+///
+/// Unlike `HashMap` it supports [`Dict::scan`], a cursor-based walk that can be interrupted
+/// and resumed while the table is modified, resizes included. Buckets are visited in
+/// reverse-binary order of their index, so the buckets still due after a resize are exactly
+/// the ones covering the same hash ranges as before, and an entry present for the whole walk
+/// is always reported (possibly more than once).
+///
+/// Resizing is incremental, so that no single operation pays for moving the whole table: a
+/// new bucket array is allocated and the entries of the old one are moved over a bucket at a
+/// time, by every later insert, removal and mutable lookup and by [`Dict::rehash_for`] when
+/// the owner is idle. While both arrays are live, new entries go to the new one and lookups
+/// check both. Bucket arrays are plain pointer arrays, so allocating and freeing them does not
+/// touch every bucket either.
+pub struct Dict<K, V> {
+    buckets: Vec<Bucket<K, V>>,
+    /// Bucket array being drained into `buckets`; empty unless a resize is in progress.
+    old: Vec<Bucket<K, V>>,
+    /// Buckets of `old` below this index have already been moved.
+    migrated: usize,
+    len: usize,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V> {
+    fn default() -> Self {
+        Self {
+            buckets: Vec::new(),
+            old: Vec::new(),
+            migrated: 0,
+            len: 0,
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Dict<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            buckets: self.buckets.clone(),
+            old: self.old.clone(),
+            migrated: self.migrated,
+            len: self.len,
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Dict<K, V> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        [&self.old, &self.buckets]
+            .into_iter()
+            .filter_map(|table| table.get(index_of(hash, table.len())))
+            .flat_map(chain)
+            .find(|node| node.key.borrow() == key)
+            .map(|node| &node.value)
+    }
+
+    /// Mutable access to the value of `key`. Like every change to the table, it also moves
+    /// one bucket along if a resize is in progress.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.rehash(1);
+        let hash = self.hasher.hash_one(key);
+        for table in [&mut self.old, &mut self.buckets] {
+            let index = index_of(hash, table.len());
+            let mut node = table.get_mut(index).and_then(|bucket| bucket.as_deref_mut());
+            while let Some(current) = node {
+                if current.key.borrow() == key {
+                    return Some(&mut current.value);
+                }
+                node = current.next.as_deref_mut();
+            }
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts or replaces the value of `key`, returning the previous value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(slot) = self.get_mut(&key) {
+            return Some(std::mem::replace(slot, value));
+        }
+        if self.len >= self.buckets.len() && !self.is_rehashing() {
+            self.start_resize((self.buckets.len() * 2).max(INITIAL_BUCKETS));
+        }
+        let index = index_of(self.hasher.hash_one(&key), self.buckets.len());
+        let bucket = &mut self.buckets[index];
+        *bucket = Some(Box::new(Node { key, value, next: bucket.take() }));
+        self.len += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.rehash(1);
+        let hash = self.hasher.hash_one(key);
+        let node = [&mut self.old, &mut self.buckets].into_iter().find_map(|table| {
+            let index = index_of(hash, table.len());
+            let mut link = table.get_mut(index)?;
+            while link.as_ref().is_some_and(|node| node.key.borrow() != key) {
+                link = &mut link.as_mut().expect("checked above").next;
+            }
+            let mut node = link.take()?;
+            *link = node.next.take();
+            Some(node)
+        })?;
+        self.len -= 1;
+        if !self.is_rehashing()
+            && self.buckets.len() > INITIAL_BUCKETS
+            && self.len * SHRINK_RATIO < self.buckets.len()
+        {
+            self.start_resize(self.len.next_power_of_two().max(INITIAL_BUCKETS));
+        }
+        Some(node.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets = Vec::new();
+        self.old = Vec::new();
+        self.migrated = 0;
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.pending()
+            .iter()
+            .chain(&self.buckets)
+            .flat_map(chain)
+            .map(|node| (&node.key, &node.value))
+    }
+
+    /// Picks an entry at random: a random non-empty bucket, then a random entry in it. Entries
+    /// sharing a bucket are slightly less likely to be picked, which is fine for sampling.
+    pub fn random_entry(&self) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut rng = rand::rng();
+        let pending = self.pending();
+        loop {
+            let index = rng.random_range(0..pending.len() + self.buckets.len());
+            let bucket = match index.checked_sub(pending.len()) {
+                Some(index) => &self.buckets[index],
+                None => &pending[index],
+            };
+            let len = chain(bucket).count();
+            if len > 0 {
+                let node = chain(bucket).nth(rng.random_range(0..len))?;
+                return Some((&node.key, &node.value));
+            }
+        }
+    }
+
+    /// Buckets of the old array still to be moved; those before them are known to be empty.
+    fn pending(&self) -> &[Bucket<K, V>] {
+        &self.old[self.migrated.min(self.old.len())..]
+    }
+
+    /// Bytes taken by the table itself: both bucket arrays during a resize and one node per
+    /// entry, but not whatever the keys and values own on the heap.
+    pub fn table_size(&self) -> usize {
+        (self.buckets.capacity() + self.old.capacity()) * size_of::<Bucket<K, V>>()
+            + self.len * size_of::<Node<K, V>>()
+    }
+
+    /// Reports every entry of the bucket at `cursor` to `visit` and returns the cursor of the
+    /// next bucket, or 0 once the walk is complete. Start with cursor 0.
+    ///
+    /// During a resize the bucket of the smaller array is visited together with every bucket
+    /// of the larger one that its hash range maps to, as in Redis' `dictScan`, so the
+    /// guarantee holds whichever array an entry is in.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&K, &V)) -> u64 {
+        if self.buckets.is_empty() {
+            return 0;
+        }
+        let mut visit_bucket = |bucket: &Bucket<K, V>| {
+            for node in chain(bucket) {
+                visit(&node.key, &node.value);
+            }
+        };
+        if !self.is_rehashing() {
+            let mask = (self.buckets.len() - 1) as u64;
+            visit_bucket(&self.buckets[(cursor & mask) as usize]);
+            return next_cursor(cursor, mask);
+        }
+
+        let (small, large) = if self.old.len() < self.buckets.len() {
+            (&self.old, &self.buckets)
+        } else {
+            (&self.buckets, &self.old)
+        };
+        let (small_mask, large_mask) = ((small.len() - 1) as u64, (large.len() - 1) as u64);
+        visit_bucket(&small[(cursor & small_mask) as usize]);
+        let mut cursor = cursor;
+        loop {
+            visit_bucket(&large[(cursor & large_mask) as usize]);
+            cursor = next_cursor(cursor, large_mask);
+            // Stop once the bits the larger mask adds have wrapped around.
+            if cursor & (small_mask ^ large_mask) == 0 {
+                return cursor;
+            }
+        }
+    }
+
+    /// Moves up to `steps` non-empty buckets of an ongoing resize. Returns whether the resize
+    /// is still in progress.
+    pub fn rehash(&mut self, steps: usize) -> bool {
+        let mut empty_visits = steps.saturating_mul(EMPTY_VISITS_PER_STEP);
+        let mut steps = steps;
+        while steps > 0 && self.migrated < self.old.len() {
+            let mut node = self.old[self.migrated].take();
+            self.migrated += 1;
+            if node.is_none() {
+                empty_visits -= 1;
+                if empty_visits == 0 {
+                    break;
+                }
+                continue;
+            }
+            while let Some(mut current) = node {
+                node = current.next.take();
+                let index = index_of(self.hasher.hash_one(&current.key), self.buckets.len());
+                let bucket = &mut self.buckets[index];
+                current.next = bucket.take();
+                *bucket = Some(current);
+            }
+            steps -= 1;
+        }
+        if self.is_rehashing() && self.migrated == self.old.len() {
+            // SAFETY: every bucket of `old` has been taken, so all of them are `None` and there
+            // is nothing to drop; freeing the array then costs nothing per bucket.
+            unsafe { self.old.set_len(0) };
+            self.old = Vec::new();
+            self.migrated = 0;
+        }
+        self.is_rehashing()
+    }
+
+    /// Moves buckets of an ongoing resize for about `budget`, for owners with time to spare.
+    /// Returns whether the resize is still in progress.
+    pub fn rehash_for(&mut self, budget: Duration) -> bool {
+        let started = Instant::now();
+        while self.rehash(BUCKETS_PER_TIMED_STEP) {
+            if started.elapsed() >= budget {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether entries are still being moved to a resized bucket array.
+    fn is_rehashing(&self) -> bool {
+        !self.old.is_empty()
+    }
+
+    /// Allocates a bucket array of `buckets` and starts moving entries into it. A resize still
+    /// in progress is finished first.
+    fn start_resize(&mut self, buckets: usize) {
+        while self.rehash(usize::MAX) {}
+        self.old = std::mem::replace(&mut self.buckets, empty_buckets(buckets));
+        self.migrated = 0;
+        // A table too small to bother spreading out is moved at once.
+        if self.old.len() <= INITIAL_BUCKETS {
+            while self.rehash(usize::MAX) {}
+        }
+    }
+}
+
+/// Array of `len` empty buckets, taken from zeroed memory so that the OS can hand out the pages
+/// as they are first used instead of every bucket being written now.
+fn empty_buckets<K, V>(len: usize) -> Vec<Bucket<K, V>> {
+    let layout = Layout::array::<Bucket<K, V>>(len).expect("bucket array too large");
+    if layout.size() == 0 {
+        return Vec::new();
+    }
+    // SAFETY: the layout is that of `len` buckets and is not zero-sized. `None` of an
+    // `Option<Box<_>>` is guaranteed to be the null pointer, so zeroed memory holds `len`
+    // valid empty buckets, and the allocation is handed to a `Vec` with that same layout.
+    unsafe {
+        let ptr = alloc::alloc_zeroed(layout).cast::<Bucket<K, V>>();
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        Vec::from_raw_parts(ptr, len, len)
+    }
+}
+
+/// Entries of a bucket, from the most recently added.
+fn chain<K, V>(bucket: &Bucket<K, V>) -> impl Iterator<Item = &Node<K, V>> {
+    std::iter::successors(bucket.as_deref(), |node| node.next.as_deref())
+}
+
+/// Bucket of `hash` in an array of `buckets`, a power of two.
+fn index_of(hash: u64, buckets: usize) -> usize {
+    (hash as usize) & buckets.wrapping_sub(1)
+}
+
+/// Cursor of the bucket after `cursor` in a walk over `mask + 1` buckets.
+///
+/// Increments the masked bits counting from the most significant one, so that cursors stay
+/// meaningful when the mask grows or shrinks.
+fn next_cursor(cursor: u64, mask: u64) -> u64 {
+    (cursor | !mask).reverse_bits().wrapping_add(1).reverse_bits()
+}
+
+/// Owning iterator over the entries of a [`Dict`].
+pub struct IntoIter<K, V> {
+    old: std::vec::IntoIter<Bucket<K, V>>,
+    buckets: std::vec::IntoIter<Bucket<K, V>>,
+    current: Bucket<K, V>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mut node) = self.current.take() {
+                self.current = node.next.take();
+                return Some((node.key, node.value));
+            }
+            self.current = self.old.next().or_else(|| self.buckets.next())?;
+        }
+    }
+}
+
+impl<K, V> IntoIterator for Dict<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            old: self.old.into_iter(),
+            buckets: self.buckets.into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Dict<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut dict = Dict::default();
+        for (key, value) in iter {
+            dict.insert(key, value);
+        }
+        dict
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Dict<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.old.iter().chain(&self.buckets).flat_map(chain).map(|n| (&n.key, &n.value)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{Dict, INITIAL_BUCKETS};
+
+    /// A table of `len` keys `0..len` that started growing on the last insert, `len` being one
+    /// past a power of two large enough for the resize to be spread out.
+    fn growing(len: u64) -> Dict<u64, u64> {
+        let dict: Dict<u64, u64> = (0..len).map(|key| (key, key * 10)).collect();
+        assert!(dict.is_rehashing() && dict.old.len() > INITIAL_BUCKETS);
+        dict
+    }
+
+    /// Every key `scan` reports on a full walk, which may include some more than once.
+    fn scan_all(dict: &Dict<u64, u64>) -> Vec<u64> {
+        let mut keys = Vec::new();
+        let mut cursor = 0;
+        loop {
+            cursor = dict.scan(cursor, |&key, _| keys.push(key));
+            if cursor == 0 {
+                return keys;
+            }
+        }
+    }
+
+    #[test]
+    fn lookups_and_changes_see_both_tables_during_a_resize() {
+        let mut dict = growing(65);
+        let mut model: HashMap<u64, u64> = (0..65).map(|key| (key, key * 10)).collect();
+        let mut changes_while_resizing = 0;
+        for step in 0..200u64 {
+            changes_while_resizing += dict.is_rehashing() as usize;
+            let key = step % 80;
+            match step % 4 {
+                0 => assert_eq!(dict.insert(1000 + step, step), model.insert(1000 + step, step)),
+                1 => assert_eq!(dict.insert(key, step), model.insert(key, step)),
+                2 => assert_eq!(dict.remove(&key), model.remove(&key)),
+                _ => {
+                    let bump = |value: &mut u64| {
+                        *value += 1;
+                        *value
+                    };
+                    assert_eq!(dict.get_mut(&key).map(bump), model.get_mut(&key).map(bump));
+                }
+            }
+            assert_eq!(dict.len(), model.len());
+            for (key, value) in &model {
+                assert_eq!(dict.get(key), Some(value), "step {step}, key {key}");
+            }
+            assert!(!dict.contains_key(&999));
+        }
+        assert!(changes_while_resizing > 10, "the resize ended after {changes_while_resizing}");
+    }
+
+    #[test]
+    fn iteration_mid_resize_yields_each_entry_once() {
+        let mut dict = growing(129);
+        for _ in 0..3 {
+            let mut keys: Vec<u64> = dict.iter().map(|(&key, _)| key).collect();
+            keys.sort_unstable();
+            assert_eq!(keys, (0..129).collect::<Vec<_>>());
+            assert!(dict.iter().all(|(key, value)| *value == key * 10));
+            dict.rehash(10);
+        }
+        assert!(dict.is_rehashing(), "the resize should still be in progress");
+
+        let mut owned: Vec<(u64, u64)> = dict.into_iter().collect();
+        owned.sort_unstable();
+        assert_eq!(owned, (0..129).map(|key| (key, key * 10)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scan_reports_entries_present_throughout_a_grow_and_a_shrink() {
+        let original: HashSet<u64> = (0..200).collect();
+        let mut dict: Dict<u64, u64> = original.iter().map(|&key| (key, 0)).collect();
+        let mut seen = HashSet::new();
+        let mut resizing_steps = 0;
+        let mut cursor = 0;
+        for step in 0.. {
+            resizing_steps += dict.is_rehashing() as usize;
+            cursor = dict.scan(cursor, |&key, _| {
+                seen.insert(key);
+            });
+            if cursor == 0 {
+                break;
+            }
+            match step {
+                // Grow well past the size the walk started at, leaving the last resize going.
+                10 => {
+                    for key in 1000..3000 {
+                        dict.insert(key, 0);
+                    }
+                }
+                // Shrink back down below it.
+                100 => {
+                    for key in 1000..3000 {
+                        dict.remove(&key);
+                    }
+                }
+                _ => {}
+            }
+            dict.rehash(1);
+        }
+        assert!(resizing_steps > 0, "the walk never saw a resize in progress");
+        assert!(original.is_subset(&seen), "missed {:?}", original.difference(&seen));
+    }
+
+    #[test]
+    fn a_walk_run_entirely_mid_resize_reports_every_entry() {
+        let grown = growing(257);
+        let keys: HashSet<u64> = scan_all(&grown).into_iter().collect();
+        assert_eq!(keys, (0..257).collect());
+
+        // Removals that leave the table sparse start a shrink.
+        let mut shrinking: Dict<u64, u64> = (0..1024).map(|key| (key, 0)).collect();
+        while shrinking.rehash(usize::MAX) {}
+        for key in (0..1024).filter(|key| key % 16 != 0) {
+            shrinking.remove(&key);
+        }
+        assert!(shrinking.rehash(1), "the shrink should still be in progress");
+        let keys: HashSet<u64> = scan_all(&shrinking).into_iter().collect();
+        assert_eq!(keys, (0..1024).step_by(16).collect());
+    }
+
+    #[test]
+    fn random_entries_come_from_the_table_however_sparse() {
+        let mut dict: Dict<u64, u64> = Dict::default();
+        assert_eq!(dict.random_entry(), None);
+        dict.insert(1, 1);
+        dict.remove(&1);
+        assert_eq!(dict.random_entry(), None);
+
+        // Two keys left among many empty buckets, some of them mid-shrink.
+        let mut dict: Dict<u64, u64> = (0..1000).map(|key| (key, key)).collect();
+        for key in 2..1000 {
+            dict.remove(&key);
+        }
+        let mut seen = HashSet::new();
+        for _ in 0..200 {
+            let (&key, &value) = dict.random_entry().expect("the table is not empty");
+            assert_eq!(key, value);
+            seen.insert(key);
+        }
+        assert_eq!(seen, HashSet::from([0, 1]));
+
+        let grown = growing(65);
+        assert!((0..100).all(|_| grown.random_entry().is_some_and(|(key, _)| *key < 65)));
+    }
+}
@@ -0,0 +1,372 @@
+use bytes::Bytes;
+use rand::Rng;
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+
+use crate::config::CONFIG;
+use crate::shard::clock::{self, UnixMillis};
+use crate::shard::shard::Shard;
+use crate::shard::types::DataStore;
+
+/// Counter value of a new key, so that it is not the first candidate for LFU eviction.
+const LFU_INIT_VAL: u8 = 5;
+
+const MS_PER_MINUTE: u64 = 60 * 1000;
+
+/// How many keys a volatile policy may look at per requested sample before giving up. Its
+/// samples come from the index of volatile keys, which also holds keys whose only TTLs are on
+/// hash fields; those are not candidates, and may be all the index has.
+const VOLATILE_TRIES_PER_SAMPLE: usize = 10;
+
+/// Which keys are evicted once a shard reaches its share of `maxmemory`, as in Redis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict nothing; commands that may grow memory fail instead.
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    /// Among keys with a TTL, the one expiring soonest.
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    /// Every policy, indexed by discriminant.
+    pub const ALL: [EvictionPolicy; 8] = [
+        Self::NoEviction,
+        Self::AllKeysLru,
+        Self::AllKeysLfu,
+        Self::AllKeysRandom,
+        Self::VolatileLru,
+        Self::VolatileLfu,
+        Self::VolatileRandom,
+        Self::VolatileTtl,
+    ];
+
+    /// Name used by `maxmemory-policy`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+            Self::AllKeysLfu => "allkeys-lfu",
+            Self::AllKeysRandom => "allkeys-random",
+            Self::VolatileLru => "volatile-lru",
+            Self::VolatileLfu => "volatile-lfu",
+            Self::VolatileRandom => "volatile-random",
+            Self::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|policy| policy.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether only keys with a TTL may be evicted.
+    fn volatile_only(self) -> bool {
+        matches!(
+            self,
+            Self::VolatileLru | Self::VolatileLfu | Self::VolatileRandom | Self::VolatileTtl
+        )
+    }
+}
+
+/// Logarithmic access counter for LFU eviction, in the manner of Redis' 8-bit `lfu` field.
+///
+/// The counter grows with probability `1 / ((count - LFU_INIT_VAL) * lfu-log-factor + 1)` per
+/// access, so it takes about a million hits to saturate at the default factor, and it drops by
+/// one for every `lfu-decay-time` minutes the key goes without access.
+#[derive(Clone, Copy, Debug)]
+pub struct LfuCounter {
+    count: u8,
+    /// Minute, since the Unix epoch, up to which decay has been applied.
+    decayed_at: u64,
+}
+
+impl LfuCounter {
+    pub fn new(now: UnixMillis) -> Self {
+        Self {
+            count: LFU_INIT_VAL,
+            decayed_at: now / MS_PER_MINUTE,
+        }
+    }
+
+    /// The counter with decay applied up to `now`.
+    pub fn value(&self, now: UnixMillis) -> u8 {
+        let decay_time = CONFIG.lfu_decay_time();
+        if decay_time == 0 {
+            return self.count;
+        }
+        let periods = (now / MS_PER_MINUTE).saturating_sub(self.decayed_at) / decay_time;
+        self.count.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// Records an access at `now`.
+    pub fn hit(&mut self, now: UnixMillis) {
+        let mut count = self.value(now);
+        if count < u8::MAX {
+            let base = count.saturating_sub(LFU_INIT_VAL) as f64;
+            let probability = 1.0 / (base * CONFIG.lfu_log_factor() as f64 + 1.0);
+            if rand::rng().random_bool(probability) {
+                count += 1;
+            }
+        }
+        self.count = count;
+        self.decayed_at = now / MS_PER_MINUTE;
+    }
+}
+
+/// Evicts keys chosen by the configured policy until `db` uses at most `limit` bytes. Returns
+/// `false` if it is still over, because the policy is `noeviction` or no key qualifies.
+///
+/// Victims are approximations: each is the best of `maxmemory-samples` random keys rather than
+/// of the whole keyspace. A sampled key already past its TTL is always taken first.
+pub fn evict_to(db: &mut DataStore, limit: usize, now: UnixMillis) -> bool {
+    let policy = CONFIG.maxmemory_policy();
+    let samples = CONFIG.maxmemory_samples().max(1);
+    while db.used_memory() > limit {
+        if policy == EvictionPolicy::NoEviction {
+            return false;
+        }
+        let Some(victim) = pick_victim(db, policy, samples, now) else {
+            return false;
+        };
+        db.delete(&victim, CONFIG.lazyfree_lazy_eviction());
+    }
+    true
+}
+
+/// Samples keys and returns the one `policy` would evict first. Volatile policies sample the
+/// index of keys with a TTL, so keys without one never crowd out the candidates.
+fn pick_victim(
+    db: &DataStore,
+    policy: EvictionPolicy,
+    samples: usize,
+    now: UnixMillis,
+) -> Option<Bytes> {
+    let tries = if policy.volatile_only() {
+        samples * VOLATILE_TRIES_PER_SAMPLE
+    } else {
+        samples
+    };
+    let mut best: Option<(&Bytes, u64)> = None;
+    let mut sampled = 0;
+    for _ in 0..tries {
+        let (key, obj) = if policy.volatile_only() {
+            db.sample_volatile()
+        } else {
+            db.sample()
+        }?;
+        if obj.is_expired(now) {
+            return Some(key.clone());
+        }
+        let deadline = obj.deadline();
+        if policy.volatile_only() && deadline.is_none() {
+            continue;
+        }
+
+        // Higher scores are evicted first.
+        let score = match policy {
+            EvictionPolicy::AllKeysRandom | EvictionPolicy::VolatileRandom => {
+                return Some(key.clone());
+            }
+            EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => {
+                now.saturating_sub(obj.last_accessed)
+            }
+            EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu => {
+                (u8::MAX - obj.lfu.value(now)) as u64
+            }
+            EvictionPolicy::VolatileTtl => u64::MAX - deadline.unwrap_or(u64::MAX),
+            EvictionPolicy::NoEviction => return None,
+        };
+        if best.is_none_or(|(_, best)| score > best) {
+            best = Some((key, score));
+        }
+        sampled += 1;
+        if sampled == samples {
+            break;
+        }
+    }
+    best.map(|(key, _)| key.clone())
+}
+
+impl Shard {
+    /// This shard's share of `maxmemory` in bytes, or `None` when memory is unlimited.
+    pub fn memory_limit(&self) -> Option<usize> {
+        match CONFIG.maxmemory() {
+            0 => None,
+            maxmemory => Some((maxmemory / self.peers.len() as u64) as usize),
+        }
+    }
+
+    /// Evicts keys on every shard in `owners` that is over its share of `maxmemory`, ahead of a
+    /// command that may use more memory there. Returns `false` if one of them could not get
+    /// back under its limit, in which case the command must be refused.
+    ///
+    /// Other shards are only sent an eviction job when the memory they last published is over
+    /// the limit, so writes to shards with room to spare cost no extra round trip.
+    pub async fn reclaim_memory(&self, owners: &BTreeSet<usize>) -> bool {
+        let Some(limit) = self.memory_limit() else {
+            return true;
+        };
+        for &owner in owners {
+            let used = if owner == self.id {
+                self.db.borrow().used_memory()
+            } else {
+                self.used_memory[owner].load(Ordering::Relaxed)
+            };
+            if used <= limit {
+                continue;
+            }
+            if !self.run_on(owner, move |db| evict_to(db, limit, clock::now())).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Settles the store after a command or job, see [`DataStore::settle`], and publishes its
+    /// memory use for [`Shard::reclaim_memory`] on the other shards.
+    pub fn settle(&self) {
+        let mut db = self.db.borrow_mut();
+        db.settle();
+        self.used_memory[self.id].store(db.used_memory(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{EvictionPolicy, LFU_INIT_VAL, LfuCounter, MS_PER_MINUTE, pick_victim};
+    use crate::shard::{
+        clock::TEST_START as START,
+        types::{DataKind, DataStore, StoreObject},
+    };
+
+    /// Enough samples that a store of a handful of keys is all but certainly seen in full.
+    const SAMPLES: usize = 200;
+
+    const NOW: u64 = START + 10 * MS_PER_MINUTE;
+
+    /// Counter last decayed at `NOW`, with `count` hits' worth of frequency.
+    fn lfu(count: u8) -> LfuCounter {
+        LfuCounter { count, decayed_at: NOW / MS_PER_MINUTE }
+    }
+
+    fn put(db: &mut DataStore, key: &str, setup: impl FnOnce(&mut StoreObject)) {
+        let mut obj = StoreObject::new(DataKind::from_string("v".into()), NOW);
+        setup(&mut obj);
+        db.insert(Bytes::from(key.to_string()), obj);
+    }
+
+    fn victim(db: &DataStore, policy: EvictionPolicy) -> Option<Bytes> {
+        pick_victim(db, policy, SAMPLES, NOW)
+    }
+
+    #[test]
+    fn lfu_counter_decays_by_one_per_idle_period() {
+        let minute = |n: u64| (NOW / MS_PER_MINUTE + n) * MS_PER_MINUTE;
+        let counter = lfu(20);
+        assert_eq!(LfuCounter::new(NOW).value(NOW), LFU_INIT_VAL);
+        assert_eq!(counter.value(minute(1) - 1), 20);
+        assert_eq!(counter.value(minute(3)), 17);
+        assert_eq!(counter.value(minute(1000)), 0);
+
+        // A hit applies the decay so far and restarts the idle period.
+        let mut counter = counter;
+        counter.hit(minute(3));
+        assert!((17..=18).contains(&counter.value(minute(3))));
+        assert!((16..=17).contains(&counter.value(minute(4))));
+    }
+
+    #[test]
+    fn lfu_counter_grows_ever_more_slowly_up_to_its_cap() {
+        let mut counter = LfuCounter::new(NOW);
+        counter.hit(NOW);
+        assert_eq!(counter.value(NOW), LFU_INIT_VAL + 1, "the first hits always count");
+
+        let mut previous = counter.value(NOW);
+        for _ in 0..1000 {
+            counter.hit(NOW);
+            assert!(counter.value(NOW) >= previous);
+            previous = counter.value(NOW);
+        }
+        // At lfu-log-factor 10, a thousand hits only add a handful.
+        assert!((LFU_INIT_VAL + 2..LFU_INIT_VAL + 30).contains(&previous), "got {previous}");
+
+        let mut saturated = lfu(u8::MAX);
+        saturated.hit(NOW);
+        assert_eq!(saturated.value(NOW), u8::MAX);
+    }
+
+    #[test]
+    fn allkeys_policies_pick_the_least_recent_or_least_frequent_key() {
+        let mut db = DataStore::default();
+        put(&mut db, "old", |obj| obj.last_accessed = START);
+        put(&mut db, "recent", |_| {});
+        put(&mut db, "rare", |obj| {
+            obj.last_accessed = NOW - 1;
+            obj.lfu = lfu(1);
+        });
+        assert_eq!(victim(&db, EvictionPolicy::AllKeysLru), Some("old".into()));
+        assert_eq!(victim(&db, EvictionPolicy::AllKeysLfu), Some("rare".into()));
+        // A counter left alone since START has decayed below the others.
+        put(&mut db, "old", |obj| obj.lfu = LfuCounter::new(START));
+        assert_eq!(victim(&db, EvictionPolicy::AllKeysLfu), Some("old".into()));
+        assert!(victim(&db, EvictionPolicy::AllKeysRandom).is_some());
+    }
+
+    #[test]
+    fn volatile_policies_only_consider_keys_with_a_ttl() {
+        let mut db = DataStore::default();
+        put(&mut db, "persistent", |obj| {
+            obj.last_accessed = START;
+            obj.lfu = lfu(0);
+        });
+        put(&mut db, "later", |obj| obj.set_expires_at(Some(NOW + 2000)));
+        put(&mut db, "sooner", |obj| {
+            obj.set_expires_at(Some(NOW + 1000));
+            obj.last_accessed = NOW - 1;
+            obj.lfu = lfu(1);
+        });
+
+        for policy in [EvictionPolicy::VolatileLru, EvictionPolicy::VolatileLfu] {
+            assert_eq!(victim(&db, policy), Some("sooner".into()), "{}", policy.name());
+        }
+        assert_eq!(victim(&db, EvictionPolicy::VolatileTtl), Some("sooner".into()));
+        for _ in 0..50 {
+            assert_ne!(victim(&db, EvictionPolicy::VolatileRandom), Some("persistent".into()));
+        }
+
+        db.remove(&"later".into());
+        db.remove(&"sooner".into());
+        for policy in [EvictionPolicy::VolatileLru, EvictionPolicy::VolatileTtl] {
+            assert_eq!(victim(&db, policy), None, "{}", policy.name());
+        }
+    }
+
+    #[test]
+    fn volatile_policies_find_a_lone_ttl_key_among_many_persistent_ones() {
+        let mut db = DataStore::default();
+        for i in 0..10_000 {
+            put(&mut db, &format!("persistent:{i}"), |_| {});
+        }
+        put(&mut db, "expiring", |obj| obj.set_expires_at(Some(NOW + 1000)));
+        assert_eq!(
+            pick_victim(&db, EvictionPolicy::VolatileRandom, 1, NOW),
+            Some("expiring".into())
+        );
+    }
+
+    #[test]
+    fn a_sampled_key_past_its_deadline_is_taken_first() {
+        let mut db = DataStore::default();
+        put(&mut db, "idle", |obj| obj.last_accessed = START);
+        put(&mut db, "expired", |obj| obj.set_expires_at(Some(NOW - 1)));
+        assert_eq!(victim(&db, EvictionPolicy::AllKeysLru), Some("expired".into()));
+        assert_eq!(victim(&db, EvictionPolicy::VolatileLfu), Some("expired".into()));
+    }
+}
@@ -0,0 +1,167 @@
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+use crate::config::CONFIG;
+use crate::shard::clock::UnixMillis;
+use crate::shard::types::{DataKind, DataStore, StoreObject};
+
+/// How often each shard runs an [`ActiveExpire`] cycle, matching Redis' default `hz 10`.
+pub const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest a cycle may keep the shard busy: a quarter of the interval, as in Redis. This
+/// bounds work actually done, so it is measured on the monotonic clock rather than the
+/// installed [`Clock`](crate::shard::clock::Clock).
+const CYCLE_BUDGET: Duration = Duration::from_millis(25);
+
+/// Keys examined by one step of a cycle before the budget is checked again.
+const KEYS_PER_STEP: usize = 64;
+
+/// Keys a cycle examines at least, unless it completes a full pass first.
+const KEYS_PER_CYCLE: usize = 256;
+
+/// Once its base quota is done, a cycle keeps going only while more than this percentage of
+/// the keys in the last step turned out to be expired.
+const ACCEPTABLE_STALE_PERCENT: usize = 10;
+
+/// Incremental reclaimer of keys and hash fields past their TTL that no client reads.
+///
+/// Each cycle resumes a [`Dict::scan`](crate::shard::dict::Dict::scan) walk over the shard's
+/// volatile keys where the previous one stopped, as Redis walks `db->expires`, so keys without
+/// a TTL cost nothing and every key with one is visited over successive cycles without any of
+/// them taking longer than [`CYCLE_BUDGET`]. A cycle that keeps finding expired keys keeps going,
+/// which lets the shard catch up when many keys expire at once.
+#[derive(Default)]
+pub struct ActiveExpire {
+    cursor: u64,
+}
+
+/// What one step of a cycle saw.
+struct Step {
+    examined: usize,
+    expired: usize,
+}
+
+impl ActiveExpire {
+    /// Runs one cycle, treating keys whose deadline is at or before `now` as expired.
+    pub fn run_cycle(&mut self, db: &mut DataStore, now: UnixMillis) {
+        let started = Instant::now();
+        let mut examined = 0;
+        loop {
+            let step = self.step(db, now);
+            examined += step.examined;
+            if self.cursor == 0 || started.elapsed() >= CYCLE_BUDGET {
+                return;
+            }
+            if examined >= KEYS_PER_CYCLE
+                && step.expired * 100 <= step.examined * ACCEPTABLE_STALE_PERCENT
+            {
+                return;
+            }
+        }
+    }
+
+    /// Walks volatile keys until [`KEYS_PER_STEP`] are examined or the pass completes, then
+    /// reclaims what was found. Hashes count as expired when some of their fields are
+    /// reclaimed; hashes left without fields are deleted.
+    fn step(&mut self, db: &mut DataStore, now: UnixMillis) -> Step {
+        let mut step = Step { examined: 0, expired: 0 };
+        let mut expired: Vec<Bytes> = Vec::new();
+        let mut hashes: Vec<Bytes> = Vec::new();
+        loop {
+            self.cursor = db.scan_volatile(self.cursor, |key, obj| {
+                step.examined += 1;
+                if obj.is_expired(now) {
+                    expired.push(key.clone());
+                } else if let DataKind::Hash(hash) = &obj.data
+                    && hash.volatile_len() > 0
+                {
+                    hashes.push(key.clone());
+                }
+            });
+            if self.cursor == 0 || step.examined >= KEYS_PER_STEP {
+                break;
+            }
+        }
+
+        step.expired += expired.len();
+        for key in expired {
+            db.delete(&key, CONFIG.lazyfree_lazy_expire());
+        }
+        for key in hashes {
+            let Some(StoreObject { data: DataKind::Hash(hash), .. }) = db.get_mut(&key) else {
+                continue;
+            };
+            if hash.expire_fields(now) > 0 {
+                step.expired += 1;
+                if hash.is_empty() {
+                    db.remove(&key);
+                }
+            }
+        }
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::ActiveExpire;
+    use crate::shard::{
+        clock::{self, TEST_START as START, UnixMillis, manual_clock},
+        types::{DataKind, DataStore, HashValue, StoreObject, lookup_mut},
+    };
+
+    fn insert(db: &mut DataStore, key: &str, deadline: Option<UnixMillis>) {
+        let mut obj = StoreObject::new(DataKind::from_string("v".into()), clock::now());
+        obj.set_expires_at(deadline);
+        db.insert(Bytes::from(key.to_string()), obj);
+    }
+
+    #[test]
+    fn lookup_drops_a_key_once_its_deadline_passes() {
+        let clock = manual_clock();
+        let mut db = DataStore::default();
+        insert(&mut db, "k", Some(START + 10));
+        let key = Bytes::from("k");
+
+        clock.advance(9);
+        assert!(lookup_mut(&mut db, &key, clock::now()).is_some());
+
+        clock.advance(1);
+        assert!(lookup_mut(&mut db, &key, clock::now()).is_none());
+        assert_eq!(db.len(), 0);
+    }
+
+    #[test]
+    fn cycle_reclaims_expired_keys_and_hash_fields_nobody_reads() {
+        let clock = manual_clock();
+        let mut db = DataStore::default();
+        for i in 0..1_000 {
+            insert(&mut db, &format!("persistent:{i}"), None);
+        }
+        for i in 0..100 {
+            insert(&mut db, &format!("volatile:{i}"), Some(START + 100));
+        }
+        let mut hash = HashValue::default();
+        hash.insert("kept".into(), "v".into());
+        hash.insert("doomed".into(), "v".into());
+        hash.set_expires_at(&"doomed".into(), START + 100);
+        db.insert("hash".into(), StoreObject::new(DataKind::Hash(hash), clock::now()));
+
+        let mut cycle = ActiveExpire::default();
+        clock.advance(99);
+        cycle.run_cycle(&mut db, clock::now());
+        assert_eq!(db.len(), 1_101);
+
+        clock.advance(1);
+        cycle.run_cycle(&mut db, clock::now());
+        db.settle();
+        assert_eq!(db.len(), 1_001);
+        let Some(DataKind::Hash(hash)) = db.get(&"hash".into()).map(|obj| &obj.data) else {
+            panic!("hash was reclaimed whole");
+        };
+        assert_eq!(hash.len(), 1);
+        assert_eq!(hash.volatile_len(), 0);
+    }
+}
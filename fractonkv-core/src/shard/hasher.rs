@@ -1,52 +1,3 @@
-// use xxhash_rust::xxh3::xxh3_64;
-
-// #[derive(Clone, Copy)]
-// pub struct ModHasher {
-//     shards: usize,
-// }
-
-// impl ModHasher {
-//     pub fn new(shards: usize) -> Self {
-//         assert!(shards > 0);
-//         Self { shards }
-//     }
-//     #[inline]
-//     pub fn hash(&self, key: &str) -> usize {
-//         (xxh3_64(extract_hashtag(key).as_bytes()) % self.shards as u64) as usize
-//     }
-// }
-
-// /// Extracts a Redis-style hashtag:
-// /// - If key contains `{...}`, return the content inside the first balanced braces.
-// /// - Otherwise, return the whole key.
-// ///
-// /// Examples:
-// /// - "user:{123}"   -> "123"
-// /// - "foo"          -> "foo"
-// /// - "{bar}"        -> "bar"
-// /// - "baz{qux}zzz"  -> "qux"
-// /// - "empty:{}"     -> "empty:{}"   (ignored, empty tag)
-// /// - "weird{nest{}}" -> "weird{nest{}}" (ignored, malformed)
-// #[inline]
-// fn extract_hashtag(key: &str) -> &str {
-//     let mut chars = key.char_indices();
-
-//     // Find the first '{'
-//     if let Some((start, _)) = chars.find(|&(_, c)| c == '{') {
-//         // Look for the next '}'
-//         if let Some((end, _)) = key[start + 1..].char_indices().find(|&(_, c)| c == '}') {
-//             let open = start + 1;
-//             let close = start + 1 + end;
-//             // Only use non-empty tags
-//             if close > open {
-//                 return &key[open..close];
-//             }
-//         }
-//     }
-//     // Fallback: no valid hashtag
-//     key
-// }
-
 use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
@@ -55,7 +6,6 @@ use twox_hash::XxHash64;
 #[derive(Clone)]
 pub struct ConsistentHashRing {
     ring: BTreeMap<u64, usize>,
-    vnodes: usize,
 }
 
 impl ConsistentHashRing {
@@ -70,7 +20,7 @@ impl ConsistentHashRing {
             }
         }
 
-        Self { ring, vnodes }
+        Self { ring }
     }
 
     pub fn get_shard<K: Hash>(&self, key: &K) -> usize {
@@ -91,3 +41,27 @@ impl ConsistentHashRing {
         hasher.finish()
     }
 }
+
+/// Extracts a Redis-style hashtag:
+/// - If key contains `{...}`, return the content inside the first balanced braces.
+/// - Otherwise, return the whole key.
+///
+/// Examples:
+/// - "user:{123}"   -> "123"
+/// - "foo"          -> "foo"
+/// - "{bar}"        -> "bar"
+/// - "baz{qux}zzz"  -> "qux"
+/// - "empty:{}"     -> "empty:{}"   (ignored, empty tag)
+#[inline]
+pub fn extract_hashtag(key: &[u8]) -> &[u8] {
+    // Find the first '{', then the next '}' after it
+    if let Some(open) = key.iter().position(|&b| b == b'{')
+        && let Some(len) = key[open + 1..].iter().position(|&b| b == b'}')
+        // Only use non-empty tags
+        && len > 0
+    {
+        return &key[open + 1..open + 1 + len];
+    }
+    // Fallback: no valid hashtag
+    key
+}
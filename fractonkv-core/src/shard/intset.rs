@@ -0,0 +1,161 @@
+/// Sorted set of integers packed in one buffer, every element stored on the narrowest width
+/// (2, 4 or 8 bytes) that fits the largest of them, as in Redis' intset.
+///
+/// Lookups are binary searches and inserts shift the tail, so sets using it convert to a hash
+/// table past `set-max-intset-entries`. Adding a value that needs a wider width re-encodes the
+/// whole set once.
+#[derive(Clone, Debug)]
+pub struct IntSet {
+    buf: Vec<u8>,
+    width: usize,
+}
+
+impl Default for IntSet {
+    fn default() -> Self {
+        Self { buf: Vec::new(), width: 2 }
+    }
+}
+
+impl IntSet {
+    pub fn len(&self) -> usize {
+        self.buf.len() / self.width
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        width_of(value) <= self.width && self.search(value).is_ok()
+    }
+
+    /// Adds `value`; returns `true` if it was not present yet.
+    pub fn insert(&mut self, value: i64) -> bool {
+        if width_of(value) > self.width {
+            self.widen(width_of(value));
+        }
+        let Err(index) = self.search(value) else {
+            return false;
+        };
+        let offset = index * self.width;
+        let bytes = value.to_le_bytes();
+        self.buf.splice(offset..offset, bytes[..self.width].iter().copied());
+        true
+    }
+
+    pub fn remove(&mut self, value: i64) -> bool {
+        if width_of(value) > self.width {
+            return false;
+        }
+        let Ok(index) = self.search(value) else {
+            return false;
+        };
+        let offset = index * self.width;
+        self.buf.drain(offset..offset + self.width);
+        true
+    }
+
+    /// Elements in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    /// Bytes allocated for the buffer.
+    pub fn heap_size(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Element at `index` in ascending order.
+    pub fn get(&self, index: usize) -> i64 {
+        let bytes = &self.buf[index * self.width..(index + 1) * self.width];
+        match self.width {
+            2 => i16::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            _ => i64::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+
+    fn search(&self, value: i64) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.get(mid).cmp(&value) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+
+    /// Re-encodes every element on `width` bytes.
+    fn widen(&mut self, width: usize) {
+        let values: Vec<i64> = self.iter().collect();
+        self.buf = Vec::with_capacity(values.len() * width);
+        for value in values {
+            self.buf.extend_from_slice(&value.to_le_bytes()[..width]);
+        }
+        self.width = width;
+    }
+}
+
+/// Narrowest width, in bytes, that holds `value`.
+fn width_of(value: i64) -> usize {
+    if i16::try_from(value).is_ok() {
+        2
+    } else if i32::try_from(value).is_ok() {
+        4
+    } else {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntSet;
+
+    #[test]
+    fn widens_from_16_to_32_to_64_bits_keeping_the_order() {
+        let mut set = IntSet::default();
+        for value in [3, -7, i16::MAX as i64, i16::MIN as i64] {
+            assert!(set.insert(value));
+        }
+        assert_eq!(set.width, 2);
+        assert!(!set.contains(i16::MAX as i64 + 1), "wider than any element");
+        assert!(!set.remove(i16::MAX as i64 + 1));
+
+        assert!(set.insert(i16::MAX as i64 + 1));
+        assert_eq!(set.width, 4);
+        assert!(set.insert(i32::MIN as i64));
+        assert_eq!(set.width, 4);
+
+        assert!(set.insert(i64::MAX));
+        assert!(set.insert(i32::MAX as i64 + 1));
+        assert!(!set.insert(i64::MAX));
+        assert_eq!(set.width, 8);
+        assert_eq!(set.buf.len(), set.len() * 8);
+
+        let mut expected = vec![
+            3,
+            -7,
+            i16::MAX as i64,
+            i16::MIN as i64,
+            i16::MAX as i64 + 1,
+            i32::MIN as i64,
+            i64::MAX,
+            i32::MAX as i64 + 1,
+        ];
+        expected.sort();
+        assert_eq!(set.iter().collect::<Vec<_>>(), expected);
+        assert!(expected.iter().all(|&value| set.contains(value)));
+    }
+
+    #[test]
+    fn removing_the_widest_elements_keeps_the_width() {
+        let mut set = IntSet::default();
+        set.insert(1);
+        set.insert(i64::MIN);
+        assert!(set.remove(i64::MIN));
+        assert!(!set.remove(i64::MIN));
+        assert_eq!(set.width, 8);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [1]);
+        assert!(set.remove(1));
+        assert_eq!(set.len(), 0);
+    }
+}
@@ -0,0 +1,74 @@
+//! Background reclamation of large values, as in Redis' lazyfree.
+//!
+//! Dropping a collection with millions of elements frees each of them in turn, which would
+//! stall every client of the shard doing it. Values that take more than
+//! [`LAZYFREE_THRESHOLD`] allocations to free are instead handed to a single process-wide
+//! thread that drops them; the key is already gone from the keyspace by then. Smaller values
+//! are cheaper to drop than to hand over, so they are always freed inline.
+
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Sender, channel};
+
+use crate::shard::types::{DataKind, StoreObject};
+
+/// Values needing at most this many allocations freed are dropped inline, as in Redis.
+const LAZYFREE_THRESHOLD: usize = 64;
+
+/// Values handed to the background thread that it has not dropped yet.
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Mailbox of the background thread, which is started on first use.
+static FREER: LazyLock<Sender<Box<dyn Send>>> = LazyLock::new(|| {
+    let (tx, rx) = channel::<Box<dyn Send>>();
+    std::thread::Builder::new()
+        .name("lazyfree".to_string())
+        .spawn(move || {
+            for garbage in rx {
+                drop(garbage);
+                PENDING.fetch_sub(1, Ordering::Relaxed);
+            }
+        })
+        .expect("failed to spawn the lazyfree thread");
+    tx
+});
+
+/// Frees a value taken out of the keyspace: on the background thread when `lazy` is set and
+/// the value is large enough to be worth it, inline otherwise.
+pub fn free(obj: StoreObject, lazy: bool) {
+    if lazy && free_effort(&obj.data) > LAZYFREE_THRESHOLD {
+        defer(Box::new(obj));
+    }
+}
+
+/// Frees `garbage`, such as a whole keyspace emptied by `FLUSHALL ASYNC`, on the background
+/// thread whatever its size.
+pub fn free_all(garbage: impl Send + 'static) {
+    defer(Box::new(garbage));
+}
+
+/// Values waiting to be freed by the background thread.
+pub fn pending_objects() -> usize {
+    PENDING.load(Ordering::Relaxed)
+}
+
+fn defer(garbage: Box<dyn Send>) {
+    PENDING.fetch_add(1, Ordering::Relaxed);
+    if let Err(returned) = FREER.send(garbage) {
+        // The thread is gone; free inline rather than leak.
+        PENDING.fetch_sub(1, Ordering::Relaxed);
+        drop(returned);
+    }
+}
+
+/// Roughly how many allocations freeing `data` takes: one per chunk of a list, one per element
+/// of a hash table or skiplist, and one for strings and the packed encodings.
+fn free_effort(data: &DataKind) -> usize {
+    match data {
+        DataKind::List(list) => list.chunk_count(),
+        DataKind::Hash(hash) if hash.encoding() == "hashtable" => hash.len(),
+        DataKind::Set(set) if set.encoding() == "hashtable" => set.len(),
+        DataKind::SortedSet(zset) if zset.encoding() == "skiplist" => zset.len(),
+        _ => 1,
+    }
+}
@@ -0,0 +1,185 @@
+/// Byte strings packed back to back in one buffer, each behind a varint length, in the spirit
+/// of Redis' listpack.
+///
+/// An entry costs its length plus one or two bytes, against a `Bytes` header and a separate
+/// allocation per element in the full encodings. Entries are addressed by the byte offset at
+/// which they start, as handed out by [`ListPack::entries`]; lookups are linear scans, so the
+/// collections using it convert to a hash table once they grow past the configured
+/// `*-max-listpack-*` thresholds.
+#[derive(Clone, Debug, Default)]
+pub struct ListPack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl ListPack {
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Byte offset just past the last entry, where [`ListPack::insert`] appends.
+    pub fn end(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries().map(|(_, entry)| entry)
+    }
+
+    /// Entries with the byte offset each starts at.
+    pub fn entries(&self) -> Entries<'_> {
+        Entries { buf: &self.buf, offset: 0 }
+    }
+
+    /// Consecutive pairs of entries with their offsets, for packs holding field/value or
+    /// member/score pairs.
+    pub fn pairs(&self) -> impl Iterator<Item = (Entry<'_>, Entry<'_>)> {
+        let mut entries = self.entries();
+        std::iter::from_fn(move || Some((entries.next()?, entries.next()?)))
+    }
+
+    /// Inserts `entries` one after the other at byte `offset`, which must be the start of an
+    /// entry or [`ListPack::end`].
+    pub fn insert(&mut self, offset: usize, entries: &[&[u8]]) {
+        let mut encoded = Vec::with_capacity(entries.iter().map(|e| e.len() + 2).sum());
+        for entry in entries {
+            encode_len(&mut encoded, entry.len());
+            encoded.extend_from_slice(entry);
+        }
+        self.buf.splice(offset..offset, encoded);
+        self.len += entries.len();
+    }
+
+    pub fn push(&mut self, entry: &[u8]) {
+        self.insert(self.end(), &[entry]);
+    }
+
+    /// Replaces the entry starting at byte `offset` with `entry`.
+    pub fn replace(&mut self, offset: usize, entry: &[u8]) {
+        let end = self.skip(offset, 1);
+        let mut encoded = Vec::with_capacity(entry.len() + 2);
+        encode_len(&mut encoded, entry.len());
+        encoded.extend_from_slice(entry);
+        self.buf.splice(offset..end, encoded);
+    }
+
+    /// Removes `count` entries starting with the one at byte `offset`.
+    pub fn remove(&mut self, offset: usize, count: usize) {
+        let end = self.skip(offset, count);
+        self.buf.drain(offset..end);
+        self.len -= count;
+    }
+
+    /// Bytes allocated for the buffer.
+    pub fn heap_size(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Offset just past the `count` entries starting at `offset`.
+    fn skip(&self, offset: usize, count: usize) -> usize {
+        let mut entries = Entries { buf: &self.buf, offset };
+        for _ in 0..count {
+            entries.next().expect("listpack entry out of range");
+        }
+        entries.offset
+    }
+}
+
+/// An entry of a [`ListPack`] with the byte offset it starts at.
+pub type Entry<'a> = (usize, &'a [u8]);
+
+/// Iterator over the entries of a [`ListPack`] with their byte offsets.
+pub struct Entries<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.buf.len() {
+            return None;
+        }
+        let start = self.offset;
+        let (len, header) = decode_len(&self.buf[start..]);
+        let data = start + header;
+        self.offset = data + len;
+        Some((start, &self.buf[data..data + len]))
+    }
+}
+
+/// Appends `len` as a little-endian base-128 varint.
+fn encode_len(buf: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        buf.push((len as u8) | 0x80);
+        len >>= 7;
+    }
+    buf.push(len as u8);
+}
+
+/// Reads a varint written by [`encode_len`]; returns it with the number of bytes it took.
+fn decode_len(buf: &[u8]) -> (usize, usize) {
+    let mut len = 0;
+    for (i, &byte) in buf.iter().enumerate() {
+        len |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (len, i + 1);
+        }
+    }
+    unreachable!("truncated listpack entry header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListPack, decode_len, encode_len};
+
+    #[test]
+    fn lengths_round_trip_through_their_varint() {
+        for (len, header) in [
+            (0, 1),
+            (1, 1),
+            (0x7f, 1),
+            (0x80, 2),
+            (0x3fff, 2),
+            (0x4000, 3),
+            (1 << 35, 6),
+            (usize::MAX, 10),
+        ] {
+            let mut buf = Vec::new();
+            encode_len(&mut buf, len);
+            assert_eq!(buf.len(), header, "header of {len}");
+            buf.push(0xff);
+            assert_eq!(decode_len(&buf), (len, header));
+        }
+    }
+
+    #[test]
+    fn entries_of_every_header_size_can_be_inserted_replaced_and_removed() {
+        let entries: Vec<Vec<u8>> =
+            [0, 1, 0x7f, 0x80, 0x4000].iter().map(|&len| vec![len as u8; len]).collect();
+        let mut pack = ListPack::default();
+        for entry in &entries {
+            pack.push(entry);
+        }
+        assert_eq!(pack.len(), entries.len());
+        assert!(pack.iter().eq(entries.iter().map(Vec::as_slice)));
+
+        // Swap the longest entry for a short one, and a short one for a long one.
+        let offsets: Vec<usize> = pack.entries().map(|(offset, _)| offset).collect();
+        pack.replace(offsets[4], b"short");
+        pack.replace(offsets[1], &[7; 0x100]);
+        let offsets: Vec<usize> = pack.entries().map(|(offset, _)| offset).collect();
+        pack.remove(offsets[2], 2);
+        pack.insert(offsets[0], &[b"a", b"b"]);
+
+        let expected: [&[u8]; 5] = [b"a", b"b", &[], &[7; 0x100], b"short"];
+        assert!(pack.iter().eq(expected));
+        assert_eq!(pack.len(), 5);
+        // One header byte per entry, two for the one longer than 127 bytes.
+        assert_eq!(pack.end(), 2 + 2 + 1 + (2 + 0x100) + (1 + 5));
+        let pairs: Vec<_> = pack.pairs().map(|((_, a), (_, b))| (a, b)).collect();
+        assert_eq!(pairs, [(&b"a"[..], &b"b"[..]), (&[][..], &[7; 0x100][..])]);
+    }
+}
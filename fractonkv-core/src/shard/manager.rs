@@ -1,9 +1,15 @@
 use crate::shard::hasher::ConsistentHashRing;
 use crate::shard::shard::Shard;
 use crate::shard::types::ShardJob;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::channel;
 
+/// Points each shard takes on the hash ring.
+pub const VNODES: usize = 64;
+
 pub struct ShardManager {
     pub num_shards: usize,
     pub senders: Vec<Sender<ShardJob>>,
@@ -17,10 +23,11 @@ impl ShardManager {
         }
     }
 
-    pub fn start(&mut self) -> Vec<std::thread::JoinHandle<()>> {
+    /// Spawns one thread per shard, all accepting connections on `addr`.
+    pub fn start(&mut self, addr: SocketAddr) -> Vec<std::thread::JoinHandle<()>> {
         let mut receivers = Vec::with_capacity(self.num_shards);
 
-        let _consistent_hasher = ConsistentHashRing::new((0..=self.num_shards).collect(), 64);
+        let consistent_hasher = ConsistentHashRing::new((0..self.num_shards).collect(), VNODES);
 
         self.senders = Vec::with_capacity(self.num_shards);
 
@@ -33,14 +40,15 @@ impl ShardManager {
 
         // Step 2. Spawn shards
         let mut handles = Vec::with_capacity(self.num_shards);
+        let used_memory: Arc<[AtomicUsize]> =
+            (0..self.num_shards).map(|_| AtomicUsize::new(0)).collect();
 
-        for (i, _rx) in receivers.into_iter().enumerate() {
-            // let peers = self.senders.clone();
+        for (i, rx) in receivers.into_iter().enumerate() {
+            let peers = self.senders.clone();
 
-            let shard = Shard::new(i);
-            // let consistent_hasher = consistent_hasher.clone();
+            let shard = Shard::new(i, consistent_hasher.clone(), peers, used_memory.clone());
 
-            let handle = std::thread::spawn(move || shard.run());
+            let handle = std::thread::spawn(move || shard.run(addr, rx));
             handles.push(handle);
         }
         handles
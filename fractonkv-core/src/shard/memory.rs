@@ -0,0 +1,46 @@
+//! Approximate memory accounting for stored values.
+//!
+//! Like Redis' `objectComputeSize`, estimates never walk a whole collection: the first few
+//! elements are measured and their average size is scaled to the collection's length, so
+//! measuring any value costs O(samples). Shared `Bytes` buffers are counted once per holder.
+
+use bytes::Bytes;
+
+use crate::shard::types::DataKind;
+
+/// Elements measured per collection when keeping the running per-shard estimate.
+pub const DEFAULT_SAMPLES: usize = 5;
+
+/// Bytes held by `key` and its value outside of the keyspace table's own slot.
+pub fn entry_size(key: &Bytes, data: &DataKind, samples: usize) -> usize {
+    key.len() + value_size(data, samples)
+}
+
+/// Heap bytes held by a value. With `samples` of 0, every element is measured.
+pub fn value_size(data: &DataKind, samples: usize) -> usize {
+    match data {
+        DataKind::BulkString(bytes) => bytes.len(),
+        DataKind::Int(_) => 0,
+        DataKind::Hash(hash) => hash.heap_size(samples),
+        DataKind::List(list) => list.heap_size(samples),
+        DataKind::Set(set) => set.heap_size(samples),
+        DataKind::SortedSet(zset) => zset.heap_size(samples),
+    }
+}
+
+/// Mean of `size` over the first `samples` of `items` (all of them if `samples` is 0), scaled
+/// to `len` items.
+pub fn sampled<T>(
+    items: impl Iterator<Item = T>,
+    len: usize,
+    samples: usize,
+    size: impl Fn(T) -> usize,
+) -> usize {
+    let limit = if samples == 0 { usize::MAX } else { samples };
+    let (mut measured, mut total) = (0, 0);
+    for item in items.take(limit) {
+        total += size(item);
+        measured += 1;
+    }
+    (total * len).checked_div(measured).unwrap_or(0)
+}
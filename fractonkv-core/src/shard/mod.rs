@@ -1,4 +1,20 @@
+pub(crate) mod blocking;
+pub(crate) mod clock;
+pub(crate) mod dict;
+pub(crate) mod evict;
+mod expire;
 mod hasher;
+pub(crate) mod intset;
+pub(crate) mod lazyfree;
+pub(crate) mod listpack;
 pub(crate) mod manager;
-mod shard;
+pub(crate) mod memory;
+pub(crate) mod quicklist;
+mod scatter;
+#[allow(clippy::module_inception)]
+pub(crate) mod shard;
+pub(crate) mod skiplist;
+#[cfg(test)]
+pub(crate) mod testing;
 pub(crate) mod types;
+pub(crate) mod zset;
@@ -0,0 +1,347 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+
+use crate::shard::memory;
+
+/// Elements per chunk before a chunk is split in two.
+const CHUNK_CAPACITY: usize = 128;
+
+/// List value: a deque of chunks, each a small deque of elements, in the spirit of Redis'
+/// quicklist.
+///
+/// Pushes and pops at either end are O(1); positional access walks chunks from the nearer end,
+/// so it costs O(n / CHUNK_CAPACITY) rather than O(n). Chunks are never left empty, and
+/// neighbours thinned out by removals are merged so that they stay reasonably full.
+#[derive(Clone, Debug, Default)]
+pub struct QuickList {
+    chunks: VecDeque<VecDeque<Bytes>>,
+    len: usize,
+}
+
+impl QuickList {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of chunks, reported as `ql_nodes` by `DEBUG OBJECT`.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn push_front(&mut self, value: Bytes) {
+        match self.chunks.front_mut() {
+            Some(chunk) if chunk.len() < CHUNK_CAPACITY => chunk.push_front(value),
+            _ => self.chunks.push_front(VecDeque::from([value])),
+        }
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: Bytes) {
+        match self.chunks.back_mut() {
+            Some(chunk) if chunk.len() < CHUNK_CAPACITY => chunk.push_back(value),
+            _ => self.chunks.push_back(VecDeque::from([value])),
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.front_mut()?;
+        let value = chunk.pop_front();
+        if chunk.is_empty() {
+            self.chunks.pop_front();
+        }
+        self.len -= 1;
+        value
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.back_mut()?;
+        let value = chunk.pop_back();
+        if chunk.is_empty() {
+            self.chunks.pop_back();
+        }
+        self.len -= 1;
+        value
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bytes> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks[chunk].get(offset)
+    }
+
+    /// Replaces the element at `index`; returns `false` if it is out of range.
+    pub fn set(&mut self, index: usize, value: Bytes) -> bool {
+        match self.locate(index) {
+            Some((chunk, offset)) => {
+                self.chunks[chunk][offset] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts `value` so that it ends up at `index`; `index == len()` appends.
+    pub fn insert(&mut self, index: usize, value: Bytes) {
+        assert!(index <= self.len, "insert index out of range");
+        if index == self.len {
+            self.push_back(value);
+            return;
+        }
+        let (chunk, offset) = self.locate(index).expect("index checked above");
+        self.chunks[chunk].insert(offset, value);
+        self.len += 1;
+
+        if self.chunks[chunk].len() > CHUNK_CAPACITY {
+            let tail = self.chunks[chunk].split_off(CHUNK_CAPACITY / 2);
+            self.chunks.insert(chunk + 1, tail);
+        }
+    }
+
+    /// Keeps only the elements in the inclusive range `start..=end`.
+    pub fn retain_range(&mut self, start: usize, end: usize) {
+        let back = self.len.saturating_sub(end + 1);
+        self.drop_back(back);
+        self.drop_front(start);
+    }
+
+    /// Removes up to `limit` elements equal to `value`, scanning from the tail when `from_tail`
+    /// is set; a `limit` of zero removes every match. Returns how many were removed.
+    pub fn remove_matching(&mut self, value: &[u8], limit: usize, from_tail: bool) -> usize {
+        let total = self.iter().filter(|element| element.as_ref() == value).count();
+        let limit = if limit == 0 { total } else { limit.min(total) };
+        // Removing the last `limit` matches is the same as skipping the first `total - limit`.
+        let mut skip = if from_tail { total - limit } else { 0 };
+        let mut left = limit;
+
+        for chunk in &mut self.chunks {
+            chunk.retain(|element| {
+                if left == 0 || element.as_ref() != value {
+                    return true;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    return true;
+                }
+                left -= 1;
+                false
+            });
+        }
+        self.len -= limit;
+        self.merge_chunks();
+        limit
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Bytes> {
+        self.chunks.iter().flatten()
+    }
+
+    /// Heap bytes held by the list, counting chunks as if full; see
+    /// [`memory::value_size`](crate::shard::memory::value_size).
+    pub fn heap_size(&self, samples: usize) -> usize {
+        self.chunks.capacity() * size_of::<VecDeque<Bytes>>()
+            + self.chunks.len() * CHUNK_CAPACITY * size_of::<Bytes>()
+            + memory::sampled(self.iter(), self.len, samples, Bytes::len)
+    }
+
+    /// Chunk and offset holding `index`, walking from whichever end is closer.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        if index < self.len / 2 {
+            let mut offset = index;
+            for (position, chunk) in self.chunks.iter().enumerate() {
+                if offset < chunk.len() {
+                    return Some((position, offset));
+                }
+                offset -= chunk.len();
+            }
+        } else {
+            let mut from_back = self.len - 1 - index;
+            for (position, chunk) in self.chunks.iter().enumerate().rev() {
+                if from_back < chunk.len() {
+                    return Some((position, chunk.len() - 1 - from_back));
+                }
+                from_back -= chunk.len();
+            }
+        }
+        None
+    }
+
+    /// Drops empty chunks and merges each chunk into the one before it where both fit in one.
+    fn merge_chunks(&mut self) {
+        let mut merged: VecDeque<VecDeque<Bytes>> = VecDeque::with_capacity(self.chunks.len());
+        for mut chunk in self.chunks.drain(..) {
+            match merged.back_mut() {
+                _ if chunk.is_empty() => {}
+                Some(last) if last.len() + chunk.len() <= CHUNK_CAPACITY => last.append(&mut chunk),
+                _ => merged.push_back(chunk),
+            }
+        }
+        self.chunks = merged;
+    }
+
+    fn drop_front(&mut self, mut count: usize) {
+        while count > 0 {
+            let Some(chunk) = self.chunks.front_mut() else {
+                break;
+            };
+            if chunk.len() <= count {
+                count -= chunk.len();
+                self.len -= chunk.len();
+                self.chunks.pop_front();
+            } else {
+                chunk.drain(..count);
+                self.len -= count;
+                count = 0;
+            }
+        }
+    }
+
+    fn drop_back(&mut self, mut count: usize) {
+        while count > 0 {
+            let Some(chunk) = self.chunks.back_mut() else {
+                break;
+            };
+            if chunk.len() <= count {
+                count -= chunk.len();
+                self.len -= chunk.len();
+                self.chunks.pop_back();
+            } else {
+                chunk.truncate(chunk.len() - count);
+                self.len -= count;
+                count = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{CHUNK_CAPACITY, QuickList};
+
+    fn element(i: usize) -> Bytes {
+        Bytes::from(i.to_string())
+    }
+
+    fn list_of(len: usize) -> QuickList {
+        let mut list = QuickList::default();
+        (0..len).for_each(|i| list.push_back(element(i)));
+        list
+    }
+
+    /// Checks the chunk invariants and that `list` holds exactly `expected`, reading it both by
+    /// iteration and by index.
+    fn assert_holds(list: &QuickList, expected: &[Bytes]) {
+        assert!(
+            list.chunks
+                .iter()
+                .all(|chunk| !chunk.is_empty() && chunk.len() <= CHUNK_CAPACITY)
+        );
+        assert_eq!(list.chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), list.len());
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), expected);
+        for (index, value) in expected.iter().enumerate() {
+            assert_eq!(list.get(index), Some(value), "index {index}");
+        }
+        assert_eq!(list.get(expected.len()), None);
+    }
+
+    #[test]
+    fn pushes_open_a_new_chunk_once_the_end_one_is_full() {
+        let mut list = list_of(CHUNK_CAPACITY);
+        assert_eq!(list.chunk_count(), 1);
+        list.push_back(element(CHUNK_CAPACITY));
+        assert_eq!(list.chunk_count(), 2);
+        list.push_front(element(999));
+        assert_eq!(list.chunk_count(), 3);
+
+        let mut expected: Vec<Bytes> = (0..=CHUNK_CAPACITY).map(element).collect();
+        expected.insert(0, element(999));
+        assert_holds(&list, &expected);
+
+        assert_eq!(list.pop_front(), Some(element(999)));
+        assert_eq!(list.pop_back(), Some(element(CHUNK_CAPACITY)));
+        assert_eq!(list.chunk_count(), 1);
+    }
+
+    #[test]
+    fn locate_finds_every_index_from_either_end_across_uneven_chunks() {
+        let mut list = list_of(3 * CHUNK_CAPACITY);
+        let mut expected: Vec<Bytes> = (0..3 * CHUNK_CAPACITY).map(element).collect();
+        // Splits the middle chunk, so chunks no longer all have the same length.
+        list.insert(CHUNK_CAPACITY + 1, element(999));
+        expected.insert(CHUNK_CAPACITY + 1, element(999));
+        assert_eq!(list.chunk_count(), 4);
+        assert_holds(&list, &expected);
+
+        assert!(list.set(list.len() - 1, element(1000)));
+        assert!(!list.set(list.len(), element(1001)));
+        *expected.last_mut().unwrap() = element(1000);
+        assert_holds(&list, &expected);
+    }
+
+    #[test]
+    fn inserting_at_chunk_edges_splits_the_full_chunk() {
+        let mut list = list_of(2 * CHUNK_CAPACITY);
+        let mut expected: Vec<Bytes> = (0..2 * CHUNK_CAPACITY).map(element).collect();
+        // Last slot of the first chunk, first slot of the second, then the very end.
+        for (index, value) in [(CHUNK_CAPACITY - 1, 1000), (CHUNK_CAPACITY + 1, 1001)] {
+            list.insert(index, element(value));
+            expected.insert(index, element(value));
+            assert_holds(&list, &expected);
+        }
+        assert_eq!(list.chunk_count(), 4);
+        list.insert(list.len(), element(1002));
+        expected.push(element(1002));
+        assert_holds(&list, &expected);
+    }
+
+    #[test]
+    fn removals_drop_emptied_chunks_and_merge_thin_neighbours() {
+        let mut list = QuickList::default();
+        let mut expected = Vec::new();
+        for i in 0..3 * CHUNK_CAPACITY {
+            // Every chunk holds a few keepers and is otherwise made of `x`.
+            let value = if i % CHUNK_CAPACITY < 4 {
+                element(i)
+            } else {
+                Bytes::from("x")
+            };
+            list.push_back(value.clone());
+            expected.push(value);
+        }
+        assert_eq!(list.chunk_count(), 3);
+
+        assert_eq!(list.remove_matching(b"x", 2, true), 2);
+        let last_x = expected.iter().rposition(|value| value == "x").unwrap();
+        expected.remove(last_x);
+        let last_x = expected.iter().rposition(|value| value == "x").unwrap();
+        expected.remove(last_x);
+        assert_holds(&list, &expected);
+
+        assert_eq!(list.remove_matching(b"x", 0, false), 3 * (CHUNK_CAPACITY - 4) - 2);
+        expected.retain(|value| value != "x");
+        assert_holds(&list, &expected);
+        assert_eq!(list.chunk_count(), 1);
+    }
+
+    #[test]
+    fn retain_range_trims_whole_and_partial_chunks() {
+        let mut list = list_of(4 * CHUNK_CAPACITY);
+        let (start, end) = (CHUNK_CAPACITY - 1, 3 * CHUNK_CAPACITY);
+        list.retain_range(start, end);
+        let expected: Vec<Bytes> = (start..=end).map(element).collect();
+        assert_holds(&list, &expected);
+        assert_eq!(list.chunk_count(), 4);
+
+        list.retain_range(CHUNK_CAPACITY + 1, CHUNK_CAPACITY + 1);
+        assert_holds(&list, &expected[CHUNK_CAPACITY + 1..=CHUNK_CAPACITY + 1]);
+        assert_eq!(list.chunk_count(), 1);
+    }
+}
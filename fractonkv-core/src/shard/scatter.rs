@@ -0,0 +1,92 @@
+use futures::future::join_all;
+use std::collections::BTreeMap;
+use tokio::sync::oneshot;
+
+use crate::shard::hasher::extract_hashtag;
+use crate::shard::shard::Shard;
+use crate::shard::types::{DataStore, ShardJob};
+
+impl Shard {
+    /// Shard that owns `key`. Only the `{hashtag}` part is hashed when present, so related keys
+    /// can be pinned to the same shard.
+    pub fn owner(&self, key: &[u8]) -> usize {
+        self.ring.get_shard(&extract_hashtag(key))
+    }
+
+    /// Runs `job` against the store of shard `target`: inline when it is this shard, otherwise
+    /// through the target's mailbox.
+    pub async fn run_on<T, F>(&self, target: usize, job: F) -> T
+    where
+        F: FnOnce(&mut DataStore) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.run_on_shard(target, move |shard| job(&mut shard.db.borrow_mut())).await
+    }
+
+    /// Like [`Shard::run_on`], but `job` gets the whole target shard, e.g. to reach its wait
+    /// queues.
+    pub async fn run_on_shard<T, F>(&self, target: usize, job: F) -> T
+    where
+        F: FnOnce(&Shard) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if target == self.id {
+            return job(self);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let job: ShardJob = Box::new(move |shard| {
+            let _ = tx.send(job(shard));
+        });
+        self.peers[target].send(job).await.expect("shard mailbox closed");
+        rx.await.expect("shard dropped a forwarded job")
+    }
+
+    /// Runs `job` against the store of every shard concurrently, for commands that act on the
+    /// whole keyspace. Results are in shard id order.
+    pub async fn broadcast<T, F>(&self, job: F) -> Vec<T>
+    where
+        F: Fn(&mut DataStore) -> T + Clone + Send + 'static,
+        T: Send + 'static,
+    {
+        join_all((0..self.peers.len()).map(|target| self.run_on(target, job.clone()))).await
+    }
+
+    /// Scatter-gather over the shards owning `items`.
+    ///
+    /// Items are grouped by the owner of `key_of(item)` and each group is sent to its shard as
+    /// a single sub-job. `job` must return exactly one result per item it receives, in order;
+    /// the results are merged back into the order of `items`.
+    pub async fn scatter_gather<I, T, K, F>(&self, items: Vec<I>, key_of: K, job: F) -> Vec<T>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        K: Fn(&I) -> &[u8],
+        F: Fn(&mut DataStore, Vec<I>) -> Vec<T> + Clone + Send + 'static,
+    {
+        let total = items.len();
+        let mut groups: BTreeMap<usize, (Vec<usize>, Vec<I>)> = BTreeMap::new();
+        for (position, item) in items.into_iter().enumerate() {
+            let group = groups.entry(self.owner(key_of(&item))).or_default();
+            group.0.push(position);
+            group.1.push(item);
+        }
+
+        let runs = groups.into_iter().map(|(target, (positions, items))| {
+            let job = job.clone();
+            async move { (positions, self.run_on(target, move |db| job(db, items)).await) }
+        });
+
+        let mut merged: Vec<Option<T>> = (0..total).map(|_| None).collect();
+        for (positions, results) in join_all(runs).await {
+            debug_assert_eq!(positions.len(), results.len());
+            for (position, result) in positions.into_iter().zip(results) {
+                merged[position] = Some(result);
+            }
+        }
+        merged
+            .into_iter()
+            .map(|result| result.expect("sub-job skipped an item"))
+            .collect()
+    }
+}
@@ -1,5 +1,18 @@
-use crate::commands::{CommandKind, dispatcher::dispatch_command};
-use crate::shard::types::DataStore;
+use crate::allocator;
+use crate::commands::{
+    CommandKind,
+    dispatcher::{
+        Route, dispatch_all_shards, dispatch_command, dispatch_connection, dispatch_cross_shard,
+        route,
+    },
+};
+use crate::config::CONFIG;
+use crate::errors::CommandExecutionError;
+use crate::shard::blocking::{UnblockMode, WaitQueues};
+use crate::shard::clock;
+use crate::shard::expire::{ACTIVE_EXPIRE_INTERVAL, ActiveExpire};
+use crate::shard::hasher::ConsistentHashRing;
+use crate::shard::types::{DataStore, ShardJob};
 
 use futures::FutureExt;
 use futures::stream::FuturesUnordered;
@@ -7,36 +20,92 @@ use futures::{SinkExt, StreamExt};
 use log::{error, info};
 use redis_protocol::codec::Resp3;
 use redis_protocol::resp3::types::BytesFrame;
+use socket2::{Domain, Socket, Type};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedSender};
 use tokio_util::codec::Framed;
 
 pub struct Shard {
     pub id: usize,
     pub db: RefCell<DataStore>,
+    /// Maps keys to the shard that owns them.
+    pub ring: ConsistentHashRing,
+    /// Mailboxes of every shard, indexed by shard id (including this one).
+    pub peers: Vec<Sender<ShardJob>>,
+    /// Clients blocked on keys owned by this shard.
+    pub blocked: RefCell<WaitQueues>,
+    /// Connections of this shard that are inside a blocking command, for `CLIENT UNBLOCK`.
+    pub blocked_clients: RefCell<HashMap<u64, UnboundedSender<UnblockMode>>>,
+    /// Keyspace bytes of every shard as of its last command, indexed by shard id, so that a
+    /// shard under its limit is not asked to evict.
+    pub used_memory: Arc<[AtomicUsize]>,
+}
+
+/// Time each expire tick may spend moving the keyspace along a resize when
+/// `activerehashing` is on, as Redis spends 1 ms per `serverCron` call.
+const ACTIVE_REHASH_BUDGET: Duration = Duration::from_millis(1);
+
+/// Source of connection ids, unique across all shards.
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The connection a command arrived on, for the commands that outlive a single request.
+pub struct Client<'a> {
+    pub id: u64,
+    socket: &'a TcpStream,
+}
+
+impl Client<'_> {
+    /// Resolves once the peer has closed the connection. Commands it pipelined in the meantime
+    /// are left for the connection loop to read, so a peer with some pending is not noticed
+    /// going away until they have run.
+    pub async fn closed(&self) {
+        match self.socket.peek(&mut [0]).await {
+            Ok(0) | Err(_) => {}
+            Ok(_) => std::future::pending().await,
+        }
+    }
 }
 
 impl Shard {
-    pub fn new(id: usize) -> Self {
-        Self { id, db: RefCell::new(HashMap::new()) }
+    pub fn new(
+        id: usize,
+        ring: ConsistentHashRing,
+        peers: Vec<Sender<ShardJob>>,
+        used_memory: Arc<[AtomicUsize]>,
+    ) -> Self {
+        Self {
+            id,
+            db: RefCell::new(DataStore::default()),
+            ring,
+            peers,
+            blocked: RefCell::new(WaitQueues::default()),
+            blocked_clients: RefCell::new(HashMap::new()),
+            used_memory,
+        }
     }
 
-    pub fn run(self) {
+    pub fn run(self, addr: SocketAddr, mailbox: Receiver<ShardJob>) {
         let local = tokio::task::LocalSet::new();
         let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
 
         rt.block_on(local.run_until(async move {
-            self.bind_and_listen("127.0.0.1:6380").await;
+            self.bind_and_listen(addr, mailbox).await;
         }));
     }
 
-    async fn bind_and_listen(&self, addr: &str) {
-        let listener = TcpListener::bind(addr).await.unwrap();
+    async fn bind_and_listen(&self, addr: SocketAddr, mut mailbox: Receiver<ShardJob>) {
+        let listener = bind_reuse_port(addr).unwrap();
         info!("Shard {} listening on {}", self.id, addr);
 
         let mut connections = FuturesUnordered::new();
+        let mut expire_tick = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+        let mut active_expire = ActiveExpire::default();
 
         loop {
             tokio::select! {
@@ -48,6 +117,23 @@ impl Shard {
                     // pass a reference to self.db instead of &mut self
                     connections.push(self.handle_connection(stream, peer_addr));
                 }
+                Some(job) = mailbox.recv() => {
+                    // Work forwarded by another shard; runs to completion on this thread.
+                    job(self);
+                    self.wake_blocked();
+                    self.settle();
+                }
+                _ = expire_tick.tick() => {
+                    {
+                        let mut db = self.db.borrow_mut();
+                        active_expire.run_cycle(&mut db, clock::now());
+                        if CONFIG.activerehashing() {
+                            db.rehash_for(ACTIVE_REHASH_BUDGET);
+                        }
+                    }
+                    self.settle();
+                    allocator::record_peak();
+                }
                 Some(_) = connections.next() => {
                     // A connection finished; automatically polled
                 }
@@ -55,16 +141,18 @@ impl Shard {
         }
     }
 
-    async fn handle_connection(&self, stream: tokio::net::TcpStream, peer_addr: SocketAddr) {
+    async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) {
         let mut framed = Framed::new(stream, Resp3::default());
+        let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
 
         while let Some(result) = framed.next().await {
             match result {
                 Ok(frame) => {
                     info!("Shard {} got frame from {}: {:?}", self.id, peer_addr, &frame);
 
-                    // Borrow db mutably only for this frame
-                    let response = handle_frame(&frame, &mut self.db.borrow_mut());
+                    let client = Client { id: client_id, socket: framed.get_ref() };
+                    let response = self.handle_frame(&frame, &client).await;
+                    self.settle();
 
                     if let Err(e) = framed.send(response).await {
                         error!("Write error to {}: {}", peer_addr, e);
@@ -79,21 +167,61 @@ impl Shard {
         }
         info!("Connection closed: {}", peer_addr);
     }
-}
 
-fn handle_frame(frame: &BytesFrame, db: &mut DataStore) -> BytesFrame {
-    let arr = match frame {
-        BytesFrame::Array { data, .. } if !data.is_empty() => data,
-        _ => {
-            return BytesFrame::SimpleError { data: "ERR invalid frame".into(), attributes: None };
+    /// Parses a frame and runs it on the shard owning its keys. Commands whose keys span
+    /// several shards go through their cross-shard executor instead, commands that need the
+    /// connection (blocking pops, `CLIENT`) are run here on its behalf, and keyspace-wide ones
+    /// (`DBSIZE`, `FLUSHALL`, ...) fan out from here to every shard. Commands that may grow
+    /// memory first make room on the owning shards, and are refused if that fails.
+    async fn handle_frame(&self, frame: &BytesFrame, client: &Client<'_>) -> BytesFrame {
+        let arr = match frame {
+            BytesFrame::Array { data, .. } if !data.is_empty() => data,
+            _ => {
+                return BytesFrame::SimpleError {
+                    data: "ERR invalid frame".into(),
+                    attributes: None,
+                };
+            }
+        };
+
+        let args: &[BytesFrame] = &arr[1..];
+        let cmd = match CommandKind::from_frame(frame) {
+            Ok(cmd) => cmd,
+            Err(err) => return BytesFrame::from(err),
+        };
+        if !cmd.accepts_argc(arr.len()) {
+            return CommandExecutionError::WrongArity(cmd.name()).into();
         }
-    };
 
-    let args: &[BytesFrame] = &arr[1..];
-    let cmd = match CommandKind::from_frame(frame) {
-        Ok(cmd) => cmd,
-        Err(err) => return BytesFrame::from(err),
-    };
+        let owners: BTreeSet<usize> = cmd.keys(args).into_iter().map(|k| self.owner(k)).collect();
+        if cmd.is_denyoom() && !self.reclaim_memory(&owners).await {
+            return CommandExecutionError::OutOfMemory.into();
+        }
+
+        let result = match (route(cmd), owners.len()) {
+            (Route::Connection, _) => dispatch_connection(self, client, cmd, args).await,
+            (Route::AllShards, _) => dispatch_all_shards(self, cmd, args).await,
+            (Route::Keys, 0) => dispatch_command(cmd, args, &mut self.db.borrow_mut()),
+            (Route::Keys, 1) => {
+                let owner = owners.into_iter().next().unwrap();
+                let args = args.to_vec();
+                self.run_on(owner, move |db| dispatch_command(cmd, &args, db)).await
+            }
+            (Route::Keys, _) => dispatch_cross_shard(self, cmd, args).await,
+        };
+        self.wake_blocked();
+        result.unwrap_or_else(|err| err.into())
+    }
+}
 
-    dispatch_command(cmd, args, db).unwrap_or_else(|err| err.into())
+/// Binds a listener with `SO_REUSEPORT` so every shard can accept on the same address and the
+/// kernel spreads incoming connections between them.
+fn bind_reuse_port(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
 }
@@ -0,0 +1,425 @@
+use bytes::Bytes;
+use rand::Rng;
+
+/// Levels a node can have; enough for 4^32 elements at the chosen branching factor.
+const MAX_LEVEL: usize = 32;
+
+/// Chance that a node reaching level `n` also reaches level `n + 1`.
+const LEVEL_PROBABILITY: f64 = 0.25;
+
+/// Arena index of the header node, which holds no element.
+const HEAD: usize = 0;
+
+/// Score index of a sorted set: a skiplist ordered by `(score, member)` where every link also
+/// records its span, the number of elements it skips. Summing spans along a search path gives
+/// an element's rank, so rank lookups and lookups by rank are O(log n) like inserts and
+/// removals, in the manner of Redis' `zskiplist`.
+///
+/// Nodes live in an arena and link to each other by index; freed slots are reused. Scores are
+/// never NaN, which keeps `<` a total order.
+#[derive(Clone, Debug)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    /// Levels in use by the tallest node.
+    level: usize,
+    len: usize,
+    tail: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    levels: Vec<Link>,
+    backward: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Link {
+    forward: Option<usize>,
+    /// Elements between this node and `forward`, counting `forward` itself. Links without a
+    /// `forward` count the elements left after this node.
+    span: usize,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            levels: vec![Link::default(); MAX_LEVEL],
+            backward: None,
+        };
+        Self {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+            tail: None,
+        }
+    }
+}
+
+impl SkipList {
+    /// Bytes taken by nodes and links, not counting the members they point to. Nodes have
+    /// 1 / (1 - LEVEL_PROBABILITY) levels on average.
+    pub fn heap_size(&self) -> usize {
+        let links = self.len * 4 / 3 + MAX_LEVEL;
+        self.nodes.capacity() * size_of::<Node>()
+            + self.free.capacity() * size_of::<usize>()
+            + links * size_of::<Link>()
+    }
+
+    /// Inserts `member` with `score`. The pair must not be in the list already.
+    pub fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i + 1 == self.level { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let new = self.alloc(Node {
+            member,
+            score,
+            levels: vec![Link::default(); level],
+            backward: None,
+        });
+        for i in 0..level {
+            let prev = update[i];
+            let skipped = rank[0] - rank[i];
+            self.nodes[new].levels[i] = Link {
+                forward: self.nodes[prev].levels[i].forward,
+                span: self.nodes[prev].levels[i].span - skipped,
+            };
+            self.nodes[prev].levels[i] = Link { forward: Some(new), span: skipped + 1 };
+        }
+        for (i, &prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[prev].levels[i].span += 1;
+        }
+
+        self.nodes[new].backward = (update[0] != HEAD).then_some(update[0]);
+        match self.nodes[new].levels[0].forward {
+            Some(next) => self.nodes[next].backward = Some(new),
+            None => self.tail = Some(new),
+        }
+        self.len += 1;
+    }
+
+    /// Removes the element `(score, member)`; returns whether it was present.
+    pub fn remove(&mut self, score: f64, member: &Bytes) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.nodes[next].precedes(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        match self.nodes[x].levels[0].forward {
+            Some(found)
+                if self.nodes[found].score == score && self.nodes[found].member == member =>
+            {
+                self.unlink(found, &update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of leading elements for which `pred(score, member)` holds. `pred` must hold for
+    /// a prefix of the list and fail for the rest, as with [`slice::partition_point`].
+    pub fn partition_point(&self, pred: impl Fn(f64, &Bytes) -> bool) -> usize {
+        let mut x = HEAD;
+        let mut rank = 0;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !pred(self.nodes[next].score, &self.nodes[next].member) {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+        }
+        rank
+    }
+
+    /// Elements from 0-based `rank` onwards, in ascending order.
+    pub fn iter_from(&self, rank: usize) -> Iter<'_> {
+        Iter {
+            list: self,
+            next: self.node_at(rank),
+            reverse: false,
+        }
+    }
+
+    /// Elements from 0-based `rank` back to the first, in descending order.
+    pub fn iter_back_from(&self, rank: usize) -> Iter<'_> {
+        Iter {
+            list: self,
+            next: self.node_at(rank),
+            reverse: true,
+        }
+    }
+
+    /// The last element, i.e. the one with the highest score.
+    pub fn last(&self) -> Option<(&Bytes, f64)> {
+        self.tail.map(|tail| self.nodes[tail].entry())
+    }
+
+    /// Arena index of the element at 0-based `rank`.
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        if rank >= self.len {
+            return None;
+        }
+        let target = rank + 1;
+        let mut x = HEAD;
+        let mut traversed = 0;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    fn unlink(&mut self, x: usize, update: &[usize; MAX_LEVEL]) {
+        for (i, &prev) in update.iter().enumerate().take(self.level) {
+            if self.nodes[prev].levels[i].forward == Some(x) {
+                let removed = self.nodes[x].levels[i];
+                let link = &mut self.nodes[prev].levels[i];
+                // `link` spanned up to `x`, so it is at least 1; the last node may span 0.
+                link.span = link.span + removed.span - 1;
+                link.forward = removed.forward;
+            } else {
+                self.nodes[prev].levels[i].span -= 1;
+            }
+        }
+        match self.nodes[x].levels[0].forward {
+            Some(next) => self.nodes[next].backward = self.nodes[x].backward,
+            None => self.tail = self.nodes[x].backward,
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+        self.len -= 1;
+
+        let node = &mut self.nodes[x];
+        node.member = Bytes::new();
+        node.levels = Vec::new();
+        node.backward = None;
+        self.free.push(x);
+    }
+
+    fn alloc(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Whether this node sorts before `(score, member)`: lower score, or equal score and a
+    /// lexicographically smaller member.
+    fn precedes(&self, score: f64, member: &Bytes) -> bool {
+        self.score < score || (self.score == score && self.member < member)
+    }
+
+    fn entry(&self) -> (&Bytes, f64) {
+        (&self.member, self.score)
+    }
+}
+
+/// Draws a node level: 1, then one more with probability [`LEVEL_PROBABILITY`] each time.
+fn random_level() -> usize {
+    let mut rng = rand::rng();
+    let mut level = 1;
+    while level < MAX_LEVEL && rng.random_bool(LEVEL_PROBABILITY) {
+        level += 1;
+    }
+    level
+}
+
+/// Iterator over a [`SkipList`], following level-0 links forwards or backward links back.
+pub struct Iter<'a> {
+    list: &'a SkipList,
+    next: Option<usize>,
+    reverse: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Bytes, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.list.nodes[self.next?];
+        self.next = if self.reverse {
+            node.backward
+        } else {
+            node.levels[0].forward
+        };
+        Some(node.entry())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bytes::Bytes;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{HEAD, SkipList};
+
+    /// Checks every link's span against the ranks of the nodes it joins, and the list against
+    /// `model`, which must be sorted by `(score, member)`.
+    fn assert_matches(list: &SkipList, model: &[(f64, Bytes)]) {
+        let ranks: HashMap<usize, usize> = {
+            let mut ranks = HashMap::from([(HEAD, 0)]);
+            let (mut x, mut rank) = (HEAD, 0);
+            while let Some(next) = list.nodes[x].levels[0].forward {
+                rank += 1;
+                ranks.insert(next, rank);
+                x = next;
+            }
+            ranks
+        };
+        for (&node, &rank) in &ranks {
+            for link in list.nodes[node].levels.iter().take(list.level) {
+                let to = link.forward.map_or(list.len, |next| ranks[&next]);
+                assert_eq!(link.span, to - rank, "span of a level of node {node}");
+            }
+        }
+
+        let forward: Vec<(f64, Bytes)> =
+            list.iter_from(0).map(|(member, score)| (score, member.clone())).collect();
+        assert_eq!(forward, model);
+        for (rank, (score, member)) in model.iter().enumerate() {
+            assert_eq!(list.iter_from(rank).next(), Some((member, *score)));
+            assert_eq!(list.iter_back_from(rank).count(), rank + 1);
+        }
+        assert!(list.iter_from(model.len()).next().is_none());
+        assert_eq!(list.last(), model.last().map(|(score, member)| (member, *score)));
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_member() {
+        let mut list = SkipList::default();
+        for member in ["b", "c", "a"] {
+            list.insert(1.0, Bytes::from(member));
+        }
+        list.insert(0.5, Bytes::from("z"));
+        list.insert(2.0, Bytes::from("0"));
+        let model: Vec<(f64, Bytes)> = [(0.5, "z"), (1.0, "a"), (1.0, "b"), (1.0, "c"), (2.0, "0")]
+            .map(|(score, member)| (score, Bytes::from(member)))
+            .into();
+        assert_matches(&list, &model);
+
+        let backward: Vec<&Bytes> = list.iter_back_from(3).map(|(member, _)| member).collect();
+        assert_eq!(backward, ["c", "b", "a", "z"]);
+    }
+
+    #[test]
+    fn removing_among_equal_scores_takes_only_the_exact_pair() {
+        let mut list = SkipList::default();
+        for member in ["a", "b", "c", "d"] {
+            list.insert(1.0, Bytes::from(member));
+        }
+        assert!(!list.remove(2.0, &Bytes::from("b")));
+        assert!(!list.remove(1.0, &Bytes::from("x")));
+        assert!(list.remove(1.0, &Bytes::from("b")));
+        assert!(list.remove(1.0, &Bytes::from("d")));
+        assert!(!list.remove(1.0, &Bytes::from("d")));
+        let model = vec![(1.0, Bytes::from("a")), (1.0, Bytes::from("c"))];
+        assert_matches(&list, &model);
+    }
+
+    #[test]
+    fn partition_point_counts_ranks_up_to_a_score_or_pair() {
+        let mut list = SkipList::default();
+        for i in 0..100 {
+            list.insert((i / 10) as f64, Bytes::from(format!("m{:02}", i % 10)));
+        }
+        assert_eq!(list.partition_point(|score, _| score < 3.0), 30);
+        assert_eq!(list.partition_point(|score, _| score <= 3.0), 40);
+        assert_eq!(list.partition_point(|score, _| score < -1.0), 0);
+        assert_eq!(list.partition_point(|score, _| score < f64::INFINITY), 100);
+        let rank_of = |score: f64, member: &str| {
+            list.partition_point(|s, m| s < score || (s == score && m.as_ref() < member.as_bytes()))
+        };
+        assert_eq!(rank_of(5.0, "m07"), 57);
+        assert_eq!(rank_of(5.0, "m075"), 58);
+    }
+
+    #[test]
+    fn random_inserts_and_removes_keep_ranks_and_spans_consistent() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut list = SkipList::default();
+        let mut model: Vec<(f64, Bytes)> = Vec::new();
+        for _ in 0..2_000 {
+            let score = rng.random_range(0..20) as f64;
+            let member = Bytes::from(format!("{}", rng.random_range(0..200)));
+            let at = model.iter().position(|(s, m)| *s == score && *m == member);
+            match at {
+                Some(at) if rng.random_bool(0.5) => {
+                    assert!(list.remove(score, &member));
+                    model.remove(at);
+                }
+                Some(_) => {}
+                None => {
+                    list.insert(score, member.clone());
+                    let at =
+                        model.partition_point(|(s, m)| *s < score || (*s == score && *m < member));
+                    model.insert(at, (score, member));
+                }
+            }
+        }
+        assert_matches(&list, &model);
+
+        // Emptying the list reuses the freed slots for the next inserts.
+        for (score, member) in std::mem::take(&mut model) {
+            assert!(list.remove(score, &member));
+        }
+        assert_matches(&list, &model);
+        let slots = list.nodes.len();
+        list.insert(1.0, Bytes::from("again"));
+        assert_eq!(list.nodes.len(), slots);
+    }
+}
@@ -0,0 +1,89 @@
+//! A server running in the test process, for tests that need several shards or a real
+//! connection: blocking pops, cross-shard commands and clients that go away mid-command.
+
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use redis_protocol::codec::Resp3;
+use redis_protocol::resp3::types::BytesFrame;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+use crate::commands::reply::{array, bulk};
+use crate::shard::hasher::{ConsistentHashRing, extract_hashtag};
+use crate::shard::manager::{ShardManager, VNODES};
+
+/// Shards listening on a port of their own. Their threads are left running when it is dropped.
+pub struct Cluster {
+    addr: SocketAddr,
+    ring: ConsistentHashRing,
+}
+
+impl Cluster {
+    pub fn start(num_shards: usize) -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0").and_then(|probe| probe.local_addr()).unwrap();
+        ShardManager::new(num_shards).start(addr);
+        Self {
+            addr,
+            ring: ConsistentHashRing::new((0..num_shards).collect(), VNODES),
+        }
+    }
+
+    /// Shard that owns `key`, as [`crate::shard::shard::Shard::owner`] computes it.
+    pub fn owner(&self, key: &str) -> usize {
+        self.ring.get_shard(&extract_hashtag(key.as_bytes()))
+    }
+
+    /// `count` keys starting with `prefix`, each owned by a different shard.
+    pub fn keys_on_distinct_shards(&self, prefix: &str, count: usize) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::with_capacity(count);
+        for key in (0..).map(|i| format!("{prefix}{i}")) {
+            if keys.len() == count {
+                break;
+            }
+            if keys.iter().all(|other| self.owner(other) != self.owner(&key)) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Opens a connection, waiting for the shards to start listening.
+    pub async fn connect(&self) -> Client {
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(self.addr).await {
+                return Client(Framed::new(stream, Resp3::default()));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("cluster is not accepting connections on {}", self.addr);
+    }
+}
+
+pub struct Client(Framed<TcpStream, Resp3>);
+
+impl Client {
+    /// Sends a command without waiting for its reply.
+    pub async fn send(&mut self, args: &[&str]) {
+        let frame =
+            array(args.iter().map(|arg| bulk(Bytes::copy_from_slice(arg.as_bytes()))).collect());
+        self.0.send(frame).await.unwrap();
+    }
+
+    /// Next reply on the connection.
+    pub async fn recv(&mut self) -> BytesFrame {
+        self.0.next().await.expect("connection closed").unwrap()
+    }
+
+    /// Whether a reply arrives within `timeout`, leaving it unread if not.
+    pub async fn replies_within(&mut self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, self.0.next()).await.is_ok()
+    }
+
+    pub async fn call(&mut self, args: &[&str]) -> BytesFrame {
+        self.send(args).await;
+        self.recv().await
+    }
+}
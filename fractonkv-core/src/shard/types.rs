@@ -1,185 +1,899 @@
 use bytes::Bytes;
-use redis_protocol::resp3::types::BytesFrame;
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::time::{Duration, Instant};
+use rand::Rng;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
 use strum_macros::Display;
 
-pub type ShardJob = String;
+use crate::commands::args::parse_i64;
+use crate::config::CONFIG;
+use crate::shard::clock::UnixMillis;
+use crate::shard::dict::Dict;
+use crate::shard::evict::LfuCounter;
+use crate::shard::intset::IntSet;
+use crate::shard::lazyfree;
+use crate::shard::listpack::ListPack;
+use crate::shard::memory::{self, DEFAULT_SAMPLES};
+use crate::shard::quicklist::QuickList;
+use crate::shard::shard::Shard;
+use crate::shard::zset::SortedSet;
 
-pub type DataStore = HashMap<Bytes, StoreObject>;
-#[derive(Debug)]
+/// Work sent to a shard's mailbox; it runs on the owning shard's thread with access to the
+/// shard's store and wait queues.
+pub type ShardJob = Box<dyn FnOnce(&Shard) + Send>;
+
+/// A shard's keyspace, with a running estimate of the memory it holds for `maxmemory`.
+///
+/// Inserts and removals update the estimate right away. Values changed in place through
+/// [`DataStore::get_mut`] are re-measured by [`DataStore::settle`], which the shard runs after
+/// every command, so the estimate may lag behind only while a command is running.
+#[derive(Default)]
+pub struct DataStore {
+    entries: Dict<Bytes, Entry>,
+    /// Sum of the entry sizes; the table itself is added by [`DataStore::used_memory`].
+    used: usize,
+    /// Keys handed out by `get_mut` since the last `settle`.
+    dirty: Vec<Bytes>,
+    /// Lists and sorted sets stored since blocked clients were last served; see
+    /// [`DataStore::take_ready`].
+    ready: Vec<Bytes>,
+    /// Keys with a deadline or with hash fields that have one, which the active expire cycle
+    /// walks instead of the whole keyspace, like Redis' `db->expires`. Kept up to date by
+    /// every insert and removal, and by [`DataStore::settle`] for values changed in place.
+    volatile: Dict<Bytes, ()>,
+    /// Missing keys a cross-shard write has claimed but not written yet; see
+    /// [`DataStore::reserve`].
+    reserved: HashSet<Bytes>,
+}
+
+struct Entry {
+    obj: StoreObject,
+    /// Estimate of what the key and value hold, as last measured.
+    size: usize,
+}
+
+impl DataStore {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Bytes) -> Option<&StoreObject> {
+        self.entries.get(key).map(|entry| &entry.obj)
+    }
+
+    /// Mutable access to a value; its size is re-measured at the next [`DataStore::settle`].
+    pub fn get_mut(&mut self, key: &Bytes) -> Option<&mut StoreObject> {
+        let entry = self.entries.get_mut(key)?;
+        self.dirty.push(key.clone());
+        Some(&mut entry.obj)
+    }
+
+    /// Inserts or replaces the value of `key`. A replaced value is freed according to
+    /// `lazyfree-lazy-server-del`.
+    pub fn insert(&mut self, key: Bytes, obj: StoreObject) {
+        let size = memory::entry_size(&key, &obj.data, DEFAULT_SAMPLES);
+        self.used += size;
+        if matches!(obj.data, DataKind::List(_) | DataKind::SortedSet(_)) {
+            self.ready.push(key.clone());
+        }
+        track_volatile(&mut self.volatile, &key, &obj);
+        if !self.reserved.is_empty() {
+            self.reserved.remove(&key);
+        }
+        if let Some(old) = self.entries.insert(key, Entry { obj, size }) {
+            self.used -= old.size;
+            lazyfree::free(old.obj, CONFIG.lazyfree_lazy_server_del());
+        }
+    }
+
+    /// Takes the value of `key` out of the keyspace, leaving it to the caller.
+    pub fn remove(&mut self, key: &Bytes) -> Option<StoreObject> {
+        let entry = self.entries.remove(key)?;
+        self.used -= entry.size;
+        self.volatile.remove(key);
+        Some(entry.obj)
+    }
+
+    /// Removes `key` and frees its value, on the background thread if `lazy` is set and the
+    /// value is large; see [`lazyfree::free`]. Returns whether the key was there.
+    pub fn delete(&mut self, key: &Bytes, lazy: bool) -> bool {
+        let Some(obj) = self.remove(key) else {
+            return false;
+        };
+        lazyfree::free(obj, lazy);
+        true
+    }
+
+    /// Claims `keys` for a write that must only happen if none of them exists, such as a
+    /// cross-shard `MSETNX` that first checks every shard involved. Reserves all of them and
+    /// returns `true` if none exists or is already reserved, and reserves nothing otherwise.
+    ///
+    /// A key written before the reservation is released supersedes it, see
+    /// [`DataStore::release`], so the claiming write is ordered before that one.
+    pub fn reserve(&mut self, keys: &[Bytes], now: UnixMillis) -> bool {
+        let taken = |key: &Bytes| {
+            self.reserved.contains(key) || self.get(key).is_some_and(|obj| !obj.is_expired(now))
+        };
+        if keys.iter().any(taken) {
+            return false;
+        }
+        self.reserved.extend(keys.iter().cloned());
+        true
+    }
+
+    /// Drops the reservation of `key` and returns whether it still held, i.e. whether the key
+    /// was not written since [`DataStore::reserve`].
+    pub fn release(&mut self, key: &Bytes) -> bool {
+        self.reserved.remove(key)
+    }
+
+    /// Empties the keyspace, freeing it on the background thread if `lazy` is set.
+    pub fn clear(&mut self, lazy: bool) {
+        if lazy {
+            lazyfree::free_all(std::mem::take(&mut self.entries));
+        } else {
+            self.entries.clear();
+        }
+        self.used = 0;
+        self.dirty.clear();
+        self.volatile.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &StoreObject)> {
+        self.entries.iter().map(|(key, entry)| (key, &entry.obj))
+    }
+
+    /// One step of a `SCAN` walk over the keys; see [`Dict::scan`].
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Bytes, &StoreObject)) -> u64 {
+        self.entries.scan(cursor, |key, entry| visit(key, &entry.obj))
+    }
+
+    /// One step of a walk over the keys that have a deadline or hash fields with one; see
+    /// [`Dict::scan`].
+    pub fn scan_volatile(&self, cursor: u64, mut visit: impl FnMut(&Bytes, &StoreObject)) -> u64 {
+        self.volatile.scan(cursor, |key, ()| {
+            if let Some(entry) = self.entries.get(key) {
+                visit(key, &entry.obj);
+            }
+        })
+    }
+
+    /// A key picked at random, expired or not; see [`Dict::random_entry`].
+    pub fn sample(&self) -> Option<(&Bytes, &StoreObject)> {
+        self.entries.random_entry().map(|(key, entry)| (key, &entry.obj))
+    }
+
+    /// A key picked at random among those with a deadline or hash fields with one, expired or
+    /// not; see [`Dict::random_entry`].
+    pub fn sample_volatile(&self) -> Option<(&Bytes, &StoreObject)> {
+        let (key, ()) = self.volatile.random_entry()?;
+        self.entries.get(key).map(|entry| (key, &entry.obj))
+    }
+
+    /// Moves the table along an ongoing resize for about `budget`; see [`Dict::rehash_for`].
+    pub fn rehash_for(&mut self, budget: Duration) {
+        self.entries.rehash_for(budget);
+    }
+
+    /// Lists and sorted sets stored since the last call, the only keys a blocked pop can be
+    /// waiting for: clients only block on keys that are missing, and collections are never
+    /// left empty, so a key gains data by being stored anew.
+    pub fn take_ready(&mut self) -> Vec<Bytes> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// Re-measures the values handed out by [`DataStore::get_mut`] since the last call, and
+    /// updates whether they carry a deadline.
+    pub fn settle(&mut self) {
+        for key in std::mem::take(&mut self.dirty) {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                let size = memory::entry_size(&key, &entry.obj.data, DEFAULT_SAMPLES);
+                self.used = self.used - entry.size + size;
+                entry.size = size;
+                track_volatile(&mut self.volatile, &key, &entry.obj);
+            }
+        }
+    }
+
+    /// Bytes held by `key` and its value, slot in the table included, measuring `samples`
+    /// elements of a collection (all of them with 0); see [`memory::value_size`].
+    pub fn usage(&self, key: &Bytes, samples: usize) -> Option<usize> {
+        let entry = self.entries.get(key)?;
+        Some(size_of::<(Bytes, Entry)>() + memory::entry_size(key, &entry.obj.data, samples))
+    }
+
+    /// Estimated bytes held by keys and values outside of the table.
+    pub fn dataset_size(&self) -> usize {
+        self.used
+    }
+
+    /// Bytes taken by the table itself, including the slot of every key, and by the index of
+    /// volatile keys; see [`Dict::table_size`].
+    pub fn overhead(&self) -> usize {
+        self.entries.table_size() + self.volatile.table_size()
+    }
+
+    /// Estimated bytes held by the keyspace, table included.
+    pub fn used_memory(&self) -> usize {
+        self.dataset_size() + self.overhead()
+    }
+}
+
+/// Adds `key` to the volatile index if `obj` or one of its hash fields has a deadline, and
+/// drops it from the index otherwise.
+fn track_volatile(volatile: &mut Dict<Bytes, ()>, key: &Bytes, obj: &StoreObject) {
+    let has_deadline = obj.deadline().is_some()
+        || matches!(&obj.data, DataKind::Hash(hash) if hash.volatile_len() > 0);
+    if !has_deadline {
+        volatile.remove(key);
+    } else if !volatile.contains_key(key) {
+        volatile.insert(key.clone(), ());
+    }
+}
+
+/// Looks up a key, dropping it first if its TTL has already passed. A hit counts as an access.
+pub fn lookup_mut<'a>(
+    db: &'a mut DataStore,
+    key: &Bytes,
+    now: UnixMillis,
+) -> Option<&'a mut StoreObject> {
+    if db.get(key).is_some_and(|obj| obj.is_expired(now)) {
+        db.delete(key, CONFIG.lazyfree_lazy_expire());
+        return None;
+    }
+    let obj = db.get_mut(key)?;
+    obj.touch(now);
+    Some(obj)
+}
+
+/// Like [`lookup_mut`], but for commands that only inspect the key, such as `EXISTS` and `TYPE`;
+/// the access time is left alone.
+pub fn lookup<'a>(db: &'a mut DataStore, key: &Bytes, now: UnixMillis) -> Option<&'a StoreObject> {
+    if db.get(key).is_some_and(|obj| obj.is_expired(now)) {
+        db.delete(key, CONFIG.lazyfree_lazy_expire());
+        return None;
+    }
+    db.get(key)
+}
+
+#[derive(Clone, Debug)]
 pub struct StoreObject {
     pub data: DataKind,
-    pub ttl: Option<Duration>,
-    pub last_accessed: Instant,
-    pub created_at: Instant,
+    /// Unix time at which the key stops being visible, if it has a TTL.
+    pub expires_at: Option<UnixMillis>,
+    /// TTL restarted by every access, making `expires_at` a sliding deadline.
+    pub sliding_ttl: Option<u64>,
+    /// Milliseconds without access after which the key expires, on top of any TTL.
+    pub idle_timeout: Option<u64>,
+    pub last_accessed: UnixMillis,
+    /// Access frequency, for the LFU eviction policies.
+    pub lfu: LfuCounter,
+}
+
+impl StoreObject {
+    pub fn new(data: DataKind, now: UnixMillis) -> Self {
+        Self {
+            data,
+            expires_at: None,
+            sliding_ttl: None,
+            idle_timeout: None,
+            last_accessed: now,
+            lfu: LfuCounter::new(now),
+        }
+    }
+
+    /// When the key expires: the earlier of its TTL and the end of its idle timeout.
+    pub fn deadline(&self) -> Option<UnixMillis> {
+        let idle_deadline = self.idle_timeout.map(|idle| self.last_accessed.saturating_add(idle));
+        match (self.expires_at, idle_deadline) {
+            (Some(ttl), Some(idle)) => Some(ttl.min(idle)),
+            (ttl, idle) => ttl.or(idle),
+        }
+    }
+
+    pub fn is_expired(&self, now: UnixMillis) -> bool {
+        self.deadline().is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Records an access, which restarts the idle timeout and any sliding TTL and counts
+    /// towards the key's LFU frequency.
+    pub fn touch(&mut self, now: UnixMillis) {
+        self.last_accessed = now;
+        self.lfu.hit(now);
+        if let Some(ttl) = self.sliding_ttl {
+            self.expires_at = Some(now.saturating_add(ttl));
+        }
+    }
+
+    /// Gives the key a fixed deadline, or none, replacing any sliding TTL.
+    pub fn set_expires_at(&mut self, deadline: Option<UnixMillis>) {
+        self.expires_at = deadline;
+        self.sliding_ttl = None;
+    }
+
+    /// Gives the key a TTL of `ttl` milliseconds that restarts on every access.
+    pub fn set_sliding_ttl(&mut self, ttl: u64, now: UnixMillis) {
+        self.expires_at = Some(now.saturating_add(ttl));
+        self.sliding_ttl = Some(ttl);
+    }
+
+    /// Drops the TTL, sliding or not, and the idle timeout. Returns `true` if there was any.
+    pub fn persist(&mut self) -> bool {
+        let had_expiry = self.deadline().is_some();
+        self.expires_at = None;
+        self.sliding_ttl = None;
+        self.idle_timeout = None;
+        had_expiry
+    }
 }
-#[derive(Debug, Display)]
+
+#[derive(Clone, Debug, Display)]
 pub enum DataKind {
-    /// Simple text or small values
-    String(Bytes),
     /// Binary-safe value
     BulkString(Bytes),
-    /// Hash (field -> value)
-    Hash(HashMap<Bytes, Bytes>),
-    /// List of values
-    List(Vec<DataKind>),
+    /// String value that is a canonical 64-bit integer, kept decoded for counters
+    Int(i64),
+    /// Hash (field -> value), with optional per-field expiry
+    Hash(HashValue),
+    /// List of byte strings
+    List(QuickList),
     /// Set of unique values
-    Set(HashSet<Bytes>),
-    /// Sorted Set (score -> member)
-    SortedSet(BTreeMap<f64, Bytes>),
+    Set(SetValue),
+    /// Sorted Set (member -> score, ordered by score)
+    SortedSet(SortedSet),
 }
 
 impl DataKind {
-    pub fn to_bytes_frame(&self) -> BytesFrame {
+    /// Builds a string value, using the integer encoding when the bytes round-trip through
+    /// an `i64`.
+    pub fn from_string(bytes: Bytes) -> Self {
+        match parse_i64(&bytes) {
+            Some(value) => DataKind::Int(value),
+            None => DataKind::BulkString(bytes),
+        }
+    }
+
+    /// Type name reported by `TYPE`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataKind::BulkString(_) | DataKind::Int(_) => "string",
+            DataKind::Hash(_) => "hash",
+            DataKind::List(_) => "list",
+            DataKind::Set(_) => "set",
+            DataKind::SortedSet(_) => "zset",
+        }
+    }
+
+    /// Internal representation reported by `OBJECT ENCODING`, named as in Redis. Strings are a
+    /// single buffer either way; the `embstr`/`raw` split follows Redis' 44-byte threshold so
+    /// that tooling written against Redis reads them the same.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            DataKind::Int(_) => "int",
+            DataKind::BulkString(bytes) if bytes.len() <= 44 => "embstr",
+            DataKind::BulkString(_) => "raw",
+            DataKind::Hash(hash) => hash.encoding(),
+            DataKind::Set(set) => set.encoding(),
+            DataKind::List(_) => "quicklist",
+            DataKind::SortedSet(zset) => zset.encoding(),
+        }
+    }
+
+    /// Raw bytes of a string value, or `None` if this is not a string type.
+    pub fn string_bytes(&self) -> Option<Bytes> {
         match self {
-            DataKind::String(s) => {
-                BytesFrame::SimpleString { data: s.clone().into(), attributes: None }
+            DataKind::BulkString(bytes) => Some(bytes.clone()),
+            DataKind::Int(value) => Some(Bytes::from(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// Fields of a hash together with the deadlines of those that have a TTL.
+///
+/// Small hashes keep fields and values alternating in a [`ListPack`], and convert to a hash
+/// table for good once they outgrow `hash-max-listpack-entries` or get a field or value longer
+/// than `hash-max-listpack-value`.
+///
+/// Expired fields are only dropped by [`HashValue::expire_fields`]; callers reading the hash
+/// are expected to run it first so that they never observe a field past its deadline. The
+/// deadlines are also kept in order, so that costs a lookup of the earliest one unless some
+/// field did expire.
+#[derive(Clone, Debug, Default)]
+pub struct HashValue {
+    fields: HashEncoding,
+    expires: HashMap<Bytes, UnixMillis>,
+    deadlines: BTreeSet<(UnixMillis, Bytes)>,
+}
+
+#[derive(Clone, Debug)]
+enum HashEncoding {
+    ListPack(ListPack),
+    HashTable(Dict<Bytes, Bytes>),
+}
+
+impl Default for HashEncoding {
+    fn default() -> Self {
+        HashEncoding::ListPack(ListPack::default())
+    }
+}
+
+impl HashValue {
+    pub fn len(&self) -> usize {
+        match &self.fields {
+            HashEncoding::ListPack(pack) => pack.len() / 2,
+            HashEncoding::HashTable(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the encoding, as reported by `OBJECT ENCODING`.
+    pub fn encoding(&self) -> &'static str {
+        match &self.fields {
+            HashEncoding::ListPack(_) => "listpack",
+            HashEncoding::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn get(&self, field: &Bytes) -> Option<Bytes> {
+        match &self.fields {
+            HashEncoding::ListPack(pack) => pack
+                .pairs()
+                .find(|((_, f), _)| *f == &field[..])
+                .map(|(_, (_, value))| Bytes::copy_from_slice(value)),
+            HashEncoding::HashTable(table) => table.get(field).cloned(),
+        }
+    }
+
+    pub fn contains_key(&self, field: &Bytes) -> bool {
+        match &self.fields {
+            HashEncoding::ListPack(pack) => pack.pairs().any(|((_, f), _)| f == &field[..]),
+            HashEncoding::HashTable(table) => table.contains_key(field),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Bytes, Bytes)> + '_> {
+        match &self.fields {
+            HashEncoding::ListPack(pack) => {
+                Box::new(pack.pairs().map(|((_, field), (_, value))| {
+                    (Bytes::copy_from_slice(field), Bytes::copy_from_slice(value))
+                }))
+            }
+            HashEncoding::HashTable(table) => {
+                Box::new(table.iter().map(|(field, value)| (field.clone(), value.clone())))
             }
+        }
+    }
 
-            DataKind::BulkString(bytes) => {
-                BytesFrame::BlobString { data: bytes.clone().into(), attributes: None }
+    pub fn keys(&self) -> impl Iterator<Item = Bytes> {
+        self.iter().map(|(field, _)| field)
+    }
+
+    /// A field and its value picked at random, without walking a hash table.
+    pub fn random(&self) -> Option<(Bytes, Bytes)> {
+        match &self.fields {
+            HashEncoding::ListPack(pack) => {
+                let index = rand::rng().random_range(0..self.len().max(1));
+                pack.pairs().nth(index).map(|((_, field), (_, value))| {
+                    (Bytes::copy_from_slice(field), Bytes::copy_from_slice(value))
+                })
             }
+            HashEncoding::HashTable(table) => {
+                table.random_entry().map(|(field, value)| (field.clone(), value.clone()))
+            }
+        }
+    }
 
-            DataKind::Hash(hash_map) => {
-                let mut frames = Vec::with_capacity(hash_map.len() * 2);
-                for (k, v) in hash_map {
-                    frames.push(BytesFrame::BlobString { data: k.clone(), attributes: None });
-                    frames.push(BytesFrame::BlobString { data: v.clone(), attributes: None });
-                }
-                BytesFrame::Array { data: frames, attributes: None }
+    pub fn values(&self) -> impl Iterator<Item = Bytes> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Sets a field, discarding any TTL it had like `HSET` does. Returns `true` if the field is
+    /// new.
+    pub fn insert(&mut self, field: Bytes, value: Bytes) -> bool {
+        self.persist(&field);
+        self.set(field, value)
+    }
+
+    /// Overwrites a field's value but keeps its TTL, for in-place updates such as `HINCRBY`.
+    pub fn update(&mut self, field: Bytes, value: Bytes) {
+        self.set(field, value);
+    }
+
+    pub fn remove(&mut self, field: &Bytes) -> bool {
+        self.persist(field);
+        match &mut self.fields {
+            HashEncoding::ListPack(pack) => {
+                let found = pack.pairs().find(|((_, f), _)| *f == &field[..]);
+                let Some(((offset, _), _)) = found else {
+                    return false;
+                };
+                pack.remove(offset, 2);
+                true
             }
+            HashEncoding::HashTable(table) => table.remove(field).is_some(),
+        }
+    }
 
-            DataKind::List(data_kinds) => {
-                let mut frames = Vec::with_capacity(data_kinds.len());
+    /// Heap bytes held by the hash; see [`memory::value_size`].
+    pub fn heap_size(&self, samples: usize) -> usize {
+        let deadlines = self.expires.capacity() * (size_of::<(Bytes, UnixMillis)>() + 1)
+            + self.deadlines.len() * size_of::<(UnixMillis, Bytes)>();
+        let fields = match &self.fields {
+            HashEncoding::ListPack(pack) => pack.heap_size(),
+            HashEncoding::HashTable(table) => {
+                table.table_size()
+                    + memory::sampled(table.iter(), table.len(), samples, |(field, value)| {
+                        field.len() + value.len()
+                    })
+            }
+        };
+        deadlines + fields
+    }
 
-                for dk in data_kinds {
-                    frames.push(dk.to_bytes_frame());
-                }
-                BytesFrame::Array { data: frames, attributes: None }
+    /// One step of an `HSCAN` walk over the fields; see [`Dict::scan`]. A listpack is reported
+    /// whole in a single step, as in Redis.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Bytes, &Bytes)) -> u64 {
+        match &self.fields {
+            HashEncoding::ListPack(_) => {
+                self.iter().for_each(|(field, value)| visit(&field, &value));
+                0
             }
+            HashEncoding::HashTable(table) => table.scan(cursor, visit),
+        }
+    }
 
-            DataKind::Set(hash_set) => {
-                let mut frames = Vec::with_capacity(hash_set.len());
+    /// Deadline of `field`, if it has one.
+    pub fn expires_at(&self, field: &Bytes) -> Option<UnixMillis> {
+        self.expires.get(field).copied()
+    }
 
-                for item in hash_set {
-                    frames.push(BytesFrame::SimpleString {
-                        data: item.clone().into(),
-                        attributes: None,
-                    });
-                }
-                BytesFrame::Array { data: frames, attributes: None }
-            }
-
-            DataKind::SortedSet(btree_map) => {
-                let mut frames = Vec::with_capacity(btree_map.len() * 2);
-                for (score, member) in btree_map {
-                    frames.push(BytesFrame::SimpleString {
-                        data: score.to_string().into(),
-                        attributes: None,
-                    });
-                    frames.push(BytesFrame::SimpleString {
-                        data: member.clone().into(),
-                        attributes: None,
-                    });
+    /// Gives an existing field a deadline. Fields that are not present are ignored.
+    pub fn set_expires_at(&mut self, field: &Bytes, deadline: UnixMillis) {
+        if self.contains_key(field) {
+            if let Some(previous) = self.expires.insert(field.clone(), deadline) {
+                self.deadlines.remove(&(previous, field.clone()));
+            }
+            self.deadlines.insert((deadline, field.clone()));
+        }
+    }
+
+    /// Clears the TTL of `field`; returns `true` if it had one.
+    pub fn persist(&mut self, field: &Bytes) -> bool {
+        let Some(deadline) = self.expires.remove(field) else {
+            return false;
+        };
+        self.deadlines.remove(&(deadline, field.clone()));
+        true
+    }
+
+    /// Number of fields carrying a TTL.
+    pub fn volatile_len(&self) -> usize {
+        self.expires.len()
+    }
+
+    /// Drops every field whose deadline has passed and returns how many were removed.
+    pub fn expire_fields(&mut self, now: UnixMillis) -> usize {
+        let mut expired = 0;
+        while let Some((_, field)) = self.deadlines.first().filter(|(deadline, _)| now >= *deadline)
+        {
+            let field = field.clone();
+            self.remove(&field);
+            expired += 1;
+        }
+        expired
+    }
+
+    /// Sets `field` to `value`, converting to a hash table first if the listpack would outgrow
+    /// its limits. Returns `true` if the field is new.
+    fn set(&mut self, field: Bytes, value: Bytes) -> bool {
+        if let HashEncoding::ListPack(pack) = &mut self.fields {
+            let max_value = CONFIG.hash_max_listpack_value();
+            if field.len() <= max_value && value.len() <= max_value {
+                let found = pack.pairs().find(|((_, f), _)| *f == &field[..]);
+                if let Some((_, (offset, _))) = found {
+                    pack.replace(offset, &value);
+                    return false;
                 }
-                BytesFrame::Array { data: frames, attributes: None }
-            }
-        }
-    }
-    pub fn from_bytes_frame(frame: &BytesFrame) -> Option<Self> {
-        match frame {
-            BytesFrame::SimpleString { data, .. } => Some(DataKind::String(data.clone())),
-            BytesFrame::BlobString { data, .. } => Some(DataKind::BulkString(data.clone())),
-            BytesFrame::Array { data, .. } => {
-                // Heuristic: try to guess the type of array:
-                // 1. Even length & all key/value frames -> Hash or SortedSet
-                // 2. Otherwise -> List
-                // Note: This depends on your encoding convention
-
-                // Try Hash: even length, all pairs are SimpleString
-                if data.len() % 2 == 0
-                    && data.iter().all(|f| matches!(f, BytesFrame::SimpleString { .. }))
-                {
-                    let mut hash_map = HashMap::with_capacity(data.len() / 2);
-                    let mut iter = data.iter();
-                    while let (Some(kf), Some(vf)) = (iter.next(), iter.next()) {
-                        if let (
-                            BytesFrame::SimpleString { data: k, .. },
-                            BytesFrame::SimpleString { data: v, .. },
-                        ) = (kf, vf)
-                        {
-                            hash_map.insert(k.clone(), v.clone());
-                        } else {
-                            return None;
-                        }
-                    }
-                    return Some(DataKind::Hash(hash_map));
+                if pack.len() / 2 < CONFIG.hash_max_listpack_entries() {
+                    pack.insert(pack.end(), &[&field, &value]);
+                    return true;
                 }
+            }
+            let table = pack
+                .pairs()
+                .map(|((_, f), (_, v))| (Bytes::copy_from_slice(f), Bytes::copy_from_slice(v)))
+                .collect();
+            self.fields = HashEncoding::HashTable(table);
+        }
+        let HashEncoding::HashTable(table) = &mut self.fields else {
+            unreachable!("converted above");
+        };
+        table.insert(field, value).is_none()
+    }
+}
 
-                // Otherwise, treat as List
-                let mut list = Vec::with_capacity(data.len());
-                for item in data {
-                    list.push(DataKind::from_bytes_frame(item)?);
-                }
-                Some(DataKind::List(list))
+/// Members of a set.
+///
+/// Sets of integers only are kept in an [`IntSet`] up to `set-max-intset-entries` members,
+/// other small sets in a [`ListPack`] up to `set-max-listpack-entries` members no longer than
+/// `set-max-listpack-value`. Past those limits a set converts to a hash table for good.
+#[derive(Clone, Debug, Default)]
+pub struct SetValue {
+    members: SetEncoding,
+}
+
+#[derive(Clone, Debug)]
+enum SetEncoding {
+    IntSet(IntSet),
+    ListPack(ListPack),
+    HashTable(Dict<Bytes, ()>),
+}
+
+impl Default for SetEncoding {
+    fn default() -> Self {
+        SetEncoding::IntSet(IntSet::default())
+    }
+}
+
+impl SetValue {
+    pub fn len(&self) -> usize {
+        match &self.members {
+            SetEncoding::IntSet(ints) => ints.len(),
+            SetEncoding::ListPack(pack) => pack.len(),
+            SetEncoding::HashTable(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the encoding, as reported by `OBJECT ENCODING`.
+    pub fn encoding(&self) -> &'static str {
+        match &self.members {
+            SetEncoding::IntSet(_) => "intset",
+            SetEncoding::ListPack(_) => "listpack",
+            SetEncoding::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn contains(&self, member: &Bytes) -> bool {
+        match &self.members {
+            SetEncoding::IntSet(ints) => {
+                parse_i64(member).is_some_and(|value| ints.contains(value))
             }
-            _ => None,
+            SetEncoding::ListPack(pack) => pack.iter().any(|m| m == &member[..]),
+            SetEncoding::HashTable(table) => table.contains_key(member),
+        }
+    }
+
+    /// Adds `member`; returns `true` if it was not present yet.
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        if let SetEncoding::IntSet(ints) = &mut self.members {
+            let len = ints.len();
+            match parse_i64(&member) {
+                Some(value) if len < CONFIG.set_max_intset_entries() => return ints.insert(value),
+                Some(value) if ints.contains(value) => return false,
+                _ => self.convert(len + 1, member.len()),
+            }
+        }
+        if let SetEncoding::ListPack(pack) = &mut self.members {
+            if pack.iter().any(|m| m == &member[..]) {
+                return false;
+            }
+            if pack.len() < CONFIG.set_max_listpack_entries()
+                && member.len() <= CONFIG.set_max_listpack_value()
+            {
+                pack.push(&member);
+                return true;
+            }
+            self.convert(usize::MAX, member.len());
+        }
+        let SetEncoding::HashTable(table) = &mut self.members else {
+            unreachable!("converted above");
+        };
+        table.insert(member, ()).is_none()
+    }
+
+    pub fn remove(&mut self, member: &Bytes) -> bool {
+        match &mut self.members {
+            SetEncoding::IntSet(ints) => parse_i64(member).is_some_and(|value| ints.remove(value)),
+            SetEncoding::ListPack(pack) => {
+                let Some((offset, _)) = pack.entries().find(|(_, m)| *m == &member[..]) else {
+                    return false;
+                };
+                pack.remove(offset, 1);
+                true
+            }
+            SetEncoding::HashTable(table) => table.remove(member).is_some(),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Bytes> + '_> {
+        match &self.members {
+            SetEncoding::IntSet(ints) => Box::new(ints.iter().map(|v| Bytes::from(v.to_string()))),
+            SetEncoding::ListPack(pack) => Box::new(pack.iter().map(Bytes::copy_from_slice)),
+            SetEncoding::HashTable(table) => Box::new(table.iter().map(|(m, _)| m.clone())),
+        }
+    }
+
+    /// A member picked at random, without walking a hash table.
+    pub fn random(&self) -> Option<Bytes> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = rand::rng().random_range(0..self.len());
+        match &self.members {
+            SetEncoding::IntSet(ints) => Some(Bytes::from(ints.get(index).to_string())),
+            SetEncoding::ListPack(pack) => pack.iter().nth(index).map(Bytes::copy_from_slice),
+            SetEncoding::HashTable(table) => table.random_entry().map(|(member, _)| member.clone()),
+        }
+    }
+
+    /// Heap bytes held by the set; see [`memory::value_size`].
+    pub fn heap_size(&self, samples: usize) -> usize {
+        match &self.members {
+            SetEncoding::IntSet(ints) => ints.heap_size(),
+            SetEncoding::ListPack(pack) => pack.heap_size(),
+            SetEncoding::HashTable(table) => {
+                table.table_size()
+                    + memory::sampled(table.iter(), table.len(), samples, |(m, _)| m.len())
+            }
+        }
+    }
+
+    /// One step of an `SSCAN` walk over the members; see [`Dict::scan`]. Intsets and listpacks
+    /// are reported whole in a single step, as in Redis.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Bytes)) -> u64 {
+        match &self.members {
+            SetEncoding::HashTable(table) => table.scan(cursor, |member, _| visit(member)),
+            _ => {
+                self.iter().for_each(|member| visit(&member));
+                0
+            }
+        }
+    }
+
+    /// Moves the members out of an intset into whichever encoding fits `len` members with one
+    /// as long as `member_len`, or out of a listpack into a hash table.
+    fn convert(&mut self, len: usize, member_len: usize) {
+        let members: Vec<Bytes> = self.iter().collect();
+        let fits_listpack = matches!(self.members, SetEncoding::IntSet(_))
+            && len <= CONFIG.set_max_listpack_entries()
+            && member_len <= CONFIG.set_max_listpack_value();
+        self.members = if fits_listpack {
+            let mut pack = ListPack::default();
+            members.iter().for_each(|member| pack.push(member));
+            SetEncoding::ListPack(pack)
+        } else {
+            SetEncoding::HashTable(members.into_iter().map(|member| (member, ())).collect())
+        };
+    }
+}
+
+impl IntoIterator for SetValue {
+    type Item = Bytes;
+    type IntoIter = Box<dyn Iterator<Item = Bytes>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self.members {
+            SetEncoding::HashTable(table) => Box::new(table.into_iter().map(|(member, ())| member)),
+            _ => Box::new(self.iter().collect::<Vec<_>>().into_iter()),
         }
     }
 }
 
-// impl From<DataKind> for Bytes {
-//     fn from(value: DataKind) -> Self {
-//         match value {
-//             DataKind::String(s) => Bytes::from(s),
-//             DataKind::BulkString(bs) => Bytes::from(bs),
-
-//             DataKind::Hash(hash) => {
-//                 // serialize as key=value\n
-//                 let mut buf = String::new();
-//                 for (k, v) in hash {
-//                     buf.push_str(&k);
-//                     buf.push('=');
-//                     buf.push_str(&v);
-//                     buf.push('\n');
-//                 }
-//                 Bytes::from(buf)
-//             }
-
-//             DataKind::List(list) => {
-//                 // recursively convert each element to bytes and join
-//                 let mut buf = Vec::new();
-//                 for (i, item) in list.into_iter().enumerate() {
-//                     if i > 0 {
-//                         buf.extend_from_slice(b"\n");
-//                     }
-//                     let part: Bytes = item.into();
-//                     buf.extend_from_slice(&part);
-//                 }
-//                 Bytes::from(buf)
-//             }
-
-//             DataKind::Set(hash_set) => {
-//                 // join by newline
-//                 let mut buf = String::new();
-//                 for (i, val) in hash_set.into_iter().enumerate() {
-//                     if i > 0 {
-//                         buf.push('\n');
-//                     }
-//                     buf.push_str(&val);
-//                 }
-//                 Bytes::from(buf)
-//             }
-
-//             DataKind::SortedSet(btree_map) => {
-//                 // format as score:member\n
-//                 let mut buf = String::new();
-//                 for (score, member) in btree_map {
-//                     buf.push_str(&format!("{}:{}\n", score, member));
-//                 }
-//                 Bytes::from(buf)
-//             }
-//         }
-//     }
-// }
+impl FromIterator<Bytes> for SetValue {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut set = SetValue::default();
+        for member in iter {
+            set.insert(member);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+
+    use super::{HashValue, SetValue};
+
+    fn numbered(prefix: &str, count: usize) -> Vec<Bytes> {
+        (0..count).map(|i| Bytes::from(format!("{prefix}{i}"))).collect()
+    }
+
+    fn long() -> Bytes {
+        Bytes::from("x".repeat(65))
+    }
+
+    #[test]
+    fn hashes_convert_past_the_listpack_entry_count() {
+        let fields = numbered("f", 129);
+        let mut hash = HashValue::default();
+        for field in &fields[..128] {
+            assert!(hash.insert(field.clone(), "v".into()));
+        }
+        assert!(!hash.insert(fields[0].clone(), "updated".into()));
+        assert_eq!(hash.encoding(), "listpack");
+
+        assert!(hash.insert(fields[128].clone(), "v".into()));
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.len(), 129);
+        assert_eq!(hash.get(&fields[0]), Some("updated".into()));
+        assert!(fields.iter().all(|field| hash.contains_key(field)));
+
+        // Converting is for good.
+        for field in &fields[1..] {
+            assert!(hash.remove(field));
+        }
+        assert_eq!(hash.encoding(), "hashtable");
+    }
+
+    #[test]
+    fn hashes_convert_on_a_long_field_or_value() {
+        let mut by_value = HashValue::default();
+        by_value.insert("f".into(), "v".into());
+        assert!(!by_value.insert("f".into(), long()));
+        assert_eq!(by_value.encoding(), "hashtable");
+        assert_eq!(by_value.get(&"f".into()), Some(long()));
+
+        let mut by_field = HashValue::default();
+        by_field.insert("f".into(), "v".into());
+        assert!(by_field.insert(long(), "v".into()));
+        assert_eq!(by_field.encoding(), "hashtable");
+        assert_eq!(by_field.len(), 2);
+    }
+
+    #[test]
+    fn integer_sets_convert_past_the_intset_entry_count() {
+        let members: Vec<Bytes> = (0..513).map(|i| Bytes::from(i.to_string())).collect();
+        let mut set: SetValue = members[..512].iter().cloned().collect();
+        assert_eq!(set.encoding(), "intset");
+        assert!(!set.insert(members[0].clone()), "a duplicate does not convert");
+        assert_eq!(set.encoding(), "intset");
+
+        // Too many members for a listpack as well.
+        assert!(set.insert(members[512].clone()));
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.iter().collect::<HashSet<_>>(), members.into_iter().collect());
+    }
+
+    #[test]
+    fn integer_sets_take_the_smallest_encoding_fitting_a_string_member() {
+        let mut small: SetValue = numbered("", 10).into_iter().collect();
+        assert!(small.insert("a".into()));
+        assert_eq!(small.encoding(), "listpack");
+        assert_eq!(small.len(), 11);
+        assert!(small.contains(&"5".into()) && small.contains(&"a".into()));
+
+        let mut large: SetValue = numbered("", 200).into_iter().collect();
+        assert!(large.insert("a".into()));
+        assert_eq!(large.encoding(), "hashtable", "200 members do not fit a listpack");
+
+        let mut long_member: SetValue = numbered("", 10).into_iter().collect();
+        assert!(long_member.insert(long()));
+        assert_eq!(long_member.encoding(), "hashtable");
+        assert_eq!(long_member.len(), 11);
+    }
+
+    #[test]
+    fn listpack_sets_convert_past_their_entry_count_or_on_a_long_member() {
+        let members = numbered("m", 129);
+        let mut set: SetValue = members[..128].iter().cloned().collect();
+        assert_eq!(set.encoding(), "listpack");
+        assert!(set.insert(members[128].clone()));
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.iter().collect::<HashSet<_>>(), members.into_iter().collect());
+
+        let mut set: SetValue = numbered("m", 3).into_iter().collect();
+        assert!(set.insert(long()));
+        assert_eq!(set.encoding(), "hashtable");
+        assert!(set.contains(&long()) && set.contains(&"m0".into()));
+    }
+}
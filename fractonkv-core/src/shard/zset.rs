@@ -0,0 +1,391 @@
+use bytes::Bytes;
+use rand::Rng;
+
+use crate::config::CONFIG;
+use crate::shard::dict::Dict;
+use crate::shard::listpack::ListPack;
+use crate::shard::memory;
+use crate::shard::skiplist::SkipList;
+
+/// Sorted set value.
+///
+/// Small sorted sets keep members and their scores alternating in a [`ListPack`], ordered by
+/// `(score, member)`. Past `zset-max-listpack-entries` members, or once a member is longer than
+/// `zset-max-listpack-value`, the set converts for good to a member → score dictionary for
+/// O(1) score lookups plus a skiplist ordered by `(score, member)` for ranks and ranges. Both
+/// always hold the same members.
+#[derive(Clone, Debug, Default)]
+pub struct SortedSet {
+    members: ZsetEncoding,
+}
+
+#[derive(Clone, Debug)]
+enum ZsetEncoding {
+    ListPack(ListPack),
+    SkipList { scores: Dict<Bytes, f64>, index: SkipList },
+}
+
+impl Default for ZsetEncoding {
+    fn default() -> Self {
+        ZsetEncoding::ListPack(ListPack::default())
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => pack.len() / 2,
+            ZsetEncoding::SkipList { scores, .. } => scores.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the encoding, as reported by `OBJECT ENCODING`.
+    pub fn encoding(&self) -> &'static str {
+        match &self.members {
+            ZsetEncoding::ListPack(_) => "listpack",
+            ZsetEncoding::SkipList { .. } => "skiplist",
+        }
+    }
+
+    pub fn score(&self, member: &Bytes) -> Option<f64> {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => {
+                packed(pack).find(|(_, m, _)| *m == &member[..]).map(|(_, _, score)| score)
+            }
+            ZsetEncoding::SkipList { scores, .. } => scores.get(member).copied(),
+        }
+    }
+
+    /// Adds `member` or moves it to `score`. Returns whether the member is new.
+    pub fn insert(&mut self, member: Bytes, score: f64) -> bool {
+        if let ZsetEncoding::ListPack(pack) = &mut self.members {
+            if member.len() <= CONFIG.zset_max_listpack_value() {
+                let found = packed(pack).find(|(_, m, _)| *m == &member[..]);
+                let is_new = match found {
+                    Some((_, _, old)) if old == score => return false,
+                    Some((offset, _, _)) => {
+                        pack.remove(offset, 2);
+                        false
+                    }
+                    None => true,
+                };
+                if !is_new || pack.len() / 2 < CONFIG.zset_max_listpack_entries() {
+                    let offset = packed(pack)
+                        .find(|(_, m, s)| *s > score || (*s == score && *m > &member[..]))
+                        .map_or(pack.end(), |(offset, _, _)| offset);
+                    pack.insert(offset, &[&member, &score.to_le_bytes()]);
+                    return is_new;
+                }
+            }
+            self.convert();
+        }
+        let ZsetEncoding::SkipList { scores, index } = &mut self.members else {
+            unreachable!("converted above");
+        };
+        match scores.insert(member.clone(), score) {
+            Some(old) if old == score => false,
+            Some(old) => {
+                index.remove(old, &member);
+                index.insert(score, member);
+                false
+            }
+            None => {
+                index.insert(score, member);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &Bytes) -> bool {
+        match &mut self.members {
+            ZsetEncoding::ListPack(pack) => {
+                let found = packed(pack).find(|(_, m, _)| *m == &member[..]);
+                let Some((offset, _, _)) = found else {
+                    return false;
+                };
+                pack.remove(offset, 2);
+                true
+            }
+            ZsetEncoding::SkipList { scores, index } => match scores.remove(member) {
+                Some(score) => index.remove(score, member),
+                None => false,
+            },
+        }
+    }
+
+    /// 0-based rank of `member`, lowest score first.
+    pub fn rank(&self, member: &Bytes) -> Option<usize> {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => packed(pack).position(|(_, m, _)| m == &member[..]),
+            ZsetEncoding::SkipList { index, .. } => {
+                let score = self.score(member)?;
+                Some(index.partition_point(|s, m| s < score || (s == score && m < member)))
+            }
+        }
+    }
+
+    /// Number of leading members, in `(score, member)` order, for which `pred` holds. `pred`
+    /// must hold up to some point and for nothing after it.
+    pub fn count_while(&self, pred: impl Fn(f64, &Bytes) -> bool) -> usize {
+        match &self.members {
+            ZsetEncoding::ListPack(_) => {
+                self.iter().take_while(|(member, score)| pred(*score, member)).count()
+            }
+            ZsetEncoding::SkipList { index, .. } => index.partition_point(pred),
+        }
+    }
+
+    /// One step of a `ZSCAN` walk over the members, in no particular order; see [`Dict::scan`].
+    /// A listpack is reported whole in a single step, as in Redis.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Bytes, f64)) -> u64 {
+        match &self.members {
+            ZsetEncoding::ListPack(_) => {
+                self.iter().for_each(|(member, score)| visit(&member, score));
+                0
+            }
+            ZsetEncoding::SkipList { scores, .. } => {
+                scores.scan(cursor, |member, &score| visit(member, score))
+            }
+        }
+    }
+
+    /// Heap bytes held by the sorted set; see
+    /// [`memory::value_size`](crate::shard::memory::value_size). Members are shared between the
+    /// dictionary and the skiplist, so they are counted once.
+    pub fn heap_size(&self, samples: usize) -> usize {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => pack.heap_size(),
+            ZsetEncoding::SkipList { scores, index } => {
+                scores.table_size()
+                    + index.heap_size()
+                    + memory::sampled(scores.iter(), scores.len(), samples, |(member, _)| {
+                        member.len()
+                    })
+            }
+        }
+    }
+
+    /// A member and its score picked at random, without walking the dictionary.
+    pub fn random(&self) -> Option<(Bytes, f64)> {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => {
+                let index = rand::rng().random_range(0..self.len().max(1));
+                packed(pack)
+                    .nth(index)
+                    .map(|(_, member, score)| (Bytes::copy_from_slice(member), score))
+            }
+            ZsetEncoding::SkipList { scores, .. } => {
+                scores.random_entry().map(|(member, score)| (member.clone(), *score))
+            }
+        }
+    }
+
+    /// Members with their scores, lowest score first.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Bytes, f64)> + '_> {
+        match &self.members {
+            ZsetEncoding::ListPack(pack) => Box::new(
+                packed(pack).map(|(_, member, score)| (Bytes::copy_from_slice(member), score)),
+            ),
+            ZsetEncoding::SkipList { index, .. } => {
+                Box::new(index.iter_from(0).map(|(member, score)| (member.clone(), score)))
+            }
+        }
+    }
+
+    /// Members from 0-based rank `start` through `end` (inclusive), lowest score first.
+    pub fn range(&self, start: usize, end: usize) -> Box<dyn Iterator<Item = (Bytes, f64)> + '_> {
+        let count = (end + 1).saturating_sub(start);
+        match &self.members {
+            ZsetEncoding::ListPack(_) => Box::new(self.iter().skip(start).take(count)),
+            ZsetEncoding::SkipList { index, .. } => Box::new(
+                index
+                    .iter_from(start)
+                    .take(count)
+                    .map(|(member, score)| (member.clone(), score)),
+            ),
+        }
+    }
+
+    /// Members from 0-based rank `end` back through `start` (inclusive), highest score first.
+    pub fn range_rev(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Box<dyn Iterator<Item = (Bytes, f64)> + '_> {
+        let count = (end + 1).saturating_sub(start);
+        match &self.members {
+            ZsetEncoding::ListPack(_) => {
+                let range: Vec<_> = self.range(start, end).collect();
+                Box::new(range.into_iter().rev())
+            }
+            ZsetEncoding::SkipList { index, .. } => Box::new(
+                index
+                    .iter_back_from(end)
+                    .take(count)
+                    .map(|(member, score)| (member.clone(), score)),
+            ),
+        }
+    }
+
+    /// Removes the members ranked `start` through `end` (inclusive); returns how many.
+    pub fn remove_range(&mut self, start: usize, end: usize) -> usize {
+        let doomed: Vec<Bytes> = self.range(start, end).map(|(member, _)| member).collect();
+        for member in &doomed {
+            self.remove(member);
+        }
+        doomed.len()
+    }
+
+    /// Removes and returns the member with the lowest score.
+    pub fn pop_min(&mut self) -> Option<(Bytes, f64)> {
+        let (member, score) = self.iter().next()?;
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// Removes and returns the member with the highest score.
+    pub fn pop_max(&mut self) -> Option<(Bytes, f64)> {
+        let (member, score) = match &self.members {
+            ZsetEncoding::ListPack(_) => self.iter().last(),
+            ZsetEncoding::SkipList { index, .. } => {
+                index.last().map(|(member, score)| (member.clone(), score))
+            }
+        }?;
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// Moves the members out of the listpack into the dictionary and skiplist.
+    fn convert(&mut self) {
+        let (mut scores, mut index) = (Dict::default(), SkipList::default());
+        for (member, score) in self.iter() {
+            scores.insert(member.clone(), score);
+            index.insert(score, member);
+        }
+        self.members = ZsetEncoding::SkipList { scores, index };
+    }
+}
+
+/// Member/score pairs of a listpack-encoded sorted set, with the byte offset of each member.
+fn packed(pack: &ListPack) -> impl Iterator<Item = (usize, &[u8], f64)> {
+    pack.pairs().map(|((offset, member), (_, score))| {
+        (offset, member, f64::from_le_bytes(score.try_into().expect("packed score")))
+    })
+}
+
+impl FromIterator<(Bytes, f64)> for SortedSet {
+    fn from_iter<I: IntoIterator<Item = (Bytes, f64)>>(iter: I) -> Self {
+        let mut zset = SortedSet::default();
+        for (member, score) in iter {
+            zset.insert(member, score);
+        }
+        zset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::SortedSet;
+
+    /// Members `m00`..`m{len}` with scores cycling through 0..5, so most scores are shared and
+    /// ties are broken by member; sorted like the set.
+    fn model(len: usize) -> Vec<(Bytes, f64)> {
+        let mut model: Vec<(Bytes, f64)> =
+            (0..len).map(|i| (Bytes::from(format!("m{i:03}")), (i % 5) as f64)).collect();
+        model.sort_by(|(m1, s1), (m2, s2)| s1.total_cmp(s2).then(m1.cmp(m2)));
+        model
+    }
+
+    fn zset(model: &[(Bytes, f64)]) -> SortedSet {
+        // Inserted back to front, so the order comes from the set and not from insertion.
+        model.iter().rev().cloned().collect()
+    }
+
+    #[test]
+    fn converts_past_the_listpack_entry_count_or_on_a_long_member() {
+        let model = model(129);
+        let mut zset = self::zset(&model[..128]);
+        assert!(!zset.insert(model[0].0.clone(), 10.0), "moving a member does not convert");
+        assert_eq!(zset.encoding(), "listpack");
+
+        assert!(zset.insert(model[128].0.clone(), model[128].1));
+        assert_eq!(zset.encoding(), "skiplist");
+        assert_eq!(zset.len(), 129);
+        assert_eq!(zset.score(&model[0].0), Some(10.0));
+        assert_eq!(zset.rank(&model[0].0), Some(128));
+
+        let mut zset = self::zset(&model[..3]);
+        let long = Bytes::from("x".repeat(65));
+        assert!(zset.insert(long.clone(), -1.0));
+        assert_eq!(zset.encoding(), "skiplist");
+        assert_eq!(zset.rank(&long), Some(0));
+        assert_eq!(zset.iter().skip(1).collect::<Vec<_>>(), model[..3]);
+    }
+
+    #[test]
+    fn ranks_and_ranges_follow_score_then_member_in_both_encodings() {
+        for (len, encoding) in [(20, "listpack"), (300, "skiplist")] {
+            let model = model(len);
+            let zset = zset(&model);
+            assert_eq!(zset.encoding(), encoding);
+
+            for (rank, (member, _)) in model.iter().enumerate() {
+                assert_eq!(zset.rank(member), Some(rank), "{encoding}: rank of {member:?}");
+            }
+            assert_eq!(zset.rank(&Bytes::from("missing")), None);
+
+            let (start, end) = (len / 4, len / 2);
+            let range: Vec<_> = zset.range(start, end).collect();
+            assert_eq!(range, model[start..=end], "{encoding}: range");
+            let reversed: Vec<_> = zset.range_rev(start, end).collect();
+            let expected: Vec<_> = model[start..=end].iter().rev().cloned().collect();
+            assert_eq!(reversed, expected, "{encoding}: reverse range");
+            assert_eq!(zset.range(len, len + 5).count(), 0);
+        }
+    }
+
+    #[test]
+    fn score_ranges_count_members_below_a_bound_in_both_encodings() {
+        for len in [20, 300] {
+            let model = model(len);
+            let zset = zset(&model);
+            for bound in [-1.0, 0.0, 2.0, 2.5, 4.0, f64::INFINITY] {
+                let below = model.iter().filter(|(_, score)| *score < bound).count();
+                assert_eq!(zset.count_while(|score, _| score < bound), below, "< {bound}");
+                let up_to = model.iter().filter(|(_, score)| *score <= bound).count();
+                assert_eq!(zset.count_while(|score, _| score <= bound), up_to, "<= {bound}");
+            }
+        }
+    }
+
+    #[test]
+    fn moving_and_removing_tied_members_keeps_the_order() {
+        for len in [20, 300] {
+            let mut model = model(len);
+            let mut zset = zset(&model);
+
+            // Moving a member to a shared score slots it in by member name.
+            let (member, _) = model.remove(len / 2);
+            assert!(!zset.insert(member.clone(), 1.0));
+            let at = model.partition_point(|(m, s)| *s < 1.0 || (*s == 1.0 && *m < member));
+            model.insert(at, (member.clone(), 1.0));
+            assert_eq!(zset.rank(&member), Some(at));
+
+            let (first_tied, _) = model.remove(model.iter().position(|(_, s)| *s == 1.0).unwrap());
+            assert!(zset.remove(&first_tied));
+            assert!(!zset.remove(&first_tied));
+            assert_eq!(zset.iter().collect::<Vec<_>>(), model);
+            assert_eq!(zset.remove_range(0, 2), 3);
+            model.drain(0..3);
+            assert_eq!(zset.pop_min(), Some(model.remove(0)));
+            assert_eq!(zset.pop_max(), model.pop());
+            assert_eq!(zset.iter().collect::<Vec<_>>(), model);
+        }
+    }
+}
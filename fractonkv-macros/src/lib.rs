@@ -13,6 +13,14 @@ struct KeySpecRange {
     limit: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct KeySpecKeyNum {
+    keynumidx: i64,
+    firstkey: i64,
+    step: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(default)]
 struct BeginSearchIndex {
@@ -22,13 +30,14 @@ struct BeginSearchIndex {
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(default)]
 struct BeginSearch {
-    index: BeginSearchIndex,
+    index: Option<BeginSearchIndex>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(default)]
 struct FindKeys {
-    range: KeySpecRange,
+    range: Option<KeySpecRange>,
+    keynum: Option<KeySpecKeyNum>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -56,6 +65,8 @@ struct Command {
     summary: Option<String>,
     arity: i8,
     #[serde(default)]
+    command_flags: Vec<String>,
+    #[serde(default)]
     key_specs: Vec<KeySpec>,
     #[serde(default)]
     arguments: Vec<ArgumentSpec>,
@@ -85,12 +96,41 @@ fn load_commands_from_folder(folder_path: &Path) -> Vec<(String, Command)> {
             .unwrap_or_else(|_| panic!("Failed to parse JSON in file {:?}", path));
 
         // Extend instead of pushing one by one
-        all_commands.extend(obj.into_iter());
+        all_commands.extend(obj);
     }
 
     all_commands
 }
 
+/// Converts an index-based key spec into a `crate::commands::KeySpec` literal. Positions are
+/// absolute argv indexes (the command name is 0); keyword-based specs are not supported.
+fn key_spec_tokens(spec: &KeySpec) -> Option<proc_macro2::TokenStream> {
+    let pos = spec.begin_search.as_ref()?.index.as_ref()?.pos;
+    let find_keys = spec.find_keys.as_ref()?;
+
+    if let Some(range) = &find_keys.range {
+        let first = pos as usize;
+        // A non-negative lastkey is relative to the first key, a negative one to the end of argv.
+        let last = if range.lastkey >= 0 {
+            (pos + range.lastkey) as isize
+        } else {
+            range.lastkey as isize
+        };
+        let step = range.step.max(1) as usize;
+        return Some(
+            quote! { crate::commands::KeySpec::Range { first: #first, last: #last, step: #step } },
+        );
+    }
+
+    let keynum = find_keys.keynum.as_ref()?;
+    let numkeys = (pos + keynum.keynumidx) as usize;
+    let first = (pos + keynum.firstkey) as usize;
+    let step = keynum.step.max(1) as usize;
+    Some(
+        quote! { crate::commands::KeySpec::KeyNum { numkeys: #numkeys, first: #first, step: #step } },
+    )
+}
+
 #[proc_macro_attribute]
 #[proc_macro_error]
 pub fn generate_command_kind(_: TokenStream, item: TokenStream) -> TokenStream {
@@ -101,6 +141,9 @@ pub fn generate_command_kind(_: TokenStream, item: TokenStream) -> TokenStream {
     let mut variants = Vec::new();
     let mut arity_matches = Vec::new();
     let mut desc_matches = Vec::new();
+    let mut name_matches = Vec::new();
+    let mut key_spec_matches = Vec::new();
+    let mut denyoom_matches = Vec::new();
 
     for (cmd_name, cmd) in commands {
         let ident_name = cmd_name.to_case(Case::Pascal);
@@ -113,6 +156,13 @@ pub fn generate_command_kind(_: TokenStream, item: TokenStream) -> TokenStream {
         variants.push(quote! { #ident });
         arity_matches.push(quote! { Self::#ident => #arity, });
         desc_matches.push(quote! { Self::#ident => #desc_lit, });
+        name_matches.push(quote! { Self::#ident => #cmd_name, });
+
+        let specs = cmd.key_specs.iter().filter_map(key_spec_tokens);
+        key_spec_matches.push(quote! { Self::#ident => &[#(#specs),*], });
+
+        let denyoom = cmd.command_flags.iter().any(|flag| flag == "DENYOOM");
+        denyoom_matches.push(quote! { Self::#ident => #denyoom, });
     }
 
     let input_enum = parse_macro_input!(item as ItemEnum);
@@ -121,6 +171,7 @@ pub fn generate_command_kind(_: TokenStream, item: TokenStream) -> TokenStream {
     let expanded = quote! {
         use strum_macros::EnumString;
         #[derive(Debug, Clone, Copy,EnumString, Hash, Eq, PartialEq)]
+        #[strum(serialize_all = "UPPERCASE")]
         pub enum #enum_ident {
             #(#variants),*
         }
@@ -137,6 +188,28 @@ pub fn generate_command_kind(_: TokenStream, item: TokenStream) -> TokenStream {
                     #(#desc_matches)*
                 }
             }
+
+            /// Upper-case command name as declared in its JSON spec.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#name_matches)*
+                }
+            }
+
+            /// Where the key arguments sit in the command line, as declared in its JSON spec.
+            pub fn key_specs(&self) -> &'static [crate::commands::KeySpec] {
+                match self {
+                    #(#key_spec_matches)*
+                }
+            }
+
+            /// Whether the command may grow memory use, i.e. carries the `DENYOOM` flag in its
+            /// JSON spec, and so is refused while the shard is over `maxmemory`.
+            pub fn is_denyoom(&self) -> bool {
+                match self {
+                    #(#denyoom_matches)*
+                }
+            }
         }
     };
 